@@ -1,33 +1,470 @@
-use crate::State;
-use reqwest::Client;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use iced::Task;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-enum Action {
-    None,
-    GetProduct,
-    Sale,
+use crate::training::Fake;
+use crate::transaction::Transaction;
+use crate::vat::Vat;
+use crate::{Message, State};
+
+fn endpoint(database_url: &str, path: &str) -> String {
+    let base = database_url.trim_end_matches('/');
+    match base.starts_with("http://") || base.starts_with("https://") {
+        true => format!("{}{}", base, path),
+        false => format!("http://{}{}", base, path),
+    }
 }
 
-impl State {
-    async fn add_product(&self, barcode: String, name: String, retail: u32) -> reqwest::Result<()> {
-        let json = json!({
-            "barcode": barcode,
-            "name": name,
-            "retail": retail,
-        });
-
-        let client = Client::new();
-        let _ = client
-            .post(self.setting.database_url.clone())
-            .json(&json)
+/// Where api calls go: the backend at a URL, or the fake one in training.
+#[derive(Clone, Debug)]
+pub enum Backend {
+    Http(String),
+    Fake(Fake),
+}
+
+/// How long a health check waits before calling the server unreachable.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long any other call waits for the server.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Health checks slower than this count as degraded.
+const SLOW: Duration = Duration::from_secs(1);
+
+/// Failed health checks in a row before the backend counts as offline.
+const OFFLINE_AFTER: u32 = 3;
+
+/// Tries of an idempotent call before its error is returned.
+const ATTEMPTS: u32 = 4;
+const BASE_DELAY: Duration = Duration::from_millis(250);
+const MAX_DELAY: Duration = Duration::from_secs(4);
+
+fn client() -> Client {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Wait before retry number `attempt`, doubling from [`BASE_DELAY`] up to
+/// [`MAX_DELAY`], of which a random half is taken off by `jitter` so tills
+/// don't retry in step.
+pub fn backoff(attempt: u32, jitter: u64) -> Duration {
+    let ceiling = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_DELAY);
+    let half = ceiling.as_millis() as u64 / 2;
+    Duration::from_millis(half + jitter % (half + 1))
+}
+
+/// Errors worth another try: the server was out of reach, slow or failing,
+/// rather than refusing the request.
+fn retryable(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+        || error.is_connect()
+        || error
+            .status()
+            .is_some_and(|status| status.is_server_error())
+}
+
+/// Runs `call` until it succeeds, fails for good or runs out of
+/// [`ATTEMPTS`]. Only for calls that can safely be repeated.
+async fn retry<T, F, Fut>(mut call: F) -> reqwest::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = reqwest::Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match call().await {
+            Err(error) if retryable(&error) && attempt + 1 < ATTEMPTS => {
+                let jitter = RandomState::new().build_hasher().finish();
+                tokio::time::sleep(backoff(attempt, jitter)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// `input` as a backend base URL without the trailing slash, if it is an
+/// `http` or `https` URL with a host.
+pub fn parse_url(input: &str) -> Result<String, &'static str> {
+    let url = Url::parse(input.trim()).map_err(|_| "รูปแบบ URL ไม่ถูกต้อง")?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("URL ต้องขึ้นต้นด้วย http:// หรือ https://");
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err("URL ไม่มีชื่อเซิร์ฟเวอร์");
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Health {
+    pub latency: Duration,
+    pub version: Option<String>,
+}
+
+/// How the backend is doing, from the latest health checks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    Online,
+    /// Answering slowly, or missed a check or two.
+    Degraded,
+    Offline,
+}
+
+impl Connectivity {
+    /// From the last health check and how many in a row have failed.
+    pub fn of(last: &Result<Health, Failure>, failures: u32) -> Self {
+        match last {
+            Ok(health) if health.latency > SLOW => Connectivity::Degraded,
+            Ok(_) => Connectivity::Online,
+            Err(_) if failures < OFFLINE_AFTER => Connectivity::Degraded,
+            Err(_) => Connectivity::Offline,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    Timeout,
+    /// Nothing answered at the address.
+    Unreachable,
+    Status(u16),
+    /// Something answered, but not with the backend's health response.
+    NotBackend,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Timeout => f.write_str("เซิร์ฟเวอร์ไม่ตอบสนองภายในเวลาที่กำหนด"),
+            Failure::Unreachable => f.write_str("เชื่อมต่อเซิร์ฟเวอร์ไม่ได้"),
+            Failure::Status(code) => write!(f, "เซิร์ฟเวอร์ตอบกลับรหัส {}", code),
+            Failure::NotBackend => f.write_str("ไม่ใช่เซิร์ฟเวอร์ของระบบ"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HealthResponse {
+    #[serde(default)]
+    version: Option<String>,
+}
+
+/// Probes `GET /health`, timing the round trip.
+pub async fn health(database_url: String) -> Result<Health, Failure> {
+    let failure = |error: reqwest::Error| match error.status() {
+        Some(status) => Failure::Status(status.as_u16()),
+        None if error.is_timeout() => Failure::Timeout,
+        None if error.is_decode() => Failure::NotBackend,
+        None => Failure::Unreachable,
+    };
+    let client = Client::builder()
+        .timeout(HEALTH_TIMEOUT)
+        .build()
+        .map_err(failure)?;
+    let start = Instant::now();
+    let response = client
+        .get(endpoint(&database_url, "/health"))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(failure)?;
+    let latency = start.elapsed();
+    let body: HealthResponse = response.json().await.map_err(failure)?;
+    Ok(Health {
+        latency,
+        version: body.version,
+    })
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Product {
+    pub barcode: String,
+    pub name: String,
+    pub retail: u32,
+    pub vat: Vat,
+    /// Units on hand.
+    #[serde(default)]
+    pub stock: i64,
+}
+
+/// Products changed since a cursor, and the cursor they bring the client
+/// up to.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Changes {
+    pub cursor: u64,
+    #[serde(default)]
+    pub products: Vec<Product>,
+    /// Barcodes of products taken out of the catalogue.
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+/// `GET /products/changes?since=`, everything when `since` is 0.
+pub async fn get_changes(database_url: String, since: u64) -> reqwest::Result<Changes> {
+    let client = client();
+    retry(|| async {
+        client
+            .get(endpoint(&database_url, "/products/changes"))
+            .query(&[("since", since)])
             .send()
-            .await?;
-        Ok(())
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    })
+    .await
+}
+
+/// `POST /products`, replacing the product with the same barcode.
+pub async fn add_product(
+    backend: Backend,
+    barcode: String,
+    name: String,
+    retail: u32,
+    vat: Vat,
+) -> reqwest::Result<()> {
+    let database_url = match backend {
+        Backend::Http(url) => url,
+        Backend::Fake(fake) => {
+            fake.with(|fake| {
+                fake.add_product(Product {
+                    barcode,
+                    name,
+                    retail,
+                    vat,
+                    stock: 0,
+                })
+            });
+            return Ok(());
+        }
+    };
+    let json = json!({
+        "barcode": barcode,
+        "name": name,
+        "retail": retail,
+        "vat": vat,
+    });
+
+    let client = client();
+    client
+        .post(endpoint(&database_url, "/products"))
+        .json(&json)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Puts returned goods back on hand as a `return` stock movement.
+pub async fn return_stock(backend: Backend, barcode: String, amount: u32) -> reqwest::Result<()> {
+    let database_url = match backend {
+        Backend::Http(url) => url,
+        Backend::Fake(fake) => {
+            fake.with(|fake| fake.move_stock(&barcode, amount as i64));
+            return Ok(());
+        }
+    };
+    let json = json!({
+        "barcode": barcode,
+        "amount": amount,
+        "kind": "return",
+    });
+
+    let client = client();
+    client
+        .post(endpoint(&database_url, "/stock/movements"))
+        .json(&json)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Asks the stock team to check the shelf label of `barcode`, which was
+/// charged at `price` instead of the system price.
+pub async fn post_price_check(
+    backend: Backend,
+    barcode: String,
+    price: u32,
+) -> reqwest::Result<()> {
+    let database_url = match backend {
+        Backend::Http(url) => url,
+        Backend::Fake(fake) => {
+            fake.with(|fake| fake.price_check(barcode, price));
+            return Ok(());
+        }
+    };
+    let json = json!({
+        "barcode": barcode,
+        "price": price,
+    });
+
+    let client = client();
+    client
+        .post(endpoint(&database_url, "/stock/price-checks"))
+        .json(&json)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+pub async fn post_transaction(backend: Backend, transaction: Transaction) -> reqwest::Result<()> {
+    let database_url = match backend {
+        Backend::Http(url) => url,
+        Backend::Fake(fake) => {
+            fake.with(|fake| fake.transaction(transaction));
+            return Ok(());
+        }
+    };
+    let client = client();
+    client
+        .post(endpoint(&database_url, "/transactions"))
+        .json(&transaction)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+impl State {
+    /// The fake backend while training, otherwise the saved one.
+    pub fn backend(&self) -> Backend {
+        match &self.training {
+            Some(training) => Backend::Fake(training.backend.clone()),
+            None => Backend::Http(self.setting.database_url().to_string()),
+        }
     }
 
-    async fn delete_product(&self, barcode: String) {}
-    async fn stock_product(&self) {}
-    async fn get_all_product(&self) {}
-    async fn get_price(&self) {}
-    async fn sell(&self) {}
+    /// Fetches product changes since `since` into the catalogue. Training
+    /// keeps to its snapshot.
+    pub fn sync_products(&self, since: u64) -> Task<Message> {
+        let url = self.setting.database_url().to_string();
+        if url.is_empty() || self.training.is_some() {
+            return Task::none();
+        }
+        Task::perform(get_changes(url.clone(), since), move |result| {
+            Message::Synced(
+                url.clone(),
+                since,
+                result.map_err(|error| error.to_string()),
+            )
+        })
+    }
+
+    /// State of the saved backend, `None` until a health check finishes.
+    pub fn connectivity(&self) -> Option<Connectivity> {
+        self.connection
+            .as_ref()
+            .map(|last| Connectivity::of(last, self.failures))
+    }
+
+    /// Health check of the saved backend, updating the connection status.
+    pub fn check_connection(&self) -> Task<Message> {
+        let url = self.setting.database_url().to_string();
+        if url.is_empty() {
+            return Task::none();
+        }
+        Task::perform(health(url.clone()), move |result| {
+            Message::Health(url.clone(), result)
+        })
+    }
+
+    pub fn send_transaction(&self, transaction: Transaction) -> Task<Message> {
+        Task::perform(post_transaction(self.backend(), transaction), Message::api)
+    }
+
+    pub fn send_price_check(&self, barcode: String, price: u32) -> Task<Message> {
+        Task::perform(
+            post_price_check(self.backend(), barcode, price),
+            Message::api,
+        )
+    }
+
+    /// Restocks every returned line, then records the refund itself.
+    pub fn send_refund(&self, transaction: Transaction) -> Task<Message> {
+        let returns: Vec<_> = transaction
+            .items
+            .iter()
+            .map(|item| {
+                Task::perform(
+                    return_stock(
+                        self.backend(),
+                        item.barcode.clone(),
+                        item.amount.parse::<u32>().unwrap_or(0),
+                    ),
+                    Message::api,
+                )
+            })
+            .collect();
+        Task::batch(
+            returns
+                .into_iter()
+                .chain([self.send_transaction(transaction)]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_backend_url() {
+        assert_eq!(
+            parse_url(" http://192.168.1.10:3000/ "),
+            Ok("http://192.168.1.10:3000".to_string())
+        );
+        assert_eq!(
+            parse_url("https://pos.example.com/api"),
+            Ok("https://pos.example.com/api".to_string())
+        );
+        assert!(parse_url("192.168.1.10:3000").is_err());
+        assert!(parse_url("ftp://pos.example.com").is_err());
+        assert!(parse_url("").is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter() {
+        assert_eq!(backoff(0, 0), Duration::from_millis(125));
+        assert_eq!(backoff(0, 125), Duration::from_millis(250));
+        assert_eq!(backoff(2, 0), Duration::from_millis(500));
+        assert_eq!(backoff(10, 0), Duration::from_secs(2));
+        for jitter in [1, 999, u64::MAX] {
+            let delay = backoff(3, jitter);
+            assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn connectivity_from_health_checks() {
+        let health = |millis| {
+            Ok(Health {
+                latency: Duration::from_millis(millis),
+                version: None,
+            })
+        };
+        assert_eq!(Connectivity::of(&health(40), 0), Connectivity::Online);
+        assert_eq!(Connectivity::of(&health(1500), 0), Connectivity::Degraded);
+        assert_eq!(
+            Connectivity::of(&Err(Failure::Timeout), 1),
+            Connectivity::Degraded
+        );
+        assert_eq!(
+            Connectivity::of(&Err(Failure::Unreachable), 3),
+            Connectivity::Offline
+        );
+    }
 }
@@ -0,0 +1,431 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::store::read_lines;
+use crate::user::Role;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum OverrideReason {
+    ShelfPrice,
+    MissingPromotion,
+    Damaged,
+    Other,
+}
+
+impl OverrideReason {
+    pub const ALL: [OverrideReason; 4] = [
+        OverrideReason::ShelfPrice,
+        OverrideReason::MissingPromotion,
+        OverrideReason::Damaged,
+        OverrideReason::Other,
+    ];
+}
+
+impl fmt::Display for OverrideReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OverrideReason::ShelfPrice => "ราคาตามป้าย",
+            OverrideReason::MissingPromotion => "โปรโมชั่นไม่ขึ้น",
+            OverrideReason::Damaged => "สินค้าชำรุด",
+            OverrideReason::Other => "อื่นๆ",
+        })
+    }
+}
+
+/// Approvers are left out when there are none, so entries logged before
+/// they were kept still hash the same.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub enum Action {
+    PriceOverride {
+        barcode: String,
+        old: u32,
+        new: u32,
+        reason: OverrideReason,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        approver: Option<String>,
+    },
+    /// A line removed from a sale before payment.
+    VoidLine {
+        barcode: String,
+        amount: String,
+        sum: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        approver: Option<String>,
+    },
+    /// A whole sale abandoned before payment.
+    VoidSale {
+        items: usize,
+        total: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        approver: Option<String>,
+    },
+    Refund {
+        receipt: u32,
+        total: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        approver: Option<String>,
+    },
+    /// Manual discounts on a sale let go over the limit.
+    Discount {
+        total: u32,
+        limit: u32,
+        approver: String,
+    },
+    /// A drawer session opened with its float.
+    DrawerOpen {
+        cashier: String,
+        float: u32,
+    },
+    /// A drawer session closed on its blind count.
+    DrawerClose {
+        cashier: String,
+        expected: i64,
+        counted: u32,
+    },
+    /// Cash put into or taken out of the drawer outside a sale.
+    CashIn {
+        amount: u32,
+        note: String,
+    },
+    CashOut {
+        amount: u32,
+        note: String,
+    },
+    SettingChange,
+    UserAdded {
+        name: String,
+        role: Role,
+    },
+    CreditOpened {
+        name: String,
+        limit: u32,
+    },
+    CreditLimit {
+        name: String,
+        old: u32,
+        new: u32,
+    },
+    /// Cash taken against what a credit account owes.
+    Repayment {
+        name: String,
+        amount: u32,
+    },
+}
+
+impl Action {
+    /// Labels of every kind of action, for filtering the log.
+    pub const LABELS: [&'static str; 14] = [
+        "แก้ราคา",
+        "ยกเลิกรายการ",
+        "ยกเลิกบิล",
+        "คืนสินค้า",
+        "เปิดลิ้นชัก",
+        "ปิดลิ้นชัก",
+        "นำเงินเข้า",
+        "นำเงินออก",
+        "แก้ไขการตั้งค่า",
+        "เพิ่มผู้ใช้",
+        "เปิดบัญชีค้างจ่าย",
+        "เปลี่ยนวงเงิน",
+        "รับชำระค้างจ่าย",
+        "ส่วนลดเกินวงเงิน",
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::PriceOverride { .. } => Action::LABELS[0],
+            Action::VoidLine { .. } => Action::LABELS[1],
+            Action::VoidSale { .. } => Action::LABELS[2],
+            Action::Refund { .. } => Action::LABELS[3],
+            Action::DrawerOpen { .. } => Action::LABELS[4],
+            Action::DrawerClose { .. } => Action::LABELS[5],
+            Action::CashIn { .. } => Action::LABELS[6],
+            Action::CashOut { .. } => Action::LABELS[7],
+            Action::SettingChange => Action::LABELS[8],
+            Action::UserAdded { .. } => Action::LABELS[9],
+            Action::CreditOpened { .. } => Action::LABELS[10],
+            Action::CreditLimit { .. } => Action::LABELS[11],
+            Action::Repayment { .. } => Action::LABELS[12],
+            Action::Discount { .. } => Action::LABELS[13],
+        }
+    }
+
+    /// Who let it through, where it needed more than a cashier.
+    pub fn approver(&self) -> Option<&str> {
+        match self {
+            Action::PriceOverride { approver, .. }
+            | Action::VoidLine { approver, .. }
+            | Action::VoidSale { approver, .. }
+            | Action::Refund { approver, .. } => approver.as_deref(),
+            Action::Discount { approver, .. } => Some(approver),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::PriceOverride {
+                barcode,
+                old,
+                new,
+                reason,
+                ..
+            } => write!(
+                f,
+                "{} {} จาก {} เป็น {} ({})",
+                self.label(),
+                barcode,
+                old,
+                new,
+                reason
+            ),
+            Action::VoidLine {
+                barcode,
+                amount,
+                sum,
+                ..
+            } => write!(f, "{} {} x{} ({})", self.label(), barcode, amount, sum),
+            Action::VoidSale { items, total, .. } => {
+                write!(f, "{} {} รายการ ({})", self.label(), items, total)
+            }
+            Action::Refund { receipt, total, .. } => {
+                write!(f, "{} ใบเสร็จ {} ({})", self.label(), receipt, total)
+            }
+            Action::Discount { total, limit, .. } => {
+                write!(f, "{} {} (วงเงิน {})", self.label(), total, limit)
+            }
+            Action::DrawerOpen { cashier, float } => {
+                write!(f, "{} {} เงินทอน {}", self.label(), cashier, float)
+            }
+            Action::DrawerClose {
+                cashier,
+                expected,
+                counted,
+            } => write!(
+                f,
+                "{} {} นับได้ {} ควรมี {} ({:+})",
+                self.label(),
+                cashier,
+                counted,
+                expected,
+                *counted as i64 - expected
+            ),
+            Action::CashIn { amount, note } | Action::CashOut { amount, note } => {
+                write!(f, "{} {} ({})", self.label(), amount, note)
+            }
+            Action::SettingChange => f.write_str(self.label()),
+            Action::UserAdded { name, role } => write!(f, "{} {} ({})", self.label(), name, role),
+            Action::CreditOpened { name, limit } => {
+                write!(f, "{} {} วงเงิน {}", self.label(), name, limit)
+            }
+            Action::CreditLimit { name, old, new } => {
+                write!(f, "{} {} จาก {} เป็น {}", self.label(), name, old, new)
+            }
+            Action::Repayment { name, amount } => {
+                write!(f, "{} {} ({})", self.label(), name, amount)
+            }
+        }?;
+        match self.approver() {
+            Some(approver) => write!(f, " อนุมัติโดย {}", approver),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Something done at the till that someone may need to answer for later.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub time: NaiveDateTime,
+    pub user: String,
+    pub action: Action,
+    /// SHA-256 of the previous entry's hash and this entry, in hex. Editing
+    /// or removing any entry breaks every hash after it.
+    pub hash: String,
+}
+
+fn chain(previous: &str, time: &NaiveDateTime, user: &str, action: &Action) -> String {
+    let contents = serde_json::to_vec(&(previous, time, user, action)).unwrap_or_default();
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Adds an entry chained to the last one and returns it.
+pub fn append(log: &mut Vec<Entry>, time: NaiveDateTime, user: String, action: Action) -> Entry {
+    let previous = log.last().map(|entry| entry.hash.as_str()).unwrap_or("");
+    let entry = Entry {
+        hash: chain(previous, &time, &user, &action),
+        time,
+        user,
+        action,
+    };
+    log.push(entry.clone());
+    entry
+}
+
+/// Index of the first entry that doesn't match its hash, if any.
+pub fn verify(log: &[Entry]) -> Result<(), usize> {
+    let mut previous = "";
+    for (index, entry) in log.iter().enumerate() {
+        if entry.hash != chain(previous, &entry.time, &entry.user, &entry.action) {
+            return Err(index);
+        }
+        previous = &entry.hash;
+    }
+    Ok(())
+}
+
+/// Reads the log, with why any of it couldn't be read. Entries after a line
+/// that doesn't parse are kept, so new ones still chain onto the last.
+pub fn get_audit(dir: &Path) -> (Vec<Entry>, Option<String>) {
+    read_lines(&dir.join("audit.jsonl"))
+}
+
+/// A CSV field, quoted so commas, quotes and line breaks stay inside it.
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Writes `entries` as CSV to `path`, returning where for display.
+pub async fn export(entries: Vec<Entry>, path: PathBuf) -> Result<String, String> {
+    let mut csv = "เวลา,ผู้ใช้,รายการ,รายละเอียด\n".to_string();
+    for entry in entries {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            entry.time.format("%d/%m/%Y %H:%M:%S"),
+            quote(&entry.user),
+            entry.action.label(),
+            quote(&entry.action.to_string())
+        ));
+    }
+    std::fs::write(&path, csv).map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn time(minute: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2025, 6, 1)
+            .and_then(|date| date.and_hms_opt(9, minute, 0))
+            .unwrap()
+    }
+
+    fn log() -> Vec<Entry> {
+        let mut log = Vec::new();
+        append(
+            &mut log,
+            time(0),
+            "สมชาย".to_string(),
+            Action::SettingChange,
+        );
+        append(
+            &mut log,
+            time(5),
+            "สมชาย".to_string(),
+            Action::VoidSale {
+                items: 2,
+                total: 120,
+                approver: None,
+            },
+        );
+        append(
+            &mut log,
+            time(9),
+            "สมศรี".to_string(),
+            Action::Refund {
+                receipt: 1,
+                total: 20,
+                approver: Some("สมศรี".to_string()),
+            },
+        );
+        log
+    }
+
+    #[test]
+    fn chain_verifies() {
+        let log = log();
+        assert_eq!(log[0].hash.len(), 64);
+        assert_ne!(log[0].hash, log[1].hash);
+        assert_eq!(verify(&log), Ok(()));
+    }
+
+    #[test]
+    fn tampering_detected() {
+        let mut edited = log();
+        edited[1].action = Action::VoidSale {
+            items: 2,
+            total: 12,
+            approver: None,
+        };
+        assert_eq!(verify(&edited), Err(1));
+
+        let mut removed = log();
+        removed.remove(1);
+        assert_eq!(verify(&removed), Err(1));
+
+        let mut reordered = log();
+        reordered.swap(1, 2);
+        assert_eq!(verify(&reordered), Err(1));
+    }
+
+    #[test]
+    fn entry_without_approver_still_verifies() {
+        let log = log();
+        let line = serde_json::to_string(&log[1]).unwrap();
+        assert!(!line.contains("approver"));
+        let read: Entry = serde_json::from_str(&line).unwrap();
+        assert_eq!(read, log[1]);
+        assert_eq!(log[2].action.approver(), Some("สมศรี"));
+        assert!(log[2].action.to_string().ends_with("อนุมัติโดย สมศรี"));
+    }
+
+    #[test]
+    fn unparsable_line_is_reported() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-audit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let lines: Vec<String> = log()
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        let contents = format!("{}\n{{\"time\": \n{}\n", lines[0], lines[1]);
+        std::fs::write(dir.join("audit.jsonl"), contents).unwrap();
+
+        let (read, error) = get_audit(&dir);
+        assert_eq!(read, log()[..2]);
+        assert!(error.unwrap().ends_with(&format!(
+            "audit.jsonl:2: {}",
+            serde_json::from_str::<Entry>("{\"time\": ").unwrap_err()
+        )));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn export_quotes_user() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut log = Vec::new();
+        append(
+            &mut log,
+            time(0),
+            "สมชาย, \"เล็ก\"".to_string(),
+            Action::SettingChange,
+        );
+        let path = dir.join("audit.csv");
+
+        iced::futures::executor::block_on(export(log, path.clone())).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap().lines().nth(1),
+            Some("01/06/2025 09:00:00,\"สมชาย, \"\"เล็ก\"\"\",แก้ไขการตั้งค่า,\"แก้ไขการตั้งค่า\"")
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
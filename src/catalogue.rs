@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Changes, Product};
+use crate::store::Write;
+
+/// Products kept on the till, so scans don't wait on the network and still
+/// work offline. Synced from the backend by its change cursor.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct Catalogue {
+    /// Backend cursor the cache is up to, 0 before the first sync.
+    pub cursor: u64,
+    pub synced: Option<DateTime<Local>>,
+    /// Products by barcode.
+    pub products: BTreeMap<String, Product>,
+}
+
+impl Catalogue {
+    pub fn product(&self, barcode: &str) -> Option<&Product> {
+        self.products.get(barcode)
+    }
+
+    /// Applies `changes` fetched since cursor `since`, where 0 asks for
+    /// everything and replaces the cache. Changes since a cursor the cache
+    /// has moved on from are dropped, returning `false`.
+    pub fn apply(&mut self, since: u64, changes: Changes, now: DateTime<Local>) -> bool {
+        if since == 0 {
+            self.products.clear();
+        } else if since != self.cursor {
+            return false;
+        }
+        for product in changes.products {
+            self.products.insert(product.barcode.clone(), product);
+        }
+        for barcode in &changes.removed {
+            self.products.remove(barcode);
+        }
+        self.cursor = changes.cursor;
+        self.synced = Some(now);
+        true
+    }
+}
+
+pub fn get_catalogue(dir: &Path) -> std::io::Result<Catalogue> {
+    let mut file = File::open(dir.join("products.json"))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let catalogue: Catalogue = serde_json::from_str(&contents)?;
+    Ok(catalogue)
+}
+
+/// `file` queued for `products.json`.
+pub async fn save_catalogue(catalogue: Catalogue, file: Write) -> Result<(), String> {
+    let catalogue = serde_json::to_vec(&catalogue).map_err(|error| error.to_string())?;
+    file.replace(&catalogue)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vat::Vat;
+
+    fn product(barcode: &str, retail: u32) -> Product {
+        Product {
+            barcode: barcode.to_string(),
+            name: "น้ำดื่ม".to_string(),
+            retail,
+            vat: Vat::Inclusive,
+            stock: 0,
+        }
+    }
+
+    fn changes(cursor: u64, products: Vec<Product>, removed: &[&str]) -> Changes {
+        Changes {
+            cursor,
+            products,
+            removed: removed.iter().map(|barcode| barcode.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn incremental_sync() {
+        let now = Local::now();
+        let mut catalogue = Catalogue::default();
+        let all = changes(10, vec![product("885", 7), product("886", 12)], &[]);
+        assert!(catalogue.apply(0, all, now));
+
+        let update = changes(12, vec![product("885", 8)], &["886"]);
+        assert!(catalogue.apply(10, update, now));
+        assert_eq!(catalogue.cursor, 12);
+        assert_eq!(catalogue.product("885"), Some(&product("885", 8)));
+        assert_eq!(catalogue.product("886"), None);
+
+        // An answer to an older cursor arriving late
+        let stale = changes(11, vec![product("886", 12)], &[]);
+        assert!(!catalogue.apply(10, stale, now));
+        assert_eq!(catalogue.cursor, 12);
+        assert_eq!(catalogue.product("886"), None);
+    }
+
+    #[test]
+    fn resync_replaces_everything() {
+        let now = Local::now();
+        let mut catalogue = Catalogue::default();
+        catalogue.apply(0, changes(5, vec![product("885", 7)], &[]), now);
+        catalogue.apply(0, changes(9, vec![product("886", 12)], &[]), now);
+
+        assert_eq!(catalogue.cursor, 9);
+        assert_eq!(catalogue.products.keys().collect::<Vec<_>>(), vec!["886"]);
+    }
+}
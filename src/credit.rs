@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::store::Write;
+
+/// Age brackets of the aging report, by days since the sale.
+pub const AGING: [&str; 4] = ["0-30 วัน", "31-60 วัน", "61-90 วัน", "เกิน 90 วัน"];
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum EntryKind {
+    /// A sale charged to the account.
+    Charge {
+        receipt: u32,
+    },
+    Payment,
+    /// Goods returned from a charged sale, taken off that charge.
+    Refund {
+        receipt: u32,
+    },
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub date: NaiveDate,
+    pub kind: EntryKind,
+    pub amount: u32,
+}
+
+/// A customer allowed to buy on credit and settle later.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Account {
+    pub name: String,
+    pub limit: u32,
+    pub entries: Vec<Entry>,
+}
+
+impl Account {
+    pub fn open(name: String, limit: u32) -> Self {
+        Account {
+            name,
+            limit,
+            entries: Vec::new(),
+        }
+    }
+
+    fn total(&self, kind: fn(&EntryKind) -> bool) -> u32 {
+        self.entries
+            .iter()
+            .filter(|entry| kind(&entry.kind))
+            .map(|entry| entry.amount)
+            .sum()
+    }
+
+    /// Amount owed.
+    pub fn balance(&self) -> u32 {
+        self.total(|kind| matches!(kind, EntryKind::Charge { .. }))
+            .saturating_sub(self.total(|kind| !matches!(kind, EntryKind::Charge { .. })))
+    }
+
+    /// Whether the sale numbered `receipt` was charged to this account.
+    pub fn charged(&self, receipt: u32) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.kind == EntryKind::Charge { receipt })
+    }
+
+    /// Credit left before the limit is reached.
+    pub fn available(&self) -> u32 {
+        self.limit.saturating_sub(self.balance())
+    }
+
+    pub fn charge(&mut self, date: NaiveDate, receipt: u32, amount: u32) {
+        self.entries.push(Entry {
+            date,
+            kind: EntryKind::Charge { receipt },
+            amount,
+        });
+    }
+
+    pub fn refund(&mut self, date: NaiveDate, receipt: u32, amount: u32) {
+        self.entries.push(Entry {
+            date,
+            kind: EntryKind::Refund { receipt },
+            amount,
+        });
+    }
+
+    pub fn pay(&mut self, date: NaiveDate, amount: u32) {
+        self.entries.push(Entry {
+            date,
+            kind: EntryKind::Payment,
+            amount,
+        });
+    }
+
+    /// Every entry with the balance owed after it.
+    pub fn statement(&self) -> Vec<(&Entry, u32)> {
+        let mut balance = 0u32;
+        self.entries
+            .iter()
+            .map(|entry| {
+                balance = match entry.kind {
+                    EntryKind::Charge { .. } => balance + entry.amount,
+                    EntryKind::Payment | EntryKind::Refund { .. } => {
+                        balance.saturating_sub(entry.amount)
+                    }
+                };
+                (entry, balance)
+            })
+            .collect()
+    }
+
+    /// Outstanding balance per bracket of [`AGING`]. Refunds come off their
+    /// own charge and payments settle the oldest charges first.
+    pub fn aging(&self, today: NaiveDate) -> [u32; 4] {
+        let mut paid = self.total(|kind| *kind == EntryKind::Payment);
+        let mut charges: Vec<(&Entry, u32)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| match entry.kind {
+                EntryKind::Charge { receipt } => {
+                    let refunded = self.total_refunded(receipt);
+                    Some((entry, entry.amount.saturating_sub(refunded)))
+                }
+                _ => None,
+            })
+            .collect();
+        charges.sort_by_key(|(entry, _)| entry.date);
+
+        let mut brackets = [0; 4];
+        for (charge, amount) in charges {
+            let settled = paid.min(amount);
+            paid -= settled;
+            let bracket = match (today - charge.date).num_days() {
+                ..=30 => 0,
+                31..=60 => 1,
+                61..=90 => 2,
+                _ => 3,
+            };
+            brackets[bracket] += amount - settled;
+        }
+        brackets
+    }
+
+    fn total_refunded(&self, receipt: u32) -> u32 {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == EntryKind::Refund { receipt })
+            .map(|entry| entry.amount)
+            .sum()
+    }
+}
+
+pub fn find<'a>(accounts: &'a [Account], name: &str) -> Option<&'a Account> {
+    accounts.iter().find(|account| account.name == name)
+}
+
+pub fn get_accounts(dir: &Path) -> std::io::Result<Vec<Account>> {
+    let mut file = File::open(dir.join("accounts.json"))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let accounts: Vec<Account> = serde_json::from_str(&contents)?;
+    Ok(accounts)
+}
+
+/// `file` queued for `accounts.json`.
+pub async fn save_accounts(accounts: Vec<Account>, file: Write) -> Result<(), String> {
+    let accounts = serde_json::to_vec(&accounts).map_err(|error| error.to_string())?;
+    file.replace(&accounts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn statement_running_balance() {
+        let mut account = Account::open("ป้าศรี".to_string(), 1000);
+        account.charge(date("2025-05-01"), 1, 300);
+        account.charge(date("2025-05-03"), 4, 200);
+        account.pay(date("2025-05-10"), 350);
+
+        let balances: Vec<u32> = account
+            .statement()
+            .iter()
+            .map(|(_, balance)| *balance)
+            .collect();
+        assert_eq!(balances, vec![300, 500, 150]);
+        assert_eq!(account.balance(), 150);
+        assert_eq!(account.available(), 850);
+    }
+
+    #[test]
+    fn aging_settles_oldest_first() {
+        let mut account = Account::open("ลุงมี".to_string(), 5000);
+        account.charge(date("2025-01-10"), 1, 400);
+        account.charge(date("2025-03-20"), 2, 300);
+        account.charge(date("2025-05-25"), 3, 100);
+        account.pay(date("2025-05-30"), 250);
+
+        assert_eq!(account.aging(date("2025-06-01")), [100, 0, 300, 150]);
+
+        // A refund clears its own charge, not the oldest
+        account.refund(date("2025-05-31"), 2, 300);
+        assert_eq!(account.aging(date("2025-06-01")), [100, 0, 0, 150]);
+        assert_eq!(account.balance(), 250);
+    }
+}
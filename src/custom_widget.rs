@@ -1,9 +1,10 @@
 use iced::font::Family;
-use iced::widget::{self, container, Button, Container, Text, TextInput};
+use iced::widget::{self, container, Button, Column, Container, Row, Space, Text, TextInput};
 use iced::Alignment::Center;
 use iced::Length::Fill;
-use iced::{Element, Font, Pixels, Theme};
+use iced::{Color, Font, Pixels, Theme};
 
+use crate::promptpay::QrCode;
 use crate::Message;
 
 pub fn thai_font() -> Font {
@@ -57,3 +58,21 @@ pub fn button<'a>(
 ) -> Button<'a, Message> {
     widget::button(text(content, font_size))
 }
+
+pub fn qr_code<'a>(qr: &QrCode, cell_size: u16) -> Container<'a, Message> {
+    let rows = qr.dark.chunks(qr.width).map(|modules| {
+        Row::with_children(modules.iter().map(|dark| {
+            let color = match dark {
+                true => Color::BLACK,
+                false => Color::WHITE,
+            };
+            container(Space::new(cell_size, cell_size))
+                .style(move |_| container::background(color))
+                .into()
+        }))
+        .into()
+    });
+    container(Column::with_children(rows))
+        .padding(cell_size * 4)
+        .style(|_| container::background(Color::WHITE))
+}
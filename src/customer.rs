@@ -0,0 +1,131 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::Write;
+use crate::transaction::{Kind, PaymentMethod, Transaction};
+
+/// A member, keyed by phone number.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Customer {
+    pub phone: String,
+    pub name: String,
+    pub points: u32,
+}
+
+/// Phone number as stored, with spaces and dashes dropped.
+pub fn normalize_phone(phone: &str) -> Option<String> {
+    let digits: String = phone.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+    match digits.len() == 10
+        && digits.starts_with('0')
+        && digits.chars().all(|c| c.is_ascii_digit())
+    {
+        true => Some(digits),
+        false => None,
+    }
+}
+
+pub fn find<'a>(customers: &'a [Customer], phone: &str) -> Option<&'a Customer> {
+    let phone = normalize_phone(phone)?;
+    customers.iter().find(|customer| customer.phone == phone)
+}
+
+/// Points earned on a sale; baht paid with points earns nothing.
+pub fn earned(transaction: &Transaction, baht_per_point: u32) -> u32 {
+    let paid = transaction.total - redeemed(transaction);
+    paid.checked_div(baht_per_point).unwrap_or(0)
+}
+
+/// Points spent as a tender on a sale.
+pub fn redeemed(transaction: &Transaction) -> u32 {
+    transaction
+        .tenders
+        .iter()
+        .filter(|tender| tender.method == PaymentMethod::Points)
+        .map(|tender| tender.amount)
+        .sum()
+}
+
+/// Points to take back for `refund` baht returned on `sale`, after
+/// `refunded` baht returned before: the points earned in proportion, so
+/// returning everything takes back exactly what the sale earned.
+pub fn unearned(sale: &Transaction, refunded: u32, refund: u32, baht_per_point: u32) -> u32 {
+    let earned = earned(sale, baht_per_point) as u64;
+    let share = |returned: u32| match sale.total {
+        0 => 0,
+        total => (earned * returned as u64 / total as u64) as u32,
+    };
+    share(refunded + refund) - share(refunded)
+}
+
+/// Sales made to `phone`, most recent first.
+pub fn history<'a>(transactions: &'a [Transaction], phone: &str) -> Vec<&'a Transaction> {
+    transactions
+        .iter()
+        .rev()
+        .filter(|transaction| {
+            transaction.kind == Kind::Sale && transaction.customer.as_deref() == Some(phone)
+        })
+        .collect()
+}
+
+pub fn get_customers(dir: &Path) -> std::io::Result<Vec<Customer>> {
+    let mut file = File::open(dir.join("customers.json"))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let customers: Vec<Customer> = serde_json::from_str(&contents)?;
+    Ok(customers)
+}
+
+/// `file` queued for `customers.json`.
+pub async fn save_customers(customers: Vec<Customer>, file: Write) -> Result<(), String> {
+    let customers = serde_json::to_vec(&customers).map_err(|error| error.to_string())?;
+    file.replace(&customers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transaction::Tender;
+
+    #[test]
+    fn phone_format() {
+        assert_eq!(
+            normalize_phone("081-234 5678"),
+            Some("0812345678".to_string())
+        );
+        assert_eq!(normalize_phone("812345678"), None);
+        assert_eq!(normalize_phone("08123456789"), None);
+    }
+
+    #[test]
+    fn points_on_paid_amount() {
+        let transaction = Transaction {
+            number: 1,
+            kind: Kind::Sale,
+            items: Vec::new(),
+            discounts: Vec::new(),
+            total: 130,
+            tenders: vec![
+                Tender {
+                    method: PaymentMethod::Points,
+                    amount: 30,
+                },
+                Tender {
+                    method: PaymentMethod::Cash,
+                    amount: 100,
+                },
+            ],
+            change: 0,
+            customer: Some("0812345678".to_string()),
+            user: "สมชาย".to_string(),
+            time: None,
+            vat_rate: 7,
+        };
+        assert_eq!(redeemed(&transaction), 30);
+        assert_eq!(earned(&transaction, 25), 4);
+        assert_eq!(earned(&transaction, 0), 0);
+    }
+}
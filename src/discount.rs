@@ -0,0 +1,197 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::promotion::Discount;
+use crate::Item;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum DiscountReason {
+    Damaged,
+    NearExpiry,
+    LoyalCustomer,
+    RoundDown,
+    PriceMatch,
+    Other,
+}
+
+impl DiscountReason {
+    pub const ALL: [DiscountReason; 6] = [
+        DiscountReason::Damaged,
+        DiscountReason::NearExpiry,
+        DiscountReason::LoyalCustomer,
+        DiscountReason::RoundDown,
+        DiscountReason::PriceMatch,
+        DiscountReason::Other,
+    ];
+}
+
+impl fmt::Display for DiscountReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DiscountReason::Damaged => "สินค้าชำรุด",
+            DiscountReason::NearExpiry => "สินค้าใกล้หมดอายุ",
+            DiscountReason::LoyalCustomer => "ลูกค้าประจำ",
+            DiscountReason::RoundDown => "ปัดเศษ",
+            DiscountReason::PriceMatch => "ราคาตามป้าย",
+            DiscountReason::Other => "อื่นๆ",
+        })
+    }
+}
+
+/// What a manual discount applies to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum Target {
+    /// A line of the sale, by index.
+    Line(usize),
+    Bill,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum Value {
+    Percent(u32),
+    Amount(u32),
+}
+
+impl Value {
+    /// Discount off `base`, never more than `base` itself.
+    pub fn of(&self, base: u32) -> u32 {
+        match self {
+            Value::Percent(percent) => (base as u64 * *percent.min(&100) as u64 / 100) as u32,
+            Value::Amount(amount) => *amount.min(&base),
+        }
+    }
+}
+
+/// A discount keyed in by the cashier.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Manual {
+    pub target: Target,
+    pub value: Value,
+    pub reason: DiscountReason,
+}
+
+/// Discount lines for the manual discounts on `items`, given what `lines`
+/// each have left after promotions. Line discounts come off their line
+/// first, never more than it has left; bill discounts then come off whatever
+/// is left of the whole.
+pub fn apply(manual: &[Manual], items: &[Item], lines: &[u32]) -> Vec<Discount> {
+    let mut lines = lines.to_vec();
+    let mut left: u32 = lines.iter().sum();
+    let line = manual
+        .iter()
+        .filter(|discount| discount.target != Target::Bill);
+    let bill = manual
+        .iter()
+        .filter(|discount| discount.target == Target::Bill);
+
+    line.chain(bill)
+        .filter_map(|discount| {
+            let (name, amount) = match discount.target {
+                Target::Line(index) => {
+                    let item = items.get(index)?;
+                    let line = lines.get_mut(index)?;
+                    let amount = discount.value.of(item.sum).min(*line);
+                    *line -= amount;
+                    (format!("ส่วนลด {} ({})", item.name, discount.reason), amount)
+                }
+                Target::Bill => (
+                    format!("ส่วนลดท้ายบิล ({})", discount.reason),
+                    discount.value.of(left),
+                ),
+            };
+            let amount = amount.min(left);
+            left -= amount;
+            Some(Discount {
+                name,
+                amount,
+                reason: Some(discount.reason),
+            })
+        })
+        .collect()
+}
+
+/// Holds `discounts` to `ceiling` in all, the last ones giving way first.
+pub fn cap(discounts: &mut [Discount], ceiling: u32) {
+    let mut left = ceiling;
+    for discount in discounts {
+        discount.amount = discount.amount.min(left);
+        left -= discount.amount;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(price: u32, amount: u32) -> Item {
+        Item {
+            price,
+            amount: amount.to_string(),
+            sum: price * amount,
+            ..Item::default()
+        }
+    }
+
+    #[test]
+    fn line_before_bill() {
+        let items = [item(100, 1), item(50, 2)];
+        let manual = [
+            Manual {
+                target: Target::Bill,
+                value: Value::Percent(10),
+                reason: DiscountReason::LoyalCustomer,
+            },
+            Manual {
+                target: Target::Line(1),
+                value: Value::Amount(20),
+                reason: DiscountReason::Damaged,
+            },
+        ];
+        let amounts: Vec<u32> = apply(&manual, &items, &[100, 100])
+            .iter()
+            .map(|discount| discount.amount)
+            .collect();
+        assert_eq!(amounts, vec![20, 18]);
+    }
+
+    #[test]
+    fn never_below_zero() {
+        let manual = [Manual {
+            target: Target::Bill,
+            value: Value::Amount(500),
+            reason: DiscountReason::RoundDown,
+        }];
+        assert_eq!(apply(&manual, &[item(30, 1)], &[30])[0].amount, 30);
+        assert_eq!(Value::Percent(150).of(40), 40);
+    }
+
+    #[test]
+    fn line_never_below_zero() {
+        let damaged = |value| Manual {
+            target: Target::Line(0),
+            value,
+            reason: DiscountReason::Damaged,
+        };
+        let manual = [damaged(Value::Percent(60)), damaged(Value::Percent(60))];
+        // 20 of the line already went to a promotion
+        let amounts: Vec<u32> = apply(&manual, &[item(100, 1), item(50, 1)], &[80, 50])
+            .iter()
+            .map(|discount| discount.amount)
+            .collect();
+        assert_eq!(amounts, vec![60, 20]);
+    }
+
+    #[test]
+    fn cap_gives_way_from_the_last() {
+        let discount = |amount| Discount {
+            name: "".to_string(),
+            amount,
+            reason: Some(DiscountReason::Other),
+        };
+        let mut discounts = [discount(15), discount(10), discount(5)];
+        cap(&mut discounts, 20);
+        let amounts: Vec<u32> = discounts.iter().map(|discount| discount.amount).collect();
+        assert_eq!(amounts, vec![15, 5, 0]);
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::Write;
+
+/// Thai banknotes and coins counted when a session is closed, largest first.
+pub const DENOMINATIONS: [u32; 9] = [1000, 500, 100, 50, 20, 10, 5, 2, 1];
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum CashMovementKind {
+    In,
+    Out,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct CashMovement {
+    pub kind: CashMovementKind,
+    pub amount: u32,
+    pub note: String,
+}
+
+/// A cashier shift on the drawer, from opening float to blind count.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct Session {
+    pub cashier: String,
+    pub float: u32,
+    pub sales: u32,
+    pub refunds: u32,
+    pub movements: Vec<CashMovement>,
+}
+
+impl Session {
+    pub fn open(cashier: String, float: u32) -> Self {
+        Session {
+            cashier,
+            float,
+            sales: 0,
+            refunds: 0,
+            movements: Vec::new(),
+        }
+    }
+
+    pub fn record_sale(&mut self, cash: u32) {
+        self.sales += cash;
+    }
+
+    pub fn record_refund(&mut self, cash: u32) {
+        self.refunds += cash;
+    }
+
+    pub fn cash_in(&mut self, amount: u32, note: String) {
+        self.movements.push(CashMovement {
+            kind: CashMovementKind::In,
+            amount,
+            note,
+        });
+    }
+
+    pub fn cash_out(&mut self, amount: u32, note: String) {
+        self.movements.push(CashMovement {
+            kind: CashMovementKind::Out,
+            amount,
+            note,
+        });
+    }
+
+    /// Cash that should be in the drawer: float, plus cash sales, minus
+    /// refunds, plus cash-in, minus cash-out.
+    pub fn expected(&self) -> i64 {
+        self.movements.iter().fold(
+            self.float as i64 + self.sales as i64 - self.refunds as i64,
+            |total, movement| match movement.kind {
+                CashMovementKind::In => total + movement.amount as i64,
+                CashMovementKind::Out => total - movement.amount as i64,
+            },
+        )
+    }
+
+    /// `None` when the count adds up to more than a drawer could hold.
+    pub fn close(&self, count: &CashCount) -> Option<Variance> {
+        Some(Variance {
+            expected: self.expected(),
+            counted: count.total()?,
+        })
+    }
+}
+
+/// Number of each denomination in the drawer, in the order of `DENOMINATIONS`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct CashCount(pub [u32; DENOMINATIONS.len()]);
+
+impl CashCount {
+    /// `None` if the total doesn't fit, as a mistyped count can make it.
+    pub fn total(&self) -> Option<u32> {
+        self.0
+            .iter()
+            .zip(DENOMINATIONS)
+            .try_fold(0u32, |total, (count, value)| {
+                count
+                    .checked_mul(value)
+                    .and_then(|sum| total.checked_add(sum))
+            })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Variance {
+    pub expected: i64,
+    pub counted: u32,
+}
+
+impl Variance {
+    /// Positive when the drawer is over, negative when it is short.
+    pub fn difference(&self) -> i64 {
+        self.counted as i64 - self.expected
+    }
+}
+
+/// The session left open in `dir`, if any.
+pub fn get_session(dir: &Path) -> Result<Option<Session>, String> {
+    let path = dir.join("drawer.json");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(format!("{}: {}", path.display(), error)),
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// `file` queued for `drawer.json`, removed once the session is closed.
+pub async fn save_session(session: Option<Session>, file: Write) -> Result<(), String> {
+    match session {
+        Some(session) => {
+            let session = serde_json::to_vec(&session).map_err(|error| error.to_string())?;
+            file.replace(&session)
+        }
+        None => file.remove(),
+    }
+}
@@ -0,0 +1,172 @@
+//! Drives [`State`] the way the iced runtime does, without a window: messages
+//! and key releases go through `update`, the tasks it returns are run to
+//! completion and their messages fed back, and widget operations such as
+//! focus are applied to the rendered view. Runs in training mode so nothing
+//! reaches the real backend or records, unless made with [`Harness::live`].
+
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use iced::futures::StreamExt;
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
+use iced::{Font, Pixels, Rectangle, Size};
+use iced_runtime::core::widget::operation::{Focusable, Outcome};
+use iced_runtime::core::widget::{Id, Operation};
+use iced_runtime::user_interface::{Cache, UserInterface};
+use iced_runtime::Action;
+use tokio::runtime::Runtime;
+
+use crate::api::Product;
+use crate::receipt::PrinterTarget;
+use crate::training::Fake;
+use crate::{keys, Message, Pages, Sale, State};
+
+const WINDOW: Size = Size::new(1280.0, 800.0);
+
+pub struct Harness {
+    pub state: State,
+    /// Messages the tasks produced, in order.
+    pub produced: Vec<Message>,
+    /// Directory of the settings and records, and of the printer's file.
+    pub dir: PathBuf,
+    renderer: iced::Renderer,
+    cache: Cache,
+    runtime: Runtime,
+}
+
+impl Harness {
+    /// Runs `state` in training against a fake backend selling `products`,
+    /// keeping its files in a directory of its own.
+    pub fn new(state: State, products: Vec<Product>) -> Self {
+        let mut harness = Harness::live(state);
+        harness.state.start_training(products);
+        harness
+    }
+
+    /// Runs `state` as it is, keeping its files in a directory of its own.
+    /// Calls to a backend go to the one set, and fail at once with none.
+    pub fn live(mut state: State) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "sunminimart-harness-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("harness directory");
+        state.setting.printer = PrinterTarget::File(dir.join("printer.bin").display().to_string());
+        state.config = dir.join("setting.json");
+        state.data = dir.clone();
+        Harness {
+            state,
+            produced: Vec::new(),
+            dir,
+            renderer: iced::Renderer::Secondary(iced_tiny_skia::Renderer::new(
+                Font::DEFAULT,
+                Pixels(16.0),
+            )),
+            cache: Cache::new(),
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("tokio runtime"),
+        }
+    }
+
+    /// Updates with `message` and everything its tasks produce. A panic in
+    /// `update` fails naming the message and the page it arrived on.
+    pub fn send(&mut self, message: Message) -> &mut Self {
+        let mut queue = VecDeque::from([message]);
+        while let Some(message) = queue.pop_front() {
+            let page = format!("{:?}", self.state.pages);
+            let state = &mut self.state;
+            let task = catch_unwind(AssertUnwindSafe(|| state.update(message.clone())))
+                .unwrap_or_else(|_| panic!("{:?} panicked on {}", message, page));
+            let Some(mut stream) = iced_runtime::task::into_stream(task) else {
+                continue;
+            };
+            while let Some(action) = self.runtime.block_on(stream.next()) {
+                match action {
+                    Action::Output(message) => {
+                        self.produced.push(message.clone());
+                        queue.push_back(message);
+                    }
+                    Action::Widget(mut operation) => self.operate(operation.as_mut()),
+                    _ => {}
+                }
+            }
+        }
+        self
+    }
+
+    /// Releases `key` on the current page.
+    pub fn key(&mut self, key: Named) -> &mut Self {
+        if let Some(message) = keys(&self.state.pages)(Key::Named(key), Modifiers::default()) {
+            self.send(message);
+        }
+        self
+    }
+
+    /// Id of the focused widget.
+    pub fn focused(&mut self) -> Option<Id> {
+        let mut focused = Focused(None);
+        self.operate(&mut focused);
+        focused.0
+    }
+
+    pub fn sale(&self) -> &Sale {
+        match &self.state.pages {
+            Pages::Sale(sale) => sale,
+            page => panic!("expected sale page, on {:?}", page),
+        }
+    }
+
+    pub fn backend(&self) -> Fake {
+        self.state.training.as_ref().unwrap().backend.clone()
+    }
+
+    /// What the printer was last sent.
+    pub fn receipt(&self) -> Vec<u8> {
+        std::fs::read(self.dir.join("printer.bin")).unwrap_or_default()
+    }
+
+    fn operate(&mut self, operation: &mut dyn Operation) {
+        let cache = std::mem::replace(&mut self.cache, Cache::new());
+        let mut ui = UserInterface::build(self.state.view(), WINDOW, cache, &mut self.renderer);
+        ui.operate(&self.renderer, operation);
+        let mut outcome = operation.finish();
+        while let Outcome::Chain(mut next) = outcome {
+            ui.operate(&self.renderer, next.as_mut());
+            outcome = next.finish();
+        }
+        self.cache = ui.into_cache();
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+struct Focused(Option<Id>);
+
+impl Operation for Focused {
+    fn container(
+        &mut self,
+        _id: Option<&Id>,
+        _bounds: Rectangle,
+        operate_on_children: &mut dyn FnMut(&mut dyn Operation),
+    ) {
+        operate_on_children(self);
+    }
+
+    fn focusable(&mut self, state: &mut dyn Focusable, id: Option<&Id>) {
+        if state.is_focused() {
+            self.0 = id.cloned();
+        }
+    }
+}
@@ -1,51 +1,214 @@
 use iced::keyboard::key::Named;
 use iced::keyboard::Key;
+use std::path::{Path, PathBuf};
+
+use iced::widget::column;
 use iced::widget::text_input::focus;
 use iced::{keyboard, Subscription};
 use iced::{Element, Task};
 
 mod custom_widget;
+mod customer;
 mod pages {
+    pub mod audit;
+    pub mod credit;
+    pub mod customer;
+    pub mod drawer;
+    pub mod invoice;
+    pub mod login;
     pub mod main;
+    pub mod refund;
+    pub mod report;
     pub mod sale;
     pub mod setting;
+    pub mod status;
     pub mod stock;
+    pub mod users;
 }
 mod api;
+mod audit;
+mod catalogue;
+mod credit;
+mod discount;
+mod drawer;
+#[cfg(test)]
+mod harness;
+#[cfg(any(test, feature = "mock"))]
+mod mock;
+mod promotion;
+mod promptpay;
+mod receipt;
+mod recovery;
+mod report;
 mod setting;
+mod store;
+mod tax_invoice;
+mod training;
+mod transaction;
+mod user;
+mod vat;
 
+use crate::api::{parse_url, Changes, Failure, Health};
+use crate::audit::{append, export, get_audit, Action, Entry, OverrideReason};
+use crate::catalogue::{get_catalogue, save_catalogue, Catalogue};
+use crate::credit::{get_accounts, save_accounts, Account};
 use crate::custom_widget::thai_font;
+use crate::customer::{find, get_customers, normalize_phone, save_customers, Customer};
+use crate::discount::{DiscountReason, Manual, Target, Value};
+use crate::drawer::{get_session, save_session, CashCount, Session};
+use crate::pages::audit::AuditPage;
+use crate::pages::credit::CreditPage;
+use crate::pages::customer::CustomerPage;
+use crate::pages::drawer::Drawer;
+use crate::pages::invoice::Invoice;
+use crate::pages::login::Login;
+use crate::pages::refund::{self as refund_page, Refund};
+use crate::pages::sale::{DiscountForm, OverrideForm};
+use crate::pages::setting::{Field, Printer, Probe, Section, SettingPage};
 use crate::pages::stock::Stock;
-use crate::setting::Setting;
+use crate::pages::users::Users;
+use crate::promotion::{evaluate, get_promotions, Discount, Promotion};
+use crate::promptpay::QrCode;
+use crate::receipt::PrinterTarget;
+use crate::recovery::{get_snapshot, remove_snapshot, save_snapshot, Snapshot};
+use crate::report::{Period, Report};
+use crate::setting::{
+    adopt_legacy, config_path, data_dir, save_setting, Appearance, Language, Setting,
+};
+use crate::store::{append_line, read_lines, Write, Writes};
+use crate::tax_invoice::{next_number, valid_tax_id, TaxInvoice};
+use crate::training::{Fake, Training};
+use crate::transaction::{
+    find_sale, next_receipt, parse_receipt, refund_tenders, refunded, returnable, Kind,
+    PaymentMethod, RefundReason, Tender, Transaction,
+};
+use crate::user::{approve, check_pin, get_users, login, save_users, Permission, Role, User};
+use crate::vat::Vat;
 
 pub fn main() -> iced::Result {
+    env_logger::init();
+    #[cfg(feature = "mock")]
+    if std::env::args().nth(1).as_deref() == Some("--mock-backend") {
+        let address = std::env::args().nth(2);
+        if let Err(error) = mock::run(address.as_deref().unwrap_or("127.0.0.1:3000")) {
+            log::error!("mock backend: {}", error);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     iced::application("Sunminimart", State::update, State::view)
         .default_font(thai_font())
         .subscription(State::subscription)
-        .run()
+        .theme(State::theme)
+        .scale_factor(State::scale_factor)
+        .run_with(|| {
+            let state = State::load(config_path(), Path::new("."));
+            let task = Task::batch([
+                state.check_connection(),
+                state.sync_products(state.catalogue.cursor),
+            ]);
+            (state, task)
+        })
 }
 
-#[derive(PartialEq, Debug)]
+/// How often the backend is health checked.
+const HEALTH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How often product changes are fetched from the backend.
+const SYNC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(PartialEq, Debug, Default)]
 struct State {
     pages: Pages,
     setting: Setting,
+    /// Why the settings file couldn't be loaded, while running on defaults.
+    setting_error: Option<String>,
+    /// Path of the settings file.
+    config: PathBuf,
+    /// Directory of the records, beside the settings file.
+    data: PathBuf,
+    writes: Writes,
+    session: Option<Session>,
+    transactions: Vec<Transaction>,
+    invoices: Vec<TaxInvoice>,
+    /// Why some of the sales or invoices on record couldn't be read.
+    records_error: Option<String>,
+    /// The invoice being written, which takes its number once it is.
+    issuing: Option<TaxInvoice>,
+    audit: Vec<Entry>,
+    /// Why some of the audit log couldn't be read, which breaks its chain.
+    audit_error: Option<String>,
+    /// What last went wrong away from the page showing, until dismissed.
+    error: Option<String>,
+    customers: Vec<Customer>,
+    accounts: Vec<Account>,
+    users: Vec<User>,
+    /// No users file yet: the first login sets up the manager.
+    setup: bool,
+    /// Why the users file couldn't be read; nobody can log in until it is fixed.
+    users_error: Option<String>,
+    /// The user logged in at the till.
+    user: Option<User>,
+    promotions: Vec<Promotion>,
+    catalogue: Catalogue,
+    /// Last health check of the saved backend, `None` until one finishes.
+    connection: Option<Result<Health, Failure>>,
+    /// Health checks of the saved backend failed in a row.
+    failures: u32,
+    training: Option<Training>,
+    /// Sale left unfinished when the till went down, until it is restored or
+    /// discarded.
+    recovered: Option<Snapshot>,
+    /// Sale last written to the journal, `None` once it is cleared.
+    journaled: Option<Snapshot>,
+    /// Drawer session as last written, `None` once it is closed.
+    kept: Option<Session>,
+    /// Number of the last request whose result is tagged, so results reaching
+    /// a page opened since are told apart.
+    requests: u64,
 }
-impl Default for State {
-    fn default() -> Self {
-        State {
-            pages: Pages::default(),
-            setting: Setting::get_setting().unwrap_or_default(),
-        }
-    }
-}
-
-#[derive(Default, PartialEq, Debug)]
+#[derive(PartialEq, Debug)]
+#[allow(clippy::large_enum_variant)]
 enum Pages {
-    #[default]
+    Login(Login),
     Main,
     Sale(Sale),
     Stock(Stock),
-    Setting,
+    Drawer(Drawer),
+    Refund(Refund),
+    Report(Report),
+    Invoice(Invoice),
+    Customer(CustomerPage),
+    Credit(CreditPage),
+    Users(Users),
+    Audit(AuditPage),
+    Setting(SettingPage),
+}
+
+impl Pages {
+    fn name(&self) -> &'static str {
+        match self {
+            Pages::Login(_) => "login",
+            Pages::Main => "main",
+            Pages::Sale(_) => "sale",
+            Pages::Stock(_) => "stock",
+            Pages::Drawer(_) => "drawer",
+            Pages::Refund(_) => "refund",
+            Pages::Report(_) => "report",
+            Pages::Invoice(_) => "invoice",
+            Pages::Customer(_) => "customer",
+            Pages::Credit(_) => "credit",
+            Pages::Users(_) => "users",
+            Pages::Audit(_) => "audit",
+            Pages::Setting(_) => "setting",
+        }
+    }
+}
+
+impl Default for Pages {
+    fn default() -> Self {
+        Pages::Login(Login::default())
+    }
 }
 
 #[derive(PartialEq, Default, Debug)]
@@ -53,18 +216,298 @@ struct Sale {
     paying: bool,
     item: Item,
     items: Vec<Item>,
+    /// Line the cashier last clicked in the grid.
+    selected: Option<usize>,
+    manual: Vec<Manual>,
+    /// Manual discount a supervisor allowed on this sale above the limit.
+    approved: u32,
+    /// Manual discount the sale asks for, before holding it to the limit.
+    requested: u32,
+    discounts: Vec<Discount>,
+    /// The discount being keyed in, if the discount panel is open.
+    discount: Option<DiscountForm>,
+    price_override: Option<OverrideForm>,
+    /// Member phone number being typed in.
+    phone: String,
+    /// Phone number of the member attached to this sale.
+    customer: Option<String>,
+    customer_error: Option<&'static str>,
+    /// Why the last barcode didn't go into the basket.
+    item_error: Option<&'static str>,
+    /// Credit account the `Credit` tender is charged to.
+    account: Option<String>,
     received: String,
     change: i32,
     total: u32,
+    method: PaymentMethod,
+    qr: Option<QrCode>,
+    payments: Vec<Tender>,
+}
+
+impl Sale {
+    /// Re-evaluates promotions and manual discounts over the whole basket and
+    /// recomputes the total. Manual discounts are held to `limit` or what was
+    /// approved, so a percent off can't outgrow it as the basket does.
+    fn reprice(&mut self, promotions: &[Promotion], rate: u64, limit: u32) {
+        let now = chrono::Local::now().naive_local();
+        let (promotions, lines) = evaluate(promotions, &self.items, now, rate);
+        let mut manual = discount::apply(&self.manual, &self.items, &lines);
+        self.requested = manual.iter().map(|discount| discount.amount).sum();
+        discount::cap(&mut manual, limit.max(self.approved));
+        let subtotal: u32 = lines.iter().sum();
+        self.total = subtotal.saturating_sub(manual.iter().map(|discount| discount.amount).sum());
+        self.discounts = promotions.into_iter().chain(manual).collect();
+    }
+
+    /// The basket to restore if the till goes down, `None` for an empty sale.
+    fn snapshot(&self) -> Option<Snapshot> {
+        if self.items.is_empty() && self.payments.is_empty() {
+            return None;
+        }
+        Some(Snapshot {
+            items: self.items.clone(),
+            manual: self.manual.clone(),
+            approved: self.approved,
+            customer: self.customer.clone(),
+            account: self.account.clone(),
+            payments: self.payments.clone(),
+        })
+    }
+
+    /// The sale `snapshot` was taken of, back at payment if tenders were
+    /// already taken.
+    fn restore(snapshot: Snapshot, promotions: &[Promotion], rate: u64, limit: u32) -> Self {
+        let mut sale = Sale {
+            paying: !snapshot.payments.is_empty(),
+            items: snapshot.items,
+            manual: snapshot.manual,
+            approved: snapshot.approved,
+            customer: snapshot.customer,
+            account: snapshot.account,
+            payments: snapshot.payments,
+            ..Sale::default()
+        };
+        sale.reprice(promotions, rate, limit);
+        sale.change = -(sale.remaining() as i32);
+        sale
+    }
+
+    fn remaining(&self) -> u32 {
+        self.total
+            .saturating_sub(self.payments.iter().map(|tender| tender.amount).sum())
+    }
+
+    /// Brings the tenders down to the total a later discount left them over,
+    /// and returns the change due. Points and credit give way first, as they
+    /// have yet to be taken, then cash is handed back, then the rest.
+    fn settle(&mut self) -> u32 {
+        let paid: u32 = self.payments.iter().map(|tender| tender.amount).sum();
+        let mut excess = paid.saturating_sub(self.total);
+        let cash: u32 = self
+            .payments
+            .iter()
+            .filter(|tender| tender.method == PaymentMethod::Cash)
+            .map(|tender| tender.amount)
+            .sum();
+        let mut trim = |excess: &mut u32, methods: &[PaymentMethod]| {
+            for tender in self
+                .payments
+                .iter_mut()
+                .rev()
+                .filter(|tender| methods.contains(&tender.method))
+            {
+                let taken = tender.amount.min(*excess);
+                tender.amount -= taken;
+                *excess -= taken;
+            }
+        };
+        trim(&mut excess, &[PaymentMethod::Points, PaymentMethod::Credit]);
+        let change = excess.min(cash);
+        excess -= change;
+        trim(
+            &mut excess,
+            &[PaymentMethod::PromptPay, PaymentMethod::Card],
+        );
+        self.payments.retain(|tender| tender.amount > 0);
+        change
+    }
+
+    /// Points the attached member can still spend on this sale.
+    fn points(&self, customers: &[Customer]) -> u32 {
+        self.balance(customers).saturating_sub(self.spent_points())
+    }
+
+    /// Points balance of the attached member, 0 without one.
+    fn balance(&self, customers: &[Customer]) -> u32 {
+        self.customer
+            .as_ref()
+            .and_then(|phone| find(customers, phone))
+            .map(|customer| customer.points)
+            .unwrap_or(0)
+    }
+
+    /// Points already tendered on this sale.
+    fn spent_points(&self) -> u32 {
+        self.payments
+            .iter()
+            .filter(|tender| tender.method == PaymentMethod::Points)
+            .map(|tender| tender.amount)
+            .sum()
+    }
+
+    /// Credit left on the chosen account, 0 without one.
+    fn available(&self, accounts: &[Account]) -> u32 {
+        self.account
+            .as_ref()
+            .and_then(|name| credit::find(accounts, name))
+            .map(|account| account.available())
+            .unwrap_or(0)
+    }
+
+    /// Credit already tendered on this sale.
+    fn charged(&self) -> u32 {
+        self.payments
+            .iter()
+            .filter(|tender| tender.method == PaymentMethod::Credit)
+            .map(|tender| tender.amount)
+            .sum()
+    }
+
+    /// Credit left on the chosen account after the credit already tendered.
+    fn credit(&self, accounts: &[Account]) -> u32 {
+        self.available(accounts).saturating_sub(self.charged())
+    }
+
+    /// Amount of the tender being entered; an empty input pays the balance.
+    fn tendered(&self) -> Option<u32> {
+        match self.received.is_empty() {
+            true => Some(self.remaining()),
+            false => self.received.parse::<u32>().ok(),
+        }
+    }
+}
+
+/// What releasing a key does on `pages`.
+fn keys(pages: &Pages) -> fn(Key, keyboard::Modifiers) -> Option<Message> {
+    match pages {
+        Pages::Login(_) | Pages::Main => |_, _| None,
+        Pages::Sale(sale) => match !sale.paying {
+            true => |key, _| match key {
+                Key::Named(Named::Escape) => Some(Message::Sale(MessageSale::Back)),
+                Key::Named(Named::F12) => Some(Message::Sale(MessageSale::EnterPay)),
+                _ => None,
+            },
+            false => |key, _| match key {
+                Key::Named(Named::Escape) => Some(Message::Sale(MessageSale::Back)),
+                Key::Named(Named::F12) => Some(Message::Sale(MessageSale::ExitPay)),
+                _ => None,
+            },
+        },
+        Pages::Stock(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Stock(MessageStock::Back)),
+            _ => None,
+        },
+        Pages::Drawer(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Drawer(MessageDrawer::Back)),
+            _ => None,
+        },
+        Pages::Refund(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Refund(MessageRefund::Back)),
+            _ => None,
+        },
+        Pages::Report(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Report(MessageReport::Back)),
+            _ => None,
+        },
+        Pages::Invoice(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Invoice(MessageInvoice::Back)),
+            _ => None,
+        },
+        Pages::Customer(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Customer(MessageCustomer::Back)),
+            _ => None,
+        },
+        Pages::Users(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Users(MessageUsers::Back)),
+            _ => None,
+        },
+        Pages::Audit(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Audit(MessageAudit::Back)),
+            _ => None,
+        },
+        Pages::Credit(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Credit(MessageCredit::Back)),
+            _ => None,
+        },
+        Pages::Setting(_) => |key, _| match key {
+            Key::Named(Named::Escape) => Some(Message::Setting(MessageSetting::Cancel)),
+            _ => None,
+        },
+    }
+}
+
+/// Name stamped on transactions and audit entries.
+fn user_name(user: &Option<User>) -> String {
+    user.as_ref()
+        .map(|user| user.name.clone())
+        .unwrap_or_default()
+}
+
+/// Checks `pin` for `permission` in a task, argon2 being too slow for the
+/// update, and hands `approved` the number of the check with whoever it
+/// approved for.
+fn check(
+    users: &[User],
+    permission: Permission,
+    pin: &str,
+    request: u64,
+    approved: fn(u64, Option<String>) -> Message,
+) -> Task<Message> {
+    Task::perform(
+        check_pin(users.to_vec(), permission, pin.to_string()),
+        move |approver| approved(request, approver),
+    )
+}
+
+/// Whether sales are practice, on the fake backend or a non-production
+/// profile.
+fn practice(training: &Option<Training>, setting: &Setting) -> bool {
+    training.is_some() || setting.training()
+}
+
+/// Chains `action` onto the audit log and appends it to the log file, which
+/// training leaves alone.
+fn record(
+    audit: &mut Vec<Entry>,
+    user: &Option<User>,
+    training: &Option<Training>,
+    writes: &Writes,
+    data: &Path,
+    action: Action,
+) -> Task<Message> {
+    let entry = append(
+        audit,
+        chrono::Local::now().naive_local(),
+        user_name(user),
+        action,
+    );
+    match training {
+        Some(_) => Task::none(),
+        None => Task::perform(
+            append_line(entry, writes.append(data.join("audit.jsonl"))),
+            Message::Saved,
+        ),
+    }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
 struct Item {
     barcode: String,
     name: String,
     price: u32,
     amount: String,
     sum: u32,
+    vat: Vat,
 }
 
 impl Default for Item {
@@ -75,23 +518,80 @@ impl Default for Item {
             price: 0,
             amount: "1".to_string(),
             sum: 0,
+            vat: Vat::Inclusive,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 enum Message {
+    Login(MessageLogin),
     Main(MessageMain),
     Sale(MessageSale),
     Stock(MessageStock),
+    Drawer(MessageDrawer),
+    Refund(MessageRefund),
+    Report(MessageReport),
+    Invoice(MessageInvoice),
+    Customer(MessageCustomer),
+    Credit(MessageCredit),
+    Users(MessageUsers),
+    Audit(MessageAudit),
     Setting(MessageSetting),
+    Api(Result<(), String>),
+    Printed(Result<(), String>),
+    /// Result of the invoice save request numbered.
+    InvoiceSaved(u64, Result<(), String>),
+    /// Whether the backend took the product with this barcode.
+    ProductSaved(String, Result<(), String>),
+    Saved(Result<(), String>),
+    /// Time for a health check of the saved backend.
+    CheckConnection,
+    /// Health check of a backend URL.
+    Health(String, Result<Health, Failure>),
+    /// Time for an incremental product sync.
+    Sync,
+    /// Product changes fetched from a backend URL since a cursor.
+    Synced(String, u64, Result<Changes, String>),
+    /// Clears the error shown in the status bar.
+    DismissError,
+}
+
+impl Message {
+    fn api(result: reqwest::Result<()>) -> Self {
+        Message::Api(result.map_err(|error| error.to_string()))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum MessageLogin {
+    NameChanged(String),
+    SecretChanged(String),
+    Submit,
+    /// Who the name and secret of the login request numbered turned out to
+    /// be, if anyone.
+    LoggedIn(u64, Option<User>),
 }
 
 #[derive(Debug, Clone)]
 enum MessageMain {
     Sale,
     Stock,
+    Drawer,
+    Refund,
+    Report,
+    Invoice,
+    Customer,
+    Credit,
+    Users,
+    Audit,
     Setting,
+    /// Starts or ends training.
+    Training,
+    /// Carries on with the sale left when the till went down.
+    Restore,
+    Discard,
+    Logout,
 }
 
 #[derive(Debug, Clone)]
@@ -103,7 +603,32 @@ enum MessageSale {
     EnterPay,
     ExitPay,
     Receive(String),
+    MethodSelected(PaymentMethod),
     Pay,
+    LineSelected(usize),
+    ToDiscount(Target),
+    DiscountPercent(bool),
+    DiscountValueChanged(String),
+    DiscountReasonSelected(DiscountReason),
+    DiscountPinChanged(String),
+    ApplyDiscount,
+    /// Who the PIN of the check numbered belongs to, if it checked out for
+    /// the discount.
+    DiscountApproved(u64, Option<String>),
+    ToOverride(usize),
+    OverridePriceChanged(String),
+    OverrideReasonSelected(OverrideReason),
+    OverridePinChanged(String),
+    PriceCheckToggled(bool),
+    ApplyOverride,
+    /// Who the PIN of the check numbered belongs to, if it checked out for
+    /// the override.
+    OverrideApproved(u64, Option<String>),
+    PhoneChanged(String),
+    PhoneSubmit,
+    AccountSelected(String),
+    VoidLine,
+    VoidSale,
     Back,
 }
 
@@ -112,53 +637,496 @@ enum MessageStock {
     Back,
     ToAddProduct,
     BarcodeChanged(String),
+    NameChanged(String),
+    PriceChanged(String),
+    VatSelected(Vat),
+    Save,
 }
 
 #[derive(Debug, Clone)]
-enum MessageSetting {
-    DatabaseChanged(String),
-    ToDatabaseSubmitButton,
-    DatabaseSubmit,
+enum MessageDrawer {
+    FloatChanged(String),
+    Open,
+    AmountChanged(String),
+    NoteChanged(String),
+    CashIn,
+    CashOut,
+    ToCount,
+    CountChanged(usize, String),
+    Close,
+    Back,
+}
+
+#[derive(Debug, Clone)]
+enum MessageRefund {
+    ReceiptChanged(String),
+    ReceiptSubmit,
+    QuantityChanged(usize, String),
+    ReasonSelected(RefundReason),
+    PinChanged(String),
+    Refund,
+    /// Who the PIN of the check numbered belongs to, if it checked out for
+    /// the refund.
+    Approved(u64, Option<String>),
+    Back,
+}
+
+#[derive(Debug, Clone)]
+enum MessageReport {
+    PeriodSelected(Period),
+    Previous,
+    Next,
+    Back,
+}
+
+#[derive(Debug, Clone)]
+enum MessageInvoice {
+    ReceiptChanged(String),
+    NameChanged(String),
+    AddressChanged(String),
+    TaxIdChanged(String),
+    BranchChanged(String),
+    Issue,
+    Back,
+}
+
+#[derive(Debug, Clone)]
+enum MessageCustomer {
+    PhoneChanged(String),
+    Search,
+    NameChanged(String),
+    Register,
+    Back,
+}
+
+#[derive(Debug, Clone)]
+enum MessageCredit {
+    NameChanged(String),
+    LimitChanged(String),
+    Open,
+    Selected(String),
+    NewLimitChanged(String),
+    ChangeLimit,
+    RepaymentChanged(String),
+    Repay,
+    ToAging,
     Back,
 }
 
+#[derive(Debug, Clone)]
+enum MessageUsers {
+    NameChanged(String),
+    SecretChanged(String),
+    RoleSelected(Role),
+    Add,
+    Back,
+}
+
+#[derive(Debug, Clone)]
+enum MessageAudit {
+    UserSelected(String),
+    ActionSelected(&'static str),
+    FromChanged(String),
+    ToChanged(String),
+    Clear,
+    Export,
+    /// Result of the export request numbered.
+    Exported(u64, Result<String, String>),
+    Back,
+}
+
+#[derive(Debug, Clone)]
+enum MessageSetting {
+    SectionSelected(Section),
+    Changed(Field, String),
+    PrinterSelected(Printer),
+    LanguageSelected(Language),
+    AppearanceSelected(Appearance),
+    ProfileSelected(String),
+    AddProfile,
+    RemoveProfile,
+    ProductionToggled(bool),
+    /// Fetches the whole catalogue again.
+    Resync,
+    TestConnection,
+    /// Saves the backend that passed the connection test.
+    UseConnection,
+    /// Resets the fields of the current section.
+    Defaults,
+    Save,
+    Cancel,
+}
+
 impl State {
+    /// Settings from `config` and the records kept beside it, after taking
+    /// over what older versions left in `legacy`, the directory they ran in.
+    fn load(config: PathBuf, legacy: &Path) -> Self {
+        let adopted = adopt_legacy(legacy, &config)
+            .map_err(|error| format!("ย้ายไฟล์จากรุ่นเก่าไม่สำเร็จ: {}", error));
+        let data = data_dir(&config);
+        let (recovered, recovery_error) = match get_snapshot(&data) {
+            Ok(recovered) => (recovered, None),
+            Err(error) => (None, Some(format!("อ่านการขายที่ค้างไว้ไม่ได้: {}", error))),
+        };
+        let (setting, setting_error) = match Setting::load(&config) {
+            Ok(setting) => (setting, None),
+            Err(error) => (Setting::default(), Some(error)),
+        };
+        let (audit, audit_error) = get_audit(&data);
+        let records = setting.records(&data);
+        let (transactions, transactions_error) = read_lines(&records.join("transactions.jsonl"));
+        let (invoices, invoices_error) = read_lines(&records.join("invoices.jsonl"));
+        let (session, session_error) = match get_session(&data) {
+            Ok(session) => (session, None),
+            Err(error) => (None, Some(format!("อ่านลิ้นชักที่เปิดค้างไว้ไม่ได้: {}", error))),
+        };
+        let (users, users_error) = match get_users(&data) {
+            Ok(users) => (users, None),
+            Err(error) => (Some(Vec::new()), Some(error)),
+        };
+        State {
+            setting,
+            setting_error,
+            audit,
+            audit_error,
+            transactions,
+            invoices,
+            records_error: transactions_error.or(invoices_error),
+            customers: get_customers(&data).unwrap_or_default(),
+            accounts: get_accounts(&data).unwrap_or_default(),
+            setup: users.is_none(),
+            users: users.unwrap_or_default(),
+            users_error,
+            promotions: get_promotions(&data).unwrap_or_default(),
+            catalogue: get_catalogue(&data).unwrap_or_default(),
+            recovered,
+            kept: session.clone(),
+            session,
+            error: adopted.err().or(recovery_error).or(session_error),
+            config,
+            data,
+            ..State::default()
+        }
+    }
+
+    /// Whether the logged-in user's role allows `permission`.
+    fn allowed(&self, permission: Permission) -> bool {
+        approve(self.user.as_ref(), permission)
+    }
+
+    /// `thai` in the language of the settings.
+    fn text(&self, thai: &'static str) -> &'static str {
+        self.setting.language.text(thai)
+    }
+
+    /// Handles `message`, then journals the sale in progress and keeps the
+    /// drawer session if they changed.
     fn update(&mut self, message: Message) -> Task<Message> {
+        let task = self.handle(message);
+        Task::batch([task, self.journal(), self.keep_session()])
+    }
+
+    /// Writes the drawer session on every change, so a restart finds the
+    /// float and takings it was counting, and removes it once closed.
+    fn keep_session(&mut self) -> Task<Message> {
+        if self.training.is_some() || self.session == self.kept {
+            return Task::none();
+        }
+        self.kept = self.session.clone();
+        Task::perform(
+            save_session(self.session.clone(), self.file("drawer.json")),
+            Message::Saved,
+        )
+    }
+
+    /// Clears the journal of a sale that is on record, for `journal` to leave
+    /// alone.
+    fn clear_journal(&mut self) -> Task<Message> {
+        if self.training.is_some() {
+            return Task::none();
+        }
+        self.journaled = None;
+        Task::perform(remove_snapshot(self.file("sale.json")), Message::Saved)
+    }
+
+    /// Keeps the journal in step with the sale on screen: written on every
+    /// change and cleared once the sale is paid, voided or left.
+    fn journal(&mut self) -> Task<Message> {
+        if self.training.is_some() {
+            return Task::none();
+        }
+        let snapshot = match &self.pages {
+            Pages::Sale(sale) => sale.snapshot(),
+            _ => None,
+        };
+        if snapshot == self.journaled {
+            return Task::none();
+        }
+        self.journaled = snapshot.clone();
+        match snapshot {
+            Some(snapshot) => Task::perform(
+                save_snapshot(snapshot, self.file("sale.json")),
+                Message::Saved,
+            ),
+            None => Task::perform(remove_snapshot(self.file("sale.json")), Message::Saved),
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
         let mut task = Task::none();
         match (&mut self.pages, message) {
+            (Pages::Login(page), Message::Login(message_login)) => match message_login {
+                MessageLogin::NameChanged(name) => page.name = name,
+                MessageLogin::SecretChanged(secret) => page.secret = secret,
+                MessageLogin::Submit => {
+                    let name = page.name.trim();
+                    if self.setup {
+                        // First run: whoever sets up the till is its manager
+                        if name.is_empty() || page.secret.len() < 4 {
+                            page.error = Some("กรุณาใส่ชื่อและรหัสผ่านอย่างน้อย 4 ตัว");
+                            return task;
+                        }
+                        let Ok(user) = User::new(name.to_string(), Role::Manager, &page.secret)
+                        else {
+                            page.error = Some("ตั้งรหัสผ่านไม่สำเร็จ");
+                            return task;
+                        };
+                        self.users.push(user.clone());
+                        self.user = Some(user);
+                        self.setup = false;
+                        task = Task::batch([
+                            Task::perform(
+                                save_users(
+                                    self.users.clone(),
+                                    self.writes.queue(self.data.join("users.json")),
+                                ),
+                                Message::Saved,
+                            ),
+                            record(
+                                &mut self.audit,
+                                &self.user,
+                                &self.training,
+                                &self.writes,
+                                &self.data,
+                                Action::UserAdded {
+                                    name: name.to_string(),
+                                    role: Role::Manager,
+                                },
+                            ),
+                        ]);
+                        self.pages = Pages::Main;
+                    } else {
+                        page.error = None;
+                        self.requests += 1;
+                        let request = self.requests;
+                        page.checking = Some(request);
+                        task = Task::perform(
+                            login(self.users.clone(), name.to_string(), page.secret.clone()),
+                            move |user| Message::Login(MessageLogin::LoggedIn(request, user)),
+                        );
+                    }
+                }
+                // Only the last submitted counts
+                MessageLogin::LoggedIn(request, _) if page.checking != Some(request) => {}
+                MessageLogin::LoggedIn(_, Some(user)) => {
+                    self.user = Some(user);
+                    self.pages = Pages::Main;
+                }
+                MessageLogin::LoggedIn(_, None) => {
+                    page.checking = None;
+                    page.secret = "".to_string();
+                    page.error = Some("ชื่อผู้ใช้หรือรหัสผ่านไม่ถูกต้อง");
+                }
+            },
             (Pages::Main, Message::Main(message_main)) => match message_main {
                 MessageMain::Sale => {
-                    self.pages = Pages::Sale(Sale::default());
-                    task = focus("barcode");
+                    // A new sale would overwrite the journal of the one left
+                    if self.recovered.is_none() || self.training.is_some() {
+                        self.pages = Pages::Sale(Sale::default());
+                        task = focus("barcode");
+                    }
+                }
+                MessageMain::Restore => {
+                    if self.training.is_some() {
+                        return task;
+                    }
+                    if let Some(snapshot) = self.recovered.take() {
+                        self.pages = Pages::Sale(Sale::restore(
+                            snapshot,
+                            &self.promotions,
+                            self.setting.vat_rate,
+                            self.setting.discount_limit,
+                        ));
+                        task = focus("barcode");
+                    }
+                }
+                MessageMain::Discard => {
+                    if self.training.is_some() || !self.allowed(Permission::Void) {
+                        return task;
+                    }
+                    if let Some(snapshot) = self.recovered.take() {
+                        let sale = Sale::restore(
+                            snapshot,
+                            &self.promotions,
+                            self.setting.vat_rate,
+                            self.setting.discount_limit,
+                        );
+                        task = Task::batch([
+                            Task::perform(remove_snapshot(self.file("sale.json")), Message::Saved),
+                            record(
+                                &mut self.audit,
+                                &self.user,
+                                &self.training,
+                                &self.writes,
+                                &self.data,
+                                Action::VoidSale {
+                                    items: sale.items.len(),
+                                    total: sale.total,
+                                    approver: Some(user_name(&self.user)),
+                                },
+                            ),
+                        ]);
+                    }
                 }
                 MessageMain::Stock => {
-                    self.pages = Pages::Stock(Stock::Stock(Vec::new()));
+                    if self.allowed(Permission::Stock) {
+                        self.pages = Pages::Stock(Stock::Menu);
+                    }
+                }
+                MessageMain::Drawer => {
+                    self.pages = Pages::Drawer(match self.session {
+                        Some(_) => Drawer::Session {
+                            amount: "".to_string(),
+                            note: "".to_string(),
+                        },
+                        None => Drawer::Open {
+                            float: "0".to_string(),
+                        },
+                    });
+                }
+                MessageMain::Refund => {
+                    self.pages = Pages::Refund(Refund::default());
+                    task = focus("receipt");
+                }
+                MessageMain::Report => {
+                    if self.allowed(Permission::Report) {
+                        self.pages = Pages::Report(Report::new(
+                            &self.transactions,
+                            chrono::Local::now().date_naive(),
+                            Period::Day,
+                        ));
+                    }
+                }
+                MessageMain::Invoice => {
+                    self.pages = Pages::Invoice(Invoice::default());
+                    task = focus("receipt");
+                }
+                MessageMain::Customer => {
+                    self.pages = Pages::Customer(CustomerPage::default());
+                    task = focus("phone");
+                }
+                MessageMain::Credit => {
+                    self.pages = Pages::Credit(CreditPage::default());
+                    task = focus("name");
+                }
+                MessageMain::Users => {
+                    if self.allowed(Permission::Users) {
+                        self.pages = Pages::Users(Users::default());
+                        task = focus("user_name");
+                    }
+                }
+                MessageMain::Audit => {
+                    if self.allowed(Permission::Audit) {
+                        self.pages = Pages::Audit(AuditPage::default());
+                    }
                 }
                 MessageMain::Setting => {
-                    self.pages = Pages::Setting;
+                    if self.allowed(Permission::Setting) {
+                        self.pages = Pages::Setting(SettingPage::new(&self.setting));
+                    }
+                }
+                MessageMain::Training => {
+                    if self.training.is_some() {
+                        self.end_training();
+                    } else {
+                        self.start_training(self.catalogue.products.values().cloned().collect());
+                    }
+                }
+                MessageMain::Logout => {
+                    self.end_training();
+                    self.user = None;
+                    self.pages = Pages::default();
+                    task = focus("login_name");
                 }
             },
             (Pages::Sale(sale), Message::Sale(message_sale)) => match message_sale {
                 MessageSale::BarcodeChanged(barcode) => {
                     sale.item.barcode = barcode;
+                    sale.item_error = None;
                 }
                 MessageSale::AmountChanged(amount) => {
                     sale.item.amount = amount;
                 }
                 MessageSale::BarcodeSubmit => {
-                    if let (Ok(amount), Ok(price)) = (
-                        sale.item.amount.parse::<u32>(),
-                        sale.item.barcode.parse::<u32>(),
-                    ) {
-                        if amount > 0 && price > 0 && price <= 1000 {
+                    sale.item.barcode = self.setting.scanned(&sale.item.barcode).to_string();
+                    // Known products sell at their price, without a round trip
+                    let known = match &self.training {
+                        Some(training) => training
+                            .backend
+                            .with(|fake| fake.product(&sale.item.barcode).cloned()),
+                        None => self.catalogue.product(&sale.item.barcode).cloned(),
+                    };
+                    if let Some(product) = known {
+                        if let Some(amount) =
+                            sale.item.amount.parse::<u32>().ok().filter(|a| *a > 0)
+                        {
+                            sale.items.push(Item {
+                                name: product.name,
+                                price: product.retail,
+                                sum: product.vat.charge(
+                                    product.retail,
+                                    amount,
+                                    self.setting.vat_rate,
+                                ),
+                                vat: product.vat,
+                                ..sale.item.clone()
+                            });
+                            sale.reprice(
+                                &self.promotions,
+                                self.setting.vat_rate,
+                                self.setting.discount_limit,
+                            );
+                            sale.item.barcode = "".to_string();
+                            sale.item.amount = "1".to_string();
+                        }
+                        return task;
+                    }
+                    // Otherwise a code of up to 1000 is an item sold at that price
+                    let price = sale
+                        .item
+                        .barcode
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|price| *price > 0 && *price <= 1000);
+                    let Some(price) = price else {
+                        if !sale.item.barcode.is_empty() {
+                            sale.item_error = Some("ไม่พบสินค้า");
+                        }
+                        return task;
+                    };
+                    if let Ok(amount) = sale.item.amount.parse::<u32>() {
+                        if amount > 0 {
                             sale.item.name = format!("สินค้าราคา {} บาท", price);
                             sale.item.price = price;
-                            sale.item.sum = amount * price;
+                            sale.item.sum =
+                                sale.item.vat.charge(price, amount, self.setting.vat_rate);
                             sale.items.push(sale.item.clone());
-                        } else {
-                            todo!()
                         }
-                        sale.total += sale.item.sum;
+                        sale.reprice(
+                            &self.promotions,
+                            self.setting.vat_rate,
+                            self.setting.discount_limit,
+                        );
                         sale.item.barcode = "".to_string();
                         sale.item.amount = "1".to_string();
                     }
@@ -170,162 +1138,1783 @@ impl State {
                 }
                 MessageSale::ExitPay => {
                     sale.paying = false;
+                    sale.method = PaymentMethod::Cash;
+                    sale.qr = None;
                     sale.received = "0".to_string();
-                    sale.change = -(sale.total as i32);
+                    sale.change = -(sale.remaining() as i32);
                     task = focus("barcode");
                 }
                 MessageSale::Receive(received) => {
                     sale.received = received;
                     if let Ok(received) = sale.received.parse::<u32>() {
-                        sale.change = received as i32 - sale.total as i32;
+                        sale.change = received as i32 - sale.remaining() as i32;
+                        if sale.method == PaymentMethod::PromptPay {
+                            sale.qr = QrCode::new(&self.setting.promptpay_id, received);
+                        }
+                    }
+                }
+                MessageSale::MethodSelected(method) => {
+                    sale.method = method;
+                    sale.qr = None;
+                    sale.change = 0;
+                    match method {
+                        PaymentMethod::Cash => sale.received = "".to_string(),
+                        PaymentMethod::PromptPay | PaymentMethod::Card | PaymentMethod::Credit => {
+                            sale.received = sale.remaining().to_string();
+                        }
+                        PaymentMethod::Points => {
+                            let points = sale.points(&self.customers);
+                            sale.received = sale.remaining().min(points).to_string();
+                        }
                     }
+                    if method == PaymentMethod::PromptPay {
+                        sale.qr = QrCode::new(&self.setting.promptpay_id, sale.remaining());
+                    }
+                    task = focus("received");
                 }
                 MessageSale::Pay => {
+                    if sale.items.is_empty() {
+                        self.pages = Pages::Sale(Sale::default());
+                        return focus("barcode");
+                    }
+                    let remaining = sale.remaining();
+                    // A discount given after the tenders can leave nothing to pay
+                    let amount = match remaining == 0 && !sale.payments.is_empty() {
+                        true => None,
+                        false => {
+                            let Some(amount) = sale.tendered().filter(|amount| *amount > 0) else {
+                                return task;
+                            };
+                            // Change is only ever given from cash
+                            if sale.method != PaymentMethod::Cash && amount > remaining {
+                                return task;
+                            }
+                            if sale.method == PaymentMethod::PromptPay && sale.qr.is_none() {
+                                return task;
+                            }
+                            if sale.method == PaymentMethod::Points
+                                && amount > sale.points(&self.customers)
+                            {
+                                return task;
+                            }
+                            if sale.method == PaymentMethod::Credit
+                                && amount > sale.credit(&self.accounts)
+                            {
+                                return task;
+                            }
+                            Some(amount)
+                        }
+                    };
+                    // Every point tendered must still be on the member's balance
+                    if sale.spent_points() > sale.balance(&self.customers) {
+                        return task;
+                    }
+                    // Credit tendered earlier must still be within the limit
+                    if sale.charged() > sale.available(&self.accounts) {
+                        return task;
+                    }
+                    let change = match amount {
+                        Some(amount) => {
+                            sale.payments.push(Tender {
+                                method: sale.method,
+                                amount,
+                            });
+                            if amount < remaining {
+                                sale.method = PaymentMethod::Cash;
+                                sale.qr = None;
+                                sale.received = "".to_string();
+                                sale.change = -(sale.remaining() as i32);
+                                return focus("received");
+                            }
+                            amount - remaining
+                        }
+                        None => sale.settle(),
+                    };
+
+                    let transaction = Transaction {
+                        number: next_receipt(&self.transactions),
+                        kind: Kind::Sale,
+                        items: sale.items.clone(),
+                        discounts: sale.discounts.clone(),
+                        total: sale.total,
+                        tenders: sale.payments.clone(),
+                        change,
+                        customer: sale.customer.clone(),
+                        user: user_name(&self.user),
+                        time: Some(chrono::Local::now().naive_local()),
+                        vat_rate: self.setting.vat_rate,
+                    };
+                    let account = sale.account.clone();
                     self.pages = Pages::Sale(Sale::default());
+                    if let Some(session) = &mut self.session {
+                        session.record_sale(transaction.cash());
+                    }
+                    let mut tasks = vec![focus("barcode"), self.print_receipt(&transaction)];
+                    if let Some(customer) = self
+                        .customers
+                        .iter_mut()
+                        .find(|customer| transaction.customer.as_ref() == Some(&customer.phone))
+                    {
+                        // Covered by the balance, as checked before the last tender
+                        if let Some(left) = customer
+                            .points
+                            .checked_sub(customer::redeemed(&transaction))
+                        {
+                            customer.points =
+                                left + customer::earned(&transaction, self.setting.baht_per_point);
+                            tasks.push(self.save_customers());
+                        }
+                    }
+                    let charged: u32 = transaction
+                        .tenders
+                        .iter()
+                        .filter(|tender| tender.method == PaymentMethod::Credit)
+                        .map(|tender| tender.amount)
+                        .sum();
+                    if let Some(account) = self
+                        .accounts
+                        .iter_mut()
+                        .find(|credit| charged > 0 && account.as_ref() == Some(&credit.name))
+                    {
+                        account.charge(
+                            chrono::Local::now().date_naive(),
+                            transaction.number,
+                            charged,
+                        );
+                        tasks.push(self.save_accounts());
+                    }
+                    // The journal goes only once the sale is on record, so a
+                    // crash in between leaves it to restore rather than lost
+                    let cleared = self.clear_journal();
+                    tasks.push(
+                        self.append_to("transactions.jsonl", transaction.clone())
+                            .chain(cleared),
+                    );
+                    self.transactions.push(transaction.clone());
+                    tasks.push(self.send_transaction(transaction));
+                    task = Task::batch(tasks);
+                }
+                MessageSale::LineSelected(index) => sale.selected = Some(index),
+                MessageSale::VoidLine => {
+                    if !approve(self.user.as_ref(), Permission::Void) {
+                        return task;
+                    }
+                    let Some(index) = sale.selected.filter(|index| *index < sale.items.len())
+                    else {
+                        return task;
+                    };
+                    let item = sale.items.remove(index);
+                    // Line discounts follow their line
+                    sale.manual
+                        .retain(|manual| manual.target != Target::Line(index));
+                    for manual in sale.manual.iter_mut() {
+                        if let Target::Line(line) = &mut manual.target {
+                            if *line > index {
+                                *line -= 1;
+                            }
+                        }
+                    }
+                    sale.selected = None;
+                    sale.reprice(
+                        &self.promotions,
+                        self.setting.vat_rate,
+                        self.setting.discount_limit,
+                    );
+                    task = Task::batch([
+                        record(
+                            &mut self.audit,
+                            &self.user,
+                            &self.training,
+                            &self.writes,
+                            &self.data,
+                            Action::VoidLine {
+                                barcode: item.barcode,
+                                amount: item.amount,
+                                sum: item.sum,
+                                approver: Some(user_name(&self.user)),
+                            },
+                        ),
+                        focus("barcode"),
+                    ]);
+                }
+                MessageSale::VoidSale => {
+                    if sale.items.is_empty() || !approve(self.user.as_ref(), Permission::Void) {
+                        return task;
+                    }
+                    let recorded = record(
+                        &mut self.audit,
+                        &self.user,
+                        &self.training,
+                        &self.writes,
+                        &self.data,
+                        Action::VoidSale {
+                            items: sale.items.len(),
+                            total: sale.total,
+                            approver: Some(user_name(&self.user)),
+                        },
+                    );
+                    self.pages = Pages::Sale(Sale::default());
+                    task = Task::batch([recorded, focus("barcode")]);
+                }
+                MessageSale::ToDiscount(target) => {
+                    sale.discount = Some(DiscountForm {
+                        target,
+                        ..DiscountForm::default()
+                    });
+                    task = focus("discount");
+                }
+                MessageSale::DiscountPercent(percent) => {
+                    if let Some(form) = &mut sale.discount {
+                        form.percent = percent;
+                    }
+                }
+                MessageSale::DiscountValueChanged(value) => {
+                    if let Some(form) = &mut sale.discount {
+                        form.value = value;
+                    }
+                }
+                MessageSale::DiscountReasonSelected(reason) => {
+                    if let Some(form) = &mut sale.discount {
+                        form.reason = Some(reason);
+                    }
+                }
+                MessageSale::DiscountPinChanged(pin) => {
+                    if let Some(form) = &mut sale.discount {
+                        // The check under way is for the PIN before
+                        form.checking = None;
+                        form.pin = pin;
+                    }
+                }
+                MessageSale::DiscountApproved(request, None) => {
+                    if let Some(form) = sale
+                        .discount
+                        .as_mut()
+                        .filter(|form| form.checking == Some(request))
+                    {
+                        form.checking = None;
+                        form.error = Some("รหัสผู้อนุมัติไม่ถูกต้อง");
+                    }
+                }
+                approval @ (MessageSale::ApplyDiscount
+                | MessageSale::DiscountApproved(_, Some(_))) => {
+                    let Some(form) = &mut sale.discount else {
+                        return task;
+                    };
+                    let (approved, approver) = match approval {
+                        // For a form since closed, or a PIN since changed
+                        MessageSale::DiscountApproved(request, _)
+                            if form.checking != Some(request) =>
+                        {
+                            return task
+                        }
+                        // For what the sale comes to now
+                        MessageSale::DiscountApproved(_, approver) => {
+                            form.checking = None;
+                            (None, approver)
+                        }
+                        _ if approve(self.user.as_ref(), Permission::Discount) => {
+                            (Some(u32::MAX), Some(user_name(&self.user)))
+                        }
+                        _ => (Some(sale.approved), None),
+                    };
+                    let value = match (form.value.parse::<u32>(), form.percent) {
+                        (Ok(value), true) if value > 0 && value <= 100 => Value::Percent(value),
+                        (Ok(value), false) if value > 0 => Value::Amount(value),
+                        _ => {
+                            form.error = Some("ส่วนลดไม่ถูกต้อง");
+                            return task;
+                        }
+                    };
+                    let Some(reason) = form.reason else {
+                        form.error = Some("กรุณาเลือกเหตุผล");
+                        return task;
+                    };
+                    sale.manual.push(Manual {
+                        target: form.target,
+                        value,
+                        reason,
+                    });
+                    sale.reprice(
+                        &self.promotions,
+                        self.setting.vat_rate,
+                        self.setting.discount_limit,
+                    );
+
+                    // The limit is on all the discounts keyed in, so it can't
+                    // be got round a discount at a time
+                    let allowed = approved.unwrap_or(sale.requested);
+                    if sale.requested > allowed.max(self.setting.discount_limit) {
+                        sale.manual.pop();
+                        sale.reprice(
+                            &self.promotions,
+                            self.setting.vat_rate,
+                            self.setting.discount_limit,
+                        );
+                        let Some(form) = &mut sale.discount else {
+                            return task;
+                        };
+                        if form.pin.is_empty() {
+                            form.error = Some("ส่วนลดเกินวงเงิน กรุณาใส่รหัสผู้อนุมัติ");
+                            return task;
+                        }
+                        // Applied again once the PIN checks out
+                        form.error = None;
+                        self.requests += 1;
+                        form.checking = Some(self.requests);
+                        return check(
+                            &self.users,
+                            Permission::Discount,
+                            &form.pin,
+                            self.requests,
+                            |request, approver| {
+                                Message::Sale(MessageSale::DiscountApproved(request, approver))
+                            },
+                        );
+                    }
+                    sale.approved = allowed;
+                    sale.reprice(
+                        &self.promotions,
+                        self.setting.vat_rate,
+                        self.setting.discount_limit,
+                    );
+                    sale.discount = None;
                     task = focus("barcode");
+                    if let Some(approver) =
+                        approver.filter(|_| sale.requested > self.setting.discount_limit)
+                    {
+                        task = Task::batch([
+                            task,
+                            record(
+                                &mut self.audit,
+                                &self.user,
+                                &self.training,
+                                &self.writes,
+                                &self.data,
+                                Action::Discount {
+                                    total: sale.requested,
+                                    limit: self.setting.discount_limit,
+                                    approver,
+                                },
+                            ),
+                        ]);
+                    }
+                }
+                MessageSale::ToOverride(line) => {
+                    sale.price_override = Some(OverrideForm {
+                        line,
+                        ..OverrideForm::default()
+                    });
+                    task = focus("override");
+                }
+                MessageSale::OverridePriceChanged(price) => {
+                    if let Some(form) = &mut sale.price_override {
+                        form.price = price;
+                    }
+                }
+                MessageSale::OverrideReasonSelected(reason) => {
+                    if let Some(form) = &mut sale.price_override {
+                        form.reason = Some(reason);
+                    }
+                }
+                MessageSale::OverridePinChanged(pin) => {
+                    if let Some(form) = &mut sale.price_override {
+                        form.checking = None;
+                        form.pin = pin;
+                    }
+                }
+                MessageSale::PriceCheckToggled(price_check) => {
+                    if let Some(form) = &mut sale.price_override {
+                        form.price_check = price_check;
+                    }
+                }
+                MessageSale::OverrideApproved(request, None) => {
+                    if let Some(form) = sale
+                        .price_override
+                        .as_mut()
+                        .filter(|form| form.checking == Some(request))
+                    {
+                        form.checking = None;
+                        form.error = Some("รหัสผู้อนุมัติไม่ถูกต้อง");
+                    }
+                }
+                approval @ (MessageSale::ApplyOverride
+                | MessageSale::OverrideApproved(_, Some(_))) => {
+                    let Some(form) = &mut sale.price_override else {
+                        return task;
+                    };
+                    let approver = match approval {
+                        MessageSale::OverrideApproved(request, _)
+                            if form.checking != Some(request) =>
+                        {
+                            return task
+                        }
+                        MessageSale::OverrideApproved(_, approver) => {
+                            form.checking = None;
+                            approver
+                        }
+                        _ => approve(self.user.as_ref(), Permission::PriceOverride)
+                            .then(|| user_name(&self.user)),
+                    };
+                    let Some(item) = sale.items.get_mut(form.line) else {
+                        return task;
+                    };
+                    let Some(price) = form.price.parse::<u32>().ok().filter(|price| *price > 0)
+                    else {
+                        form.error = Some("ราคาไม่ถูกต้อง");
+                        return task;
+                    };
+                    let Some(reason) = form.reason else {
+                        form.error = Some("กรุณาเลือกเหตุผล");
+                        return task;
+                    };
+                    let Some(approver) = approver else {
+                        if form.pin.is_empty() {
+                            form.error = Some("รหัสผู้อนุมัติไม่ถูกต้อง");
+                            return task;
+                        }
+                        form.error = None;
+                        self.requests += 1;
+                        form.checking = Some(self.requests);
+                        return check(
+                            &self.users,
+                            Permission::PriceOverride,
+                            &form.pin,
+                            self.requests,
+                            |request, approver| {
+                                Message::Sale(MessageSale::OverrideApproved(request, approver))
+                            },
+                        );
+                    };
+
+                    let recorded = record(
+                        &mut self.audit,
+                        &self.user,
+                        &self.training,
+                        &self.writes,
+                        &self.data,
+                        Action::PriceOverride {
+                            barcode: item.barcode.clone(),
+                            old: item.price,
+                            new: price,
+                            reason,
+                            approver: Some(approver),
+                        },
+                    );
+                    item.price = price;
+                    item.sum = item.vat.charge(
+                        price,
+                        item.amount.parse::<u32>().unwrap_or(0),
+                        self.setting.vat_rate,
+                    );
+                    let price_check = form.price_check.then(|| item.barcode.clone());
+                    sale.price_override = None;
+                    sale.reprice(
+                        &self.promotions,
+                        self.setting.vat_rate,
+                        self.setting.discount_limit,
+                    );
+
+                    task = Task::batch([recorded, focus("barcode")]);
+                    if let Some(barcode) = price_check {
+                        task = Task::batch([task, self.send_price_check(barcode, price)]);
+                    }
+                }
+                MessageSale::PhoneChanged(phone) => sale.phone = phone,
+                // Points already tendered belong to the member attached
+                MessageSale::PhoneSubmit if !sale.payments.is_empty() => {
+                    sale.customer_error = Some("เปลี่ยนสมาชิกไม่ได้หลังรับชำระแล้ว");
+                }
+                MessageSale::PhoneSubmit => match find(&self.customers, &sale.phone) {
+                    Some(customer) => {
+                        sale.customer = Some(customer.phone.clone());
+                        sale.customer_error = None;
+                        task = focus("barcode");
+                    }
+                    None => {
+                        sale.customer = None;
+                        sale.customer_error = Some("ไม่พบสมาชิก");
+                    }
+                },
+                MessageSale::AccountSelected(name) => {
+                    // Credit tendered stays on the account it was checked against
+                    if sale.charged() > 0 {
+                        return task;
+                    }
+                    sale.account = Some(name);
+                    task = focus("received");
+                }
+                MessageSale::Back => {
+                    if sale.discount.is_some() || sale.price_override.is_some() {
+                        sale.discount = None;
+                        sale.price_override = None;
+                        task = focus("barcode");
+                    } else {
+                        self.pages = Pages::Main;
+                    }
                 }
-                MessageSale::Back => self.pages = Pages::Main,
             },
             (Pages::Stock(stock), Message::Stock(message_stock)) => match message_stock {
                 MessageStock::ToAddProduct => {
                     self.pages = Pages::Stock(Stock::AddProduct(pages::stock::Item::default()))
                 }
                 MessageStock::BarcodeChanged(input) => {
-                    if let pages::stock::Stock::AddProduct(stock) = stock {
+                    if let Stock::AddProduct(stock) = stock {
                         stock.barcode = input;
                     }
                 }
+                MessageStock::NameChanged(input) => {
+                    if let Stock::AddProduct(stock) = stock {
+                        stock.name = input;
+                    }
+                }
+                MessageStock::PriceChanged(input) => {
+                    if let Stock::AddProduct(stock) = stock {
+                        stock.price = input;
+                    }
+                }
+                MessageStock::VatSelected(vat) => {
+                    if let Stock::AddProduct(stock) = stock {
+                        stock.vat = vat;
+                    }
+                }
+                MessageStock::Save => {
+                    let Stock::AddProduct(item) = stock else {
+                        return task;
+                    };
+                    if !approve(self.user.as_ref(), Permission::Stock) || item.saving {
+                        return task;
+                    }
+                    let barcode = item.barcode.trim().to_string();
+                    let name = item.name.trim().to_string();
+                    let Ok(price) = item.price.trim().parse::<u32>() else {
+                        item.result = Some(Err("ราคาไม่ถูกต้อง".to_string()));
+                        return task;
+                    };
+                    if barcode.is_empty() || name.is_empty() {
+                        item.result = Some(Err("กรอกรหัสและชื่อสินค้า".to_string()));
+                        return task;
+                    }
+                    item.saving = true;
+                    let vat = item.vat;
+                    task = Task::perform(
+                        api::add_product(self.backend(), barcode.clone(), name, price, vat),
+                        move |result| {
+                            Message::ProductSaved(
+                                barcode.clone(),
+                                result.map_err(|error| error.to_string()),
+                            )
+                        },
+                    );
+                }
                 MessageStock::Back => self.pages = Pages::Main,
             },
-            (Pages::Setting, Message::Setting(message_setting)) => match message_setting {
-                MessageSetting::DatabaseChanged(database) => self.setting.database_url = database,
-                MessageSetting::ToDatabaseSubmitButton => {}
-                MessageSetting::DatabaseSubmit => {
-                    let _ = self.setting.set_setting();
+            (Pages::Drawer(drawer), Message::Drawer(message_drawer)) => match message_drawer {
+                MessageDrawer::FloatChanged(input) => {
+                    if let Drawer::Open { float } = drawer {
+                        *float = input;
+                    }
                 }
-                MessageSetting::Back => self.pages = Pages::Main,
-            },
-            _ => {
-                panic!();
-            }
-        }
-        task
-    }
-
-    fn view(&self) -> Element<Message> {
-        // View start
-        match &self.pages {
-            Pages::Main => self.main_page(),
-            Pages::Sale(sale) => self.sale_page(sale),
-            Pages::Stock(stock) => self.stock_page(stock),
-            Pages::Setting => self.setting_page(),
-        }
-    }
-
-    fn subscription(&self) -> Subscription<Message> {
-        match &self.pages {
-            Pages::Main => keyboard::on_key_release(|_, _| None),
-            Pages::Sale(sale) => match !sale.paying {
-                true => keyboard::on_key_release(|key, _| match key {
-                    Key::Named(Named::Escape) => Some(Message::Sale(MessageSale::Back)),
-                    Key::Named(Named::F12) => Some(Message::Sale(MessageSale::EnterPay)),
-                    _ => None,
-                }),
-                false => keyboard::on_key_release(|key, _| match key {
-                    Key::Named(Named::Escape) => Some(Message::Sale(MessageSale::Back)),
-                    Key::Named(Named::F12) => Some(Message::Sale(MessageSale::ExitPay)),
+                MessageDrawer::Open => {
+                    if let Drawer::Open { float } = drawer {
+                        if let Ok(float) = float.parse::<u32>() {
+                            let cashier = user_name(&self.user);
+                            if !cashier.is_empty() {
+                                self.session = Some(Session::open(cashier.clone(), float));
+                                task = record(
+                                    &mut self.audit,
+                                    &self.user,
+                                    &self.training,
+                                    &self.writes,
+                                    &self.data,
+                                    Action::DrawerOpen { cashier, float },
+                                );
+                                *drawer = Drawer::Session {
+                                    amount: "".to_string(),
+                                    note: "".to_string(),
+                                };
+                            }
+                        }
+                    }
+                }
+                MessageDrawer::AmountChanged(input) => {
+                    if let Drawer::Session { amount, .. } = drawer {
+                        *amount = input;
+                    }
+                }
+                MessageDrawer::NoteChanged(input) => {
+                    if let Drawer::Session { note, .. } = drawer {
+                        *note = input;
+                    }
+                }
+                MessageDrawer::CashIn | MessageDrawer::CashOut => {
+                    if let (Drawer::Session { amount, note }, Some(session)) =
+                        (&mut *drawer, &mut self.session)
+                    {
+                        if let Ok(value) = amount.parse::<u32>() {
+                            if value > 0 {
+                                let action = match message_drawer {
+                                    MessageDrawer::CashIn => {
+                                        session.cash_in(value, note.clone());
+                                        Action::CashIn {
+                                            amount: value,
+                                            note: note.clone(),
+                                        }
+                                    }
+                                    _ => {
+                                        session.cash_out(value, note.clone());
+                                        Action::CashOut {
+                                            amount: value,
+                                            note: note.clone(),
+                                        }
+                                    }
+                                };
+                                task = record(
+                                    &mut self.audit,
+                                    &self.user,
+                                    &self.training,
+                                    &self.writes,
+                                    &self.data,
+                                    action,
+                                );
+                                *amount = "".to_string();
+                                *note = "".to_string();
+                            }
+                        }
+                    }
+                }
+                MessageDrawer::ToCount => {
+                    if self.session.is_some() {
+                        *drawer = Drawer::count();
+                    }
+                }
+                MessageDrawer::CountChanged(index, input) => {
+                    if let Drawer::Count(counts) = drawer {
+                        if let Some(count) = counts.get_mut(index) {
+                            *count = input;
+                        }
+                    }
+                }
+                MessageDrawer::Close => {
+                    if let Drawer::Count(counts) = drawer {
+                        let mut count = CashCount::default();
+                        for (index, input) in counts.iter().enumerate() {
+                            match input.parse::<u32>() {
+                                Ok(value) => count.0[index] = value,
+                                Err(_) => return task,
+                            }
+                        }
+                        let Some(variance) = self
+                            .session
+                            .as_ref()
+                            .and_then(|session| session.close(&count))
+                        else {
+                            return task;
+                        };
+                        if let Some(session) = self.session.take() {
+                            task = record(
+                                &mut self.audit,
+                                &self.user,
+                                &self.training,
+                                &self.writes,
+                                &self.data,
+                                Action::DrawerClose {
+                                    cashier: session.cashier,
+                                    expected: variance.expected,
+                                    counted: variance.counted,
+                                },
+                            );
+                            *drawer = Drawer::Report(variance);
+                        }
+                    }
+                }
+                MessageDrawer::Back => self.pages = Pages::Main,
+            },
+            (Pages::Refund(refund), Message::Refund(message_refund)) => match message_refund {
+                MessageRefund::ReceiptChanged(input) => refund.receipt_input = input,
+                MessageRefund::ReceiptSubmit => {
+                    let sale = parse_receipt(&refund.receipt_input)
+                        .and_then(|number| find_sale(&self.transactions, number));
+                    *refund = match sale {
+                        Some(sale) => Refund {
+                            receipt_input: refund.receipt_input.clone(),
+                            receipt: Some(sale.number),
+                            lines: sale
+                                .items
+                                .iter()
+                                .zip(returnable(&self.transactions, sale))
+                                .map(|(item, returnable)| refund_page::Line {
+                                    item: item.clone(),
+                                    returnable,
+                                    quantity: "0".to_string(),
+                                })
+                                .collect(),
+                            ..Refund::default()
+                        },
+                        None => Refund {
+                            receipt_input: refund.receipt_input.clone(),
+                            error: Some("ไม่พบใบเสร็จ"),
+                            ..Refund::default()
+                        },
+                    };
+                }
+                MessageRefund::QuantityChanged(index, input) => {
+                    if let Some(line) = refund.lines.get_mut(index) {
+                        line.quantity = input;
+                    }
+                }
+                MessageRefund::ReasonSelected(reason) => refund.reason = Some(reason),
+                MessageRefund::PinChanged(pin) => {
+                    refund.checking = None;
+                    refund.pin = pin;
+                }
+                MessageRefund::Approved(request, None) => {
+                    if refund.checking == Some(request) {
+                        refund.checking = None;
+                        refund.error = Some("รหัสผู้อนุมัติไม่ถูกต้อง");
+                    }
+                }
+                approval @ (MessageRefund::Refund | MessageRefund::Approved(_, Some(_))) => {
+                    let approver = match approval {
+                        MessageRefund::Approved(request, _) if refund.checking != Some(request) => {
+                            return task
+                        }
+                        MessageRefund::Approved(_, approver) => {
+                            refund.checking = None;
+                            approver
+                        }
+                        _ => approve(self.user.as_ref(), Permission::Refund)
+                            .then(|| user_name(&self.user)),
+                    };
+                    let Some(sale) = refund
+                        .receipt
+                        .and_then(|receipt| find_sale(&self.transactions, receipt))
+                    else {
+                        return task;
+                    };
+                    let receipt = sale.number;
+                    let Some(reason) = refund.reason else {
+                        refund.error = Some("กรุณาเลือกเหตุผล");
+                        return task;
+                    };
+                    let Some(approver) = approver else {
+                        if refund.pin.is_empty() {
+                            refund.error = Some("รหัสผู้อนุมัติไม่ถูกต้อง");
+                            return task;
+                        }
+                        refund.error = None;
+                        self.requests += 1;
+                        refund.checking = Some(self.requests);
+                        return check(
+                            &self.users,
+                            Permission::Refund,
+                            &refund.pin,
+                            self.requests,
+                            |request, approver| {
+                                Message::Refund(MessageRefund::Approved(request, approver))
+                            },
+                        );
+                    };
+
+                    let mut items = Vec::new();
+                    let mut lines = Vec::new();
+                    for (index, line) in refund.lines.iter().enumerate() {
+                        match line.quantity.parse::<u32>() {
+                            Ok(0) => {}
+                            Ok(quantity) if quantity <= line.returnable => {
+                                items.push(Item {
+                                    amount: quantity.to_string(),
+                                    sum: line.item.vat.charge(
+                                        line.item.price,
+                                        quantity,
+                                        self.setting.vat_rate,
+                                    ),
+                                    ..line.item.clone()
+                                });
+                                lines.push(index);
+                            }
+                            _ => {
+                                refund.error = Some("จำนวนคืนไม่ถูกต้อง");
+                                return task;
+                            }
+                        }
+                    }
+                    if items.is_empty() {
+                        refund.error = Some("ไม่มีรายการคืน");
+                        return task;
+                    }
+
+                    let total = sale.discounted(items.iter().map(|item| item.sum).sum());
+                    let unearned = customer::unearned(
+                        sale,
+                        refunded(&self.transactions, sale),
+                        total,
+                        self.setting.baht_per_point,
+                    );
+                    let transaction = Transaction {
+                        number: next_receipt(&self.transactions),
+                        total,
+                        kind: Kind::Refund {
+                            receipt,
+                            reason,
+                            lines,
+                        },
+                        discounts: Vec::new(),
+                        tenders: refund_tenders(&self.transactions, sale, total),
+                        change: 0,
+                        items,
+                        customer: sale.customer.clone(),
+                        user: user_name(&self.user),
+                        time: Some(chrono::Local::now().naive_local()),
+                        vat_rate: sale.vat_rate,
+                    };
+                    if let Some(session) = &mut self.session {
+                        session.record_refund(transaction.cash());
+                    }
+                    let mut tasks = Vec::new();
+                    // Points spent come back and points earned on the goods go
+                    if let Some(customer) = self
+                        .customers
+                        .iter_mut()
+                        .find(|customer| transaction.customer.as_ref() == Some(&customer.phone))
+                    {
+                        customer.points = (customer.points + customer::redeemed(&transaction))
+                            .saturating_sub(unearned);
+                        tasks.push(self.save_customers());
+                    }
+                    let credited: u32 = transaction
+                        .tenders
+                        .iter()
+                        .filter(|tender| tender.method == PaymentMethod::Credit)
+                        .map(|tender| tender.amount)
+                        .sum();
+                    if let Some(account) = self
+                        .accounts
+                        .iter_mut()
+                        .find(|account| credited > 0 && account.charged(receipt))
+                    {
+                        account.refund(chrono::Local::now().date_naive(), receipt, credited);
+                        tasks.push(self.save_accounts());
+                    }
+                    self.transactions.push(transaction.clone());
+                    self.pages = Pages::Refund(Refund::default());
+                    task = Task::batch(tasks.into_iter().chain([
+                        self.append_to("transactions.jsonl", transaction.clone()),
+                        record(
+                            &mut self.audit,
+                            &self.user,
+                            &self.training,
+                            &self.writes,
+                            &self.data,
+                            Action::Refund {
+                                receipt,
+                                total: transaction.total,
+                                approver: Some(approver),
+                            },
+                        ),
+                        focus("receipt"),
+                        self.print_receipt(&transaction),
+                        self.send_refund(transaction),
+                    ]));
+                }
+                MessageRefund::Back => self.pages = Pages::Main,
+            },
+            (Pages::Report(report), Message::Report(message_report)) => match message_report {
+                MessageReport::PeriodSelected(period) => {
+                    *report = Report::new(&self.transactions, report.day, period);
+                }
+                MessageReport::Previous | MessageReport::Next => {
+                    let back = matches!(message_report, MessageReport::Previous);
+                    if let Some(day) = report.period.step(report.day, back) {
+                        *report = Report::new(&self.transactions, day, report.period);
+                    }
+                }
+                MessageReport::Back => self.pages = Pages::Main,
+            },
+            (Pages::Invoice(invoice), Message::Invoice(message_invoice)) => match message_invoice {
+                MessageInvoice::ReceiptChanged(input) => invoice.receipt = input,
+                MessageInvoice::NameChanged(input) => invoice.buyer.name = input,
+                MessageInvoice::AddressChanged(input) => invoice.buyer.address = input,
+                MessageInvoice::TaxIdChanged(input) => invoice.buyer.tax_id = input,
+                MessageInvoice::BranchChanged(input) => invoice.buyer.branch = input,
+                MessageInvoice::Issue => {
+                    if self.issuing.is_some() {
+                        return task;
+                    }
+                    let Some(sale) = parse_receipt(&invoice.receipt)
+                        .and_then(|number| find_sale(&self.transactions, number))
+                    else {
+                        invoice.error = Some("ไม่พบใบเสร็จ");
+                        return task;
+                    };
+                    if self
+                        .invoices
+                        .iter()
+                        .any(|issued| issued.receipt == sale.number)
+                    {
+                        invoice.error = Some("ใบเสร็จนี้ออกใบกำกับภาษีแล้ว");
+                        return task;
+                    }
+                    if invoice.buyer.name.trim().is_empty() {
+                        invoice.error = Some("กรุณากรอกชื่อผู้ซื้อ");
+                        return task;
+                    }
+                    if !valid_tax_id(&invoice.buyer.tax_id) {
+                        invoice.error = Some("เลขประจำตัวผู้เสียภาษีไม่ถูกต้อง");
+                        return task;
+                    }
+                    if invoice.buyer.branch.is_empty() {
+                        invoice.buyer.branch = "00000".to_string();
+                    }
+                    if invoice.buyer.branch.len() != 5
+                        || !invoice.buyer.branch.chars().all(|c| c.is_ascii_digit())
+                    {
+                        invoice.error = Some("เลขที่สาขาต้องเป็นตัวเลข 5 หลัก");
+                        return task;
+                    }
+
+                    let tax_invoice = TaxInvoice {
+                        number: next_number(&self.invoices, &self.setting.branch),
+                        issued: chrono::Local::now().naive_local(),
+                        receipt: sale.number,
+                        buyer: invoice.buyer.clone(),
+                        items: sale.items.clone(),
+                        total: sale.total,
+                        vat_rate: sale.vat_rate,
+                    };
+                    invoice.failed = None;
+                    invoice.issued = None;
+                    if practice(&self.training, &self.setting) {
+                        *invoice = Invoice {
+                            issued: Some(tax_invoice.number.clone()),
+                            ..Invoice::default()
+                        };
+                        self.invoices.push(tax_invoice);
+                        return task;
+                    }
+                    self.requests += 1;
+                    invoice.saving = Some(self.requests);
+                    task = self.save_invoice(&tax_invoice, self.requests);
+                    self.issuing = Some(tax_invoice);
+                }
+                MessageInvoice::Back => self.pages = Pages::Main,
+            },
+            (Pages::Customer(page), Message::Customer(message_customer)) => {
+                match message_customer {
+                    MessageCustomer::PhoneChanged(phone) => page.phone = phone,
+                    MessageCustomer::Search => {
+                        *page = match find(&self.customers, &page.phone) {
+                            Some(customer) => CustomerPage {
+                                phone: page.phone.clone(),
+                                found: Some(customer.phone.clone()),
+                                ..CustomerPage::default()
+                            },
+                            None => CustomerPage {
+                                phone: page.phone.clone(),
+                                error: Some("ไม่พบสมาชิก"),
+                                ..CustomerPage::default()
+                            },
+                        };
+                    }
+                    MessageCustomer::NameChanged(name) => page.name = name,
+                    MessageCustomer::Register => {
+                        let Some(phone) = normalize_phone(&page.phone) else {
+                            page.error = Some("เบอร์โทรไม่ถูกต้อง");
+                            return task;
+                        };
+                        if find(&self.customers, &phone).is_some() {
+                            page.error = Some("เป็นสมาชิกอยู่แล้ว");
+                            return task;
+                        }
+                        if page.name.trim().is_empty() {
+                            page.error = Some("กรุณาใส่ชื่อ");
+                            return task;
+                        }
+                        self.customers.push(Customer {
+                            phone: phone.clone(),
+                            name: page.name.trim().to_string(),
+                            points: 0,
+                        });
+                        *page = CustomerPage {
+                            phone: phone.clone(),
+                            found: Some(phone),
+                            ..CustomerPage::default()
+                        };
+                        task = self.save_customers();
+                    }
+                    MessageCustomer::Back => self.pages = Pages::Main,
+                }
+            }
+            (Pages::Users(page), Message::Users(message_users)) => match message_users {
+                MessageUsers::NameChanged(name) => page.name = name,
+                MessageUsers::SecretChanged(secret) => page.secret = secret,
+                MessageUsers::RoleSelected(role) => page.role = role,
+                MessageUsers::Add => {
+                    let name = page.name.trim().to_string();
+                    if name.is_empty() {
+                        page.error = Some("กรุณาใส่ชื่อ");
+                        return task;
+                    }
+                    if self.users.iter().any(|user| user.name == name) {
+                        page.error = Some("มีผู้ใช้ชื่อนี้อยู่แล้ว");
+                        return task;
+                    }
+                    if page.secret.len() < 4 {
+                        page.error = Some("รหัสผ่านต้องมีอย่างน้อย 4 ตัว");
+                        return task;
+                    }
+                    let action = Action::UserAdded {
+                        name: name.clone(),
+                        role: page.role,
+                    };
+                    let Ok(user) = User::new(name, page.role, &page.secret) else {
+                        page.error = Some("ตั้งรหัสผ่านไม่สำเร็จ");
+                        return task;
+                    };
+                    self.users.push(user);
+                    *page = Users::default();
+                    task = Task::batch([
+                        Task::perform(
+                            save_users(self.users.clone(), self.file("users.json")),
+                            Message::Saved,
+                        ),
+                        record(
+                            &mut self.audit,
+                            &self.user,
+                            &self.training,
+                            &self.writes,
+                            &self.data,
+                            action,
+                        ),
+                    ]);
+                }
+                MessageUsers::Back => self.pages = Pages::Main,
+            },
+            (Pages::Credit(page), Message::Credit(message_credit)) => match message_credit {
+                MessageCredit::NameChanged(name) => page.name = name,
+                MessageCredit::LimitChanged(limit) => page.limit = limit,
+                MessageCredit::Open => {
+                    if !approve(self.user.as_ref(), Permission::Credit) {
+                        page.error = Some("ไม่มีสิทธิ์เปิดบัญชี");
+                        return task;
+                    }
+                    let name = page.name.trim().to_string();
+                    if name.is_empty() {
+                        page.error = Some("กรุณาใส่ชื่อ");
+                        return task;
+                    }
+                    if credit::find(&self.accounts, &name).is_some() {
+                        page.error = Some("มีบัญชีชื่อนี้อยู่แล้ว");
+                        return task;
+                    }
+                    let Ok(limit) = page.limit.parse::<u32>() else {
+                        page.error = Some("วงเงินไม่ถูกต้อง");
+                        return task;
+                    };
+                    self.accounts.push(Account::open(name.clone(), limit));
+                    *page = CreditPage {
+                        selected: Some(name.clone()),
+                        ..CreditPage::default()
+                    };
+                    task = Task::batch([
+                        self.save_accounts(),
+                        record(
+                            &mut self.audit,
+                            &self.user,
+                            &self.training,
+                            &self.writes,
+                            &self.data,
+                            Action::CreditOpened { name, limit },
+                        ),
+                    ]);
+                }
+                MessageCredit::NewLimitChanged(limit) => page.new_limit = limit,
+                MessageCredit::ChangeLimit => {
+                    if !approve(self.user.as_ref(), Permission::Credit) {
+                        page.error = Some("ไม่มีสิทธิ์เปลี่ยนวงเงิน");
+                        return task;
+                    }
+                    let Some(account) = self
+                        .accounts
+                        .iter_mut()
+                        .find(|account| page.selected.as_ref() == Some(&account.name))
+                    else {
+                        return task;
+                    };
+                    let Ok(limit) = page.new_limit.parse::<u32>() else {
+                        page.error = Some("วงเงินไม่ถูกต้อง");
+                        return task;
+                    };
+                    let action = Action::CreditLimit {
+                        name: account.name.clone(),
+                        old: account.limit,
+                        new: limit,
+                    };
+                    account.limit = limit;
+                    page.new_limit = "".to_string();
+                    page.error = None;
+                    task = Task::batch([
+                        self.save_accounts(),
+                        record(
+                            &mut self.audit,
+                            &self.user,
+                            &self.training,
+                            &self.writes,
+                            &self.data,
+                            action,
+                        ),
+                    ]);
+                }
+                MessageCredit::Selected(name) => {
+                    *page = CreditPage {
+                        selected: Some(name),
+                        ..CreditPage::default()
+                    };
+                    task = focus("repayment");
+                }
+                MessageCredit::RepaymentChanged(repayment) => page.repayment = repayment,
+                MessageCredit::Repay => {
+                    let Some(account) = self
+                        .accounts
+                        .iter_mut()
+                        .find(|account| page.selected.as_ref() == Some(&account.name))
+                    else {
+                        return task;
+                    };
+                    let Some(amount) = page
+                        .repayment
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|amount| *amount > 0 && *amount <= account.balance())
+                    else {
+                        page.error = Some("จำนวนเงินไม่ถูกต้อง");
+                        return task;
+                    };
+                    account.pay(chrono::Local::now().date_naive(), amount);
+                    // Repayments are taken in cash
+                    if let Some(session) = &mut self.session {
+                        session.cash_in(amount, format!("ชำระค้างจ่าย {}", account.name));
+                    }
+                    let action = Action::Repayment {
+                        name: account.name.clone(),
+                        amount,
+                    };
+                    page.repayment = "".to_string();
+                    page.error = None;
+                    task = Task::batch([
+                        self.save_accounts(),
+                        record(
+                            &mut self.audit,
+                            &self.user,
+                            &self.training,
+                            &self.writes,
+                            &self.data,
+                            action,
+                        ),
+                    ]);
+                }
+                MessageCredit::ToAging => {
+                    *page = CreditPage {
+                        aging: true,
+                        ..CreditPage::default()
+                    };
+                }
+                MessageCredit::Back => self.pages = Pages::Main,
+            },
+            // Kept whatever page it finishes on, and shown on the one it was
+            // issued from if still open
+            (page, Message::InvoiceSaved(request, result)) => {
+                let Some(tax_invoice) = self.issuing.take() else {
+                    return task;
+                };
+                let invoice = match page {
+                    Pages::Invoice(invoice) if invoice.saving == Some(request) => {
+                        invoice.saving = None;
+                        Some(invoice)
+                    }
                     _ => None,
-                }),
+                };
+                match result {
+                    Ok(()) => {
+                        if let Some(invoice) = invoice {
+                            *invoice = Invoice {
+                                issued: Some(tax_invoice.number.clone()),
+                                ..Invoice::default()
+                            };
+                        }
+                        task = self.append_to("invoices.jsonl", tax_invoice.clone());
+                        self.invoices.push(tax_invoice);
+                    }
+                    // The number stays free for the next try
+                    Err(error) => match invoice {
+                        Some(invoice) => invoice.failed = Some(error),
+                        None => self.error = Some(format!("บันทึกใบกำกับภาษีไม่สำเร็จ: {}", error)),
+                    },
+                }
+            }
+            (page, Message::ProductSaved(barcode, result)) => match result {
+                Ok(()) => {
+                    if let Pages::Stock(Stock::AddProduct(item)) = page {
+                        *item = pages::stock::Item {
+                            result: Some(Ok(barcode)),
+                            ..pages::stock::Item::default()
+                        };
+                    }
+                    task = self.sync_products(self.catalogue.cursor);
+                }
+                Err(error) => match page {
+                    Pages::Stock(Stock::AddProduct(item)) => {
+                        item.saving = false;
+                        item.result = Some(Err(format!("บันทึกสินค้าไม่สำเร็จ: {}", error)));
+                    }
+                    _ => self.error = Some(format!("บันทึกสินค้า {} ไม่สำเร็จ: {}", barcode, error)),
+                },
             },
-            Pages::Stock(stock) => keyboard::on_key_release(|key, _| match key {
-                Key::Named(Named::Escape) => Some(Message::Stock(MessageStock::Back)),
-                _ => None,
-            }),
-            Pages::Setting => keyboard::on_key_release(|key, _| match key {
-                Key::Named(Named::Escape) => Some(Message::Setting(MessageSetting::Back)),
-                _ => None,
-            }),
+            // Shown whichever page they finish on
+            (_, Message::Api(Err(error))) => {
+                self.error = Some(format!("ส่งข้อมูลไปยังเซิร์ฟเวอร์ไม่สำเร็จ: {}", error));
+            }
+            (_, Message::Printed(Err(error))) => {
+                self.error = Some(format!("พิมพ์ใบเสร็จไม่สำเร็จ: {}", error));
+            }
+            (_, Message::Saved(Err(error))) => {
+                self.error = Some(format!("บันทึกข้อมูลไม่สำเร็จ: {}", error));
+            }
+            (_, Message::Api(Ok(())) | Message::Printed(Ok(())) | Message::Saved(Ok(()))) => {}
+            (_, Message::DismissError) => self.error = None,
+            (_, Message::CheckConnection) => task = self.check_connection(),
+            (_, Message::Sync) => task = self.sync_products(self.catalogue.cursor),
+            (page, Message::Synced(url, since, result)) => {
+                // Products of a backend since left behind
+                if url != self.setting.database_url() {
+                    return task;
+                }
+                if let Pages::Setting(page) = page {
+                    if since == 0 {
+                        page.resyncing = false;
+                    }
+                }
+                match result {
+                    Ok(changes) => {
+                        let changed = since == 0
+                            || !changes.products.is_empty()
+                            || !changes.removed.is_empty();
+                        if self.catalogue.apply(since, changes, chrono::Local::now()) && changed {
+                            task = Task::perform(
+                                save_catalogue(self.catalogue.clone(), self.file("products.json")),
+                                Message::Saved,
+                            );
+                        }
+                    }
+                    Err(error) => self.error = Some(format!("ซิงค์สินค้าไม่สำเร็จ: {}", error)),
+                }
+            }
+            (page, Message::Health(url, result)) => {
+                if url == self.setting.database_url() {
+                    self.failures = match result {
+                        Ok(_) => 0,
+                        Err(_) => self.failures.saturating_add(1),
+                    };
+                    self.connection = Some(result.clone());
+                }
+                if let Pages::Setting(page) = page {
+                    if page.probe == Some(Probe::Testing(url.clone())) {
+                        page.probe = Some(Probe::Done(url, result));
+                    }
+                }
+            }
+            // The export may finish after the viewer is closed, or reopened
+            (page, Message::Audit(MessageAudit::Exported(request, result))) => match page {
+                Pages::Audit(page) if page.exporting == Some(request) => {
+                    page.exporting = None;
+                    page.exported = Some(result);
+                }
+                _ => {
+                    if let Err(error) = result {
+                        self.error = Some(format!("ส่งออกบันทึกไม่สำเร็จ: {}", error));
+                    }
+                }
+            },
+            (Pages::Audit(page), Message::Audit(message_audit)) => match message_audit {
+                MessageAudit::UserSelected(user) => page.user = Some(user),
+                MessageAudit::ActionSelected(action) => page.action = Some(action),
+                MessageAudit::FromChanged(from) => page.from = from,
+                MessageAudit::ToChanged(to) => page.to = to,
+                MessageAudit::Clear => *page = AuditPage::default(),
+                MessageAudit::Export => {
+                    let entries: Vec<Entry> = page.filter(&self.audit).cloned().collect();
+                    // Beside the records rather than wherever the till was started
+                    let path = self.data.join(format!(
+                        "audit-{}.csv",
+                        chrono::Local::now().format("%Y%m%d-%H%M%S")
+                    ));
+                    self.requests += 1;
+                    let request = self.requests;
+                    page.exporting = Some(request);
+                    task = Task::perform(export(entries, path), move |result| {
+                        Message::Audit(MessageAudit::Exported(request, result))
+                    });
+                }
+                MessageAudit::Exported(..) => {}
+                MessageAudit::Back => self.pages = Pages::Main,
+            },
+            (Pages::Setting(page), Message::Setting(message_setting)) => match message_setting {
+                MessageSetting::SectionSelected(section) => page.section = section,
+                MessageSetting::Changed(field, input) => *page.input_mut(field) = input,
+                MessageSetting::PrinterSelected(printer) => page.printer = printer,
+                MessageSetting::LanguageSelected(language) => page.language = language,
+                MessageSetting::AppearanceSelected(appearance) => page.appearance = appearance,
+                MessageSetting::ProfileSelected(name) => {
+                    let index = page
+                        .profiles
+                        .iter()
+                        .position(|profile| profile.name == name);
+                    if let Some(Err((field, error))) = index.map(|index| page.select(index)) {
+                        page.error = Some(error);
+                        task = focus(field.label());
+                    }
+                }
+                MessageSetting::AddProfile => match page.add_profile() {
+                    Ok(()) => task = focus(Field::ProfileName.label()),
+                    Err((field, error)) => {
+                        page.error = Some(error);
+                        task = focus(field.label());
+                    }
+                },
+                MessageSetting::RemoveProfile => page.remove_profile(),
+                MessageSetting::ProductionToggled(production) => {
+                    page.profiles[page.profile].production = production
+                }
+                MessageSetting::Resync => {
+                    page.resyncing = true;
+                    task = self.sync_products(0);
+                }
+                MessageSetting::Defaults => page.reset(page.section),
+                MessageSetting::TestConnection => match parse_url(page.input(Field::DatabaseUrl)) {
+                    Ok(url) => {
+                        page.error = None;
+                        page.probe = Some(Probe::Testing(url.clone()));
+                        task = Task::perform(api::health(url.clone()), move |result| {
+                            Message::Health(url.clone(), result)
+                        });
+                    }
+                    Err(error) => {
+                        page.probe = None;
+                        page.error = Some(error);
+                    }
+                },
+                MessageSetting::UseConnection => {
+                    if let Some(Probe::Done(url, Ok(health))) = page.probe.clone() {
+                        *page.input_mut(Field::DatabaseUrl) = url;
+                        let (profiles, profile) = match page.profiles() {
+                            Ok(profiles) => profiles,
+                            Err((field, error)) => {
+                                page.error = Some(error);
+                                return focus(field.label());
+                            }
+                        };
+                        let records = self.setting.records(&self.data);
+                        let previous = self.setting.database_url().to_string();
+                        self.setting.profiles = profiles;
+                        self.setting.profile = profile;
+                        self.switch_records(&records);
+                        self.connection = Some(Ok(health));
+                        self.failures = 0;
+                        task = Task::batch([
+                            self.switch_backend(&previous),
+                            Task::perform(
+                                save_setting(
+                                    self.setting.clone(),
+                                    self.writes.queue(self.config.clone()),
+                                ),
+                                Message::Saved,
+                            ),
+                            record(
+                                &mut self.audit,
+                                &self.user,
+                                &self.training,
+                                &self.writes,
+                                &self.data,
+                                Action::SettingChange,
+                            ),
+                        ]);
+                    }
+                }
+                MessageSetting::Save => match page.apply(&self.setting) {
+                    Ok(setting) => {
+                        let moved = setting.database_url() != self.setting.database_url();
+                        let records = self.setting.records(&self.data);
+                        let previous = self.setting.database_url().to_string();
+                        self.setting = setting;
+                        self.switch_records(&records);
+                        // Saving replaces the file that failed to load
+                        self.setting_error = None;
+                        self.pages = Pages::Main;
+                        if moved {
+                            self.connection = None;
+                            self.failures = 0;
+                        }
+                        task = Task::batch([
+                            match moved {
+                                true => self.check_connection(),
+                                false => Task::none(),
+                            },
+                            self.switch_backend(&previous),
+                            Task::perform(
+                                save_setting(
+                                    self.setting.clone(),
+                                    self.writes.queue(self.config.clone()),
+                                ),
+                                Message::Saved,
+                            ),
+                            record(
+                                &mut self.audit,
+                                &self.user,
+                                &self.training,
+                                &self.writes,
+                                &self.data,
+                                Action::SettingChange,
+                            ),
+                        ]);
+                    }
+                    Err((field, error)) => {
+                        page.section = field.section();
+                        page.error = Some(error);
+                        task = focus(field.label());
+                    }
+                },
+                MessageSetting::Cancel => self.pages = Pages::Main,
+            },
+            // Late results and keys released as the page changed
+            (page, message) => log::debug!("ignored {:?} on {} page", message, page.name()),
+        }
+        task
+    }
+
+    fn in_training(&self) -> bool {
+        practice(&self.training, &self.setting)
+    }
+
+    /// Drops the products cached from `previous` if the backend is now
+    /// another, and fetches the new one's from the start.
+    fn switch_backend(&mut self, previous: &str) -> Task<Message> {
+        if self.setting.database_url() == previous {
+            return Task::none();
         }
+        self.catalogue = Catalogue::default();
+        Task::batch([
+            Task::perform(
+                save_catalogue(self.catalogue.clone(), self.file("products.json")),
+                Message::Saved,
+            ),
+            self.sync_products(0),
+        ])
+    }
+
+    /// Reads the sales and tax invoices of the profile in use if it keeps
+    /// them elsewhere than `previous`. Training has the real ones set aside
+    /// and puts them back as they were.
+    fn switch_records(&mut self, previous: &Path) {
+        let records = self.setting.records(&self.data);
+        if records == previous || self.training.is_some() {
+            return;
+        }
+        let (transactions, transactions_error) = read_lines(&records.join("transactions.jsonl"));
+        let (invoices, invoices_error) = read_lines(&records.join("invoices.jsonl"));
+        self.transactions = transactions;
+        self.invoices = invoices;
+        self.records_error = transactions_error.or(invoices_error);
+    }
+
+    /// Sets the real records aside and sends every call to a fake backend
+    /// selling `products`.
+    fn start_training(&mut self, products: Vec<api::Product>) {
+        self.training = Some(Training {
+            backend: Fake::new(products),
+            session: self.session.take(),
+            transactions: std::mem::take(&mut self.transactions),
+            invoices: std::mem::take(&mut self.invoices),
+            customers: self.customers.clone(),
+            accounts: self.accounts.clone(),
+            audit: self.audit.clone(),
+        });
+    }
+
+    /// Puts back the records set aside for training, dropping what was done
+    /// meanwhile.
+    fn end_training(&mut self) {
+        if let Some(training) = self.training.take() {
+            self.session = training.session;
+            self.transactions = training.transactions;
+            self.invoices = training.invoices;
+            self.customers = training.customers;
+            self.accounts = training.accounts;
+            self.audit = training.audit;
+        }
+    }
+
+    /// A write of record `name`, after those already queued.
+    fn file(&self, name: &str) -> Write {
+        self.writes.queue(self.data.join(name))
+    }
+
+    /// Appends `value` to journal `name` of the profile's records after those
+    /// already queued, which training leaves alone.
+    fn append_to<T: serde::Serialize + Send + 'static>(
+        &self,
+        name: &str,
+        value: T,
+    ) -> Task<Message> {
+        match self.training {
+            Some(_) => Task::none(),
+            None => Task::perform(
+                append_line(
+                    value,
+                    self.writes
+                        .append(self.setting.records(&self.data).join(name)),
+                ),
+                Message::Saved,
+            ),
+        }
+    }
+
+    fn save_customers(&self) -> Task<Message> {
+        match self.training {
+            Some(_) => Task::none(),
+            None => Task::perform(
+                save_customers(self.customers.clone(), self.file("customers.json")),
+                Message::Saved,
+            ),
+        }
+    }
+
+    fn save_accounts(&self) -> Task<Message> {
+        match self.training {
+            Some(_) => Task::none(),
+            None => Task::perform(
+                save_accounts(self.accounts.clone(), self.file("accounts.json")),
+                Message::Saved,
+            ),
+        }
+    }
+
+    fn print_receipt(&self, transaction: &Transaction) -> Task<Message> {
+        if self.setting.printer == PrinterTarget::None {
+            return Task::none();
+        }
+        let target = self.setting.printer.clone();
+        let bytes = receipt::render(transaction, &self.setting, self.in_training());
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(move || receipt::print(&target, &bytes))
+                    .await
+                    .unwrap_or_else(|error| Err(std::io::Error::other(error)))
+            },
+            |result: std::io::Result<()>| {
+                Message::Printed(result.map_err(|error| error.to_string()))
+            },
+        )
+    }
+
+    /// Writes the invoice as `<number>.xml` and `<number>.pdf` under
+    /// `setting.invoice_dir` in the data directory, as request `request`.
+    fn save_invoice(&self, invoice: &TaxInvoice, request: u64) -> Task<Message> {
+        let dir = self.setting.invoices(&self.data);
+        let number = invoice.number.clone();
+        let xml = invoice.to_xml(&self.setting);
+        let pdf = invoice.to_pdf(&self.setting);
+        Task::perform(
+            async move { tax_invoice::save(&dir, &number, xml.as_bytes(), &pdf?) },
+            move |result| Message::InvoiceSaved(request, result),
+        )
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        // View start
+        let page = match &self.pages {
+            Pages::Login(login) => self.login_page(login),
+            Pages::Main => self.main_page(),
+            Pages::Sale(sale) => self.sale_page(sale),
+            Pages::Stock(stock) => self.stock_page(stock),
+            Pages::Drawer(drawer) => self.drawer_page(drawer),
+            Pages::Refund(refund) => self.refund_page(refund),
+            Pages::Report(report) => self.report_page(report),
+            Pages::Invoice(invoice) => self.invoice_page(invoice),
+            Pages::Customer(page) => self.customer_page(page),
+            Pages::Credit(page) => self.credit_page(page),
+            Pages::Users(users) => self.users_page(users),
+            Pages::Audit(page) => self.audit_page(page),
+            Pages::Setting(page) => self.setting_page(page),
+        };
+        column![self.status_bar(), page].into()
+    }
+
+    fn theme(&self) -> iced::Theme {
+        self.setting.appearance.theme()
+    }
+
+    fn scale_factor(&self) -> f64 {
+        self.setting.font_scale as f64 / 100.0
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let health = iced::time::every(HEALTH_INTERVAL).map(|_| Message::CheckConnection);
+        let sync = iced::time::every(SYNC_INTERVAL).map(|_| Message::Sync);
+        Subscription::batch([keyboard::on_key_release(keys(&self.pages)), health, sync])
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::api::{Connectivity, Product};
+    use crate::harness::Harness;
+    use crate::setting::Profile;
+    use iced::futures::StreamExt;
+    use iced_runtime::core::widget::Id;
+
+    /// A cashier "สมชาย" (PIN 1111) on the main page, with supervisor
+    /// "สมศรี" (PIN 1234) and manager "admin" (PIN 9999) on file.
+    fn logged_in() -> State {
+        logged_in_as(Role::Cashier)
+    }
+
+    /// Sends `message` through a [`Harness`], so the login or PIN check it
+    /// starts is answered. What its tasks write goes to the harness's own
+    /// directory, and `state` keeps its files and printer.
+    fn send(state: &mut State, message: Message) {
+        let (config, data, printer) = (
+            state.config.clone(),
+            state.data.clone(),
+            state.setting.printer.clone(),
+        );
+        let mut app = Harness::live(std::mem::take(state));
+        app.send(message);
+        *state = State {
+            config,
+            data,
+            ..std::mem::take(&mut app.state)
+        };
+        state.setting.printer = printer;
+    }
+
+    fn logged_in_as(role: Role) -> State {
+        static USERS: std::sync::OnceLock<Vec<User>> = std::sync::OnceLock::new();
+        let users = USERS.get_or_init(|| {
+            vec![
+                User::new("สมชาย".to_string(), Role::Cashier, "1111").unwrap(),
+                User::new("สมศรี".to_string(), Role::Supervisor, "1234").unwrap(),
+                User::new("admin".to_string(), Role::Manager, "9999").unwrap(),
+            ]
+        });
+        State {
+            pages: Pages::Main,
+            user: users.iter().find(|user| user.role == role).cloned(),
+            users: users.clone(),
+            ..State::default()
+        }
+    }
 
     #[test]
     fn barcode_changed() {
-        let mut state = State::default();
-        state.update(Message::Main(MessageMain::Sale));
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
 
-        state.update(Message::Sale(MessageSale::BarcodeChanged(
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
             "11011".to_string(),
         )));
 
-        if let Pages::Sale(sale) = state.pages {
-            assert_eq!(sale.item.barcode, "11011".to_string());
-        }
+        let Pages::Sale(sale) = state.pages else {
+            panic!("expected sale page");
+        };
+        assert_eq!(sale.item.barcode, "11011".to_string());
     }
 
     #[test]
     fn amount_changed() {
-        let mut state = State::default();
-        state.update(Message::Main(MessageMain::Sale));
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
 
-        state.update(Message::Sale(MessageSale::AmountChanged("10".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::AmountChanged("10".to_string())));
 
-        if let Pages::Sale(sale) = state.pages {
-            assert_eq!(sale.item.amount, "10".to_string());
-        }
+        let Pages::Sale(sale) = state.pages else {
+            panic!("expected sale page");
+        };
+        assert_eq!(sale.item.amount, "10".to_string());
     }
 
     #[test]
     fn barcode_submit() {
-        let mut state = State::default();
-        state.update(Message::Main(MessageMain::Sale));
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
 
-        state.update(Message::Sale(MessageSale::BarcodeChanged(
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
             "100".to_string(),
         )));
-        state.update(Message::Sale(MessageSale::BarcodeSubmit));
-        if let Pages::Sale(sale) = state.pages {
-            assert_eq!(sale.items.len(), 1);
-        }
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let Pages::Sale(sale) = state.pages else {
+            panic!("expected sale page");
+        };
+        assert_eq!(sale.items.len(), 1);
     }
 
     #[test]
     fn barcode_submit_empty() {
-        let mut state = State::default();
-        state.update(Message::Main(MessageMain::Sale));
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
 
-        state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
 
-        if let Pages::Sale(sale) = state.pages {
-            assert!(sale.item.barcode.is_empty());
-            assert!(sale.items.is_empty());
-        }
+        let Pages::Sale(sale) = state.pages else {
+            panic!("expected sale page");
+        };
+        assert!(sale.item.barcode.is_empty());
+        assert!(sale.items.is_empty());
+        assert_eq!(sale.item_error, None);
+    }
+
+    #[test]
+    fn barcode_submit_unknown() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "12345".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let Pages::Sale(sale) = &state.pages else {
+            panic!("expected sale page");
+        };
+        assert!(sale.items.is_empty());
+        assert_eq!(sale.item_error, Some("ไม่พบสินค้า"));
+
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged("".to_string())));
+        let Pages::Sale(sale) = state.pages else {
+            panic!("expected sale page");
+        };
+        assert_eq!(sale.item_error, None);
     }
 
     #[test]
     fn barcode_submit_amount_not_number() {
-        let mut state = State::default();
-        state.update(Message::Main(MessageMain::Sale));
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
 
-        state.update(Message::Sale(MessageSale::BarcodeChanged(
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
             "100".to_string(),
         )));
-        state.update(Message::Sale(MessageSale::AmountChanged("a".to_string())));
-        state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::AmountChanged("a".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
 
-        if let Pages::Sale(sale) = state.pages {
-            assert!(sale.items.is_empty());
-        }
+        let Pages::Sale(sale) = state.pages else {
+            panic!("expected sale page");
+        };
+        assert!(sale.items.is_empty());
     }
 
     #[test]
     fn goto_sale() {
-        let mut state = State::default();
-        state.update(Message::Main(MessageMain::Sale));
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
         assert_eq!(state.pages, Pages::Sale(Sale::default()),);
     }
 
@@ -338,15 +2927,2606 @@ mod test {
 
     #[test]
     fn enter_pay() {
-        let mut state = State::default();
-        state.update(Message::Main(MessageMain::Sale));
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let Pages::Sale(sale) = state.pages else {
+            panic!("expected sale page");
+        };
+        assert!(sale.paying);
+    }
+
+    fn open_drawer(state: &mut State, float: &str) {
+        let _ = state.update(Message::Main(MessageMain::Drawer));
+        let _ = state.update(Message::Drawer(MessageDrawer::FloatChanged(
+            float.to_string(),
+        )));
+        let _ = state.update(Message::Drawer(MessageDrawer::Open));
+    }
+
+    #[test]
+    fn drawer_open() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "500");
+
+        assert_eq!(state.session, Some(Session::open("สมชาย".to_string(), 500)));
+        assert!(matches!(state.pages, Pages::Drawer(Drawer::Session { .. })));
+    }
+
+    #[test]
+    fn drawer_opened_for_logged_in_user() {
+        let mut state = logged_in_as(Role::Supervisor);
+        open_drawer(&mut state, "300");
+
+        assert_eq!(state.session, Some(Session::open("สมศรี".to_string(), 300)));
+    }
+
+    #[test]
+    fn drawer_open_float_not_number() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "a");
+
+        assert_eq!(state.session, None);
+    }
+
+    #[test]
+    fn drawer_count_too_large_refused() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "500");
+        let _ = state.update(Message::Drawer(MessageDrawer::ToCount));
+        let _ = state.update(Message::Drawer(MessageDrawer::CountChanged(
+            0,
+            "5000000".to_string(),
+        )));
+        let _ = state.update(Message::Drawer(MessageDrawer::Close));
+
+        assert!(state.session.is_some());
+        assert!(matches!(state.pages, Pages::Drawer(Drawer::Count(_))));
+        assert_eq!(state.audit.len(), 1);
+    }
+
+    #[test]
+    fn drawer_variance() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "500");
+        let _ = state.update(Message::Drawer(MessageDrawer::AmountChanged(
+            "120".to_string(),
+        )));
+        let _ = state.update(Message::Drawer(MessageDrawer::NoteChanged(
+            "ค่าส่งของ".to_string(),
+        )));
+        let _ = state.update(Message::Drawer(MessageDrawer::CashOut));
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "100".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        let _ = state.update(Message::Sale(MessageSale::Back));
+
+        let _ = state.update(Message::Main(MessageMain::Drawer));
+        let _ = state.update(Message::Drawer(MessageDrawer::ToCount));
+        // 4 x 100 + 1 x 50 + 1 x 20 counted against 500 + 100 - 120 expected
+        let _ = state.update(Message::Drawer(MessageDrawer::CountChanged(
+            2,
+            "4".to_string(),
+        )));
+        let _ = state.update(Message::Drawer(MessageDrawer::CountChanged(
+            3,
+            "1".to_string(),
+        )));
+        let _ = state.update(Message::Drawer(MessageDrawer::CountChanged(
+            4,
+            "1".to_string(),
+        )));
+        let _ = state.update(Message::Drawer(MessageDrawer::Close));
+
+        assert_eq!(state.session, None);
+        match state.pages {
+            Pages::Drawer(Drawer::Report(variance)) => {
+                assert_eq!(variance.expected, 480);
+                assert_eq!(variance.counted, 470);
+                assert_eq!(variance.difference(), -10);
+            }
+            _ => panic!("expected drawer report"),
+        }
+        assert_eq!(
+            state.audit[1].action,
+            Action::CashOut {
+                amount: 120,
+                note: "ค่าส่งของ".to_string(),
+            }
+        );
+        assert_eq!(
+            state.audit[2].action,
+            Action::DrawerClose {
+                cashier: "สมชาย".to_string(),
+                expected: 480,
+                counted: 470,
+            }
+        );
+        assert!(state.audit[2].action.to_string().ends_with("(-10)"));
+    }
+
+    fn sell(state: &mut State, barcodes: &[&str]) {
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        for barcode in barcodes {
+            let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+                barcode.to_string(),
+            )));
+            let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        }
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        let _ = state.update(Message::Sale(MessageSale::Back));
+    }
+
+    fn refund(state: &mut State, receipt: &str, quantities: &[&str], pin: &str) {
+        let _ = state.update(Message::Main(MessageMain::Refund));
+        let _ = state.update(Message::Refund(MessageRefund::ReceiptChanged(
+            receipt.to_string(),
+        )));
+        let _ = state.update(Message::Refund(MessageRefund::ReceiptSubmit));
+        for (index, quantity) in quantities.iter().enumerate() {
+            let _ = state.update(Message::Refund(MessageRefund::QuantityChanged(
+                index,
+                quantity.to_string(),
+            )));
+        }
+        let _ = state.update(Message::Refund(MessageRefund::ReasonSelected(
+            RefundReason::Damaged,
+        )));
+        let _ = state.update(Message::Refund(MessageRefund::PinChanged(pin.to_string())));
+        send(state, Message::Refund(MessageRefund::Refund));
+    }
+
+    #[test]
+    fn pay_records_transaction() {
+        let mut state = logged_in();
+        sell(&mut state, &["100", "20"]);
+
+        assert_eq!(state.transactions.len(), 1);
+        assert_eq!(state.transactions[0].number, 1);
+        assert_eq!(state.transactions[0].total, 120);
+    }
+
+    #[test]
+    fn refund_by_receipt_barcode() {
+        let mut state = logged_in();
+        sell(&mut state, &["100", "20"]);
+
+        refund(&mut state, "R00000001", &["0", "1"], "1234");
 
-        state.update(Message::Sale(MessageSale::EnterPay));
-        if let Pages::Sale(sale) = state.pages {
-            assert!(sale.paying);
+        assert_eq!(state.transactions.len(), 2);
+        let refund = &state.transactions[1];
+        assert_eq!(refund.total, 20);
+        assert_eq!(
+            refund.kind,
+            Kind::Refund {
+                receipt: 1,
+                reason: RefundReason::Damaged,
+                lines: vec![1],
+            }
+        );
+        assert_eq!(
+            returnable(&state.transactions, &state.transactions[0]),
+            vec![1, 0]
+        );
+        assert_eq!(state.audit.last().unwrap().user, "สมชาย");
+        assert_eq!(
+            state.audit.last().unwrap().action,
+            Action::Refund {
+                receipt: 1,
+                total: 20,
+                approver: Some("สมศรี".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn refund_wrong_pin() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+
+        refund(&mut state, "1", &["1"], "0000");
+
+        assert_eq!(state.transactions.len(), 1);
+        match state.pages {
+            Pages::Refund(refund) => assert_eq!(refund.error, Some("รหัสผู้อนุมัติไม่ถูกต้อง")),
+            _ => panic!("expected refund page"),
         }
     }
 
+    #[test]
+    fn refund_more_than_sold() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+
+        refund(&mut state, "1", &["2"], "1234");
+
+        assert_eq!(state.transactions.len(), 1);
+    }
+
+    #[test]
+    fn refund_reduces_expected_cash() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "500");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        sell(&mut state, &["100"]);
+
+        refund(&mut state, "1", &["1"], "1234");
+
+        assert_eq!(state.session.map(|session| session.expected()), Some(500));
+    }
+
+    #[test]
+    fn pay_promptpay() {
+        let mut state = logged_in();
+        state.setting.promptpay_id = "0812345678".to_string();
+        open_drawer(&mut state, "500");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "100".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+
+        let _ = state.update(Message::Sale(MessageSale::MethodSelected(
+            PaymentMethod::PromptPay,
+        )));
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(
+                sale.qr.as_ref().map(|qr| qr.payload.clone()),
+                promptpay::payload("0812345678", 100)
+            ),
+            _ => panic!("expected sale page"),
+        }
+
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        assert_eq!(
+            state.transactions[0].tenders,
+            vec![Tender {
+                method: PaymentMethod::PromptPay,
+                amount: 100
+            }]
+        );
+        assert_eq!(state.session.map(|session| session.expected()), Some(500));
+    }
+
+    #[test]
+    fn pay_promptpay_not_configured() {
+        let mut state = logged_in();
+        state.setting.promptpay_id = "".to_string();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "100".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::MethodSelected(
+            PaymentMethod::PromptPay,
+        )));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+
+        assert!(state.transactions.is_empty());
+    }
+
+    fn tender(state: &mut State, method: PaymentMethod, amount: &str) {
+        let _ = state.update(Message::Sale(MessageSale::MethodSelected(method)));
+        let _ = state.update(Message::Sale(MessageSale::Receive(amount.to_string())));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+    }
+
+    #[test]
+    fn pay_split_tender() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "0");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "100".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+
+        tender(&mut state, PaymentMethod::Cash, "30");
+        tender(&mut state, PaymentMethod::Card, "50");
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.remaining(), 20),
+            _ => panic!("expected sale page"),
+        }
+        tender(&mut state, PaymentMethod::Cash, "50");
+
+        let transaction = &state.transactions[0];
+        assert_eq!(transaction.tenders.len(), 3);
+        assert_eq!(transaction.change, 30);
+        assert_eq!(transaction.cash(), 50);
+        assert_eq!(state.session.map(|session| session.expected()), Some(50));
+    }
+
+    #[test]
+    fn pay_card_over_balance() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "100".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+
+        tender(&mut state, PaymentMethod::Card, "150");
+
+        assert!(state.transactions.is_empty());
+        match &state.pages {
+            Pages::Sale(sale) => assert!(sale.payments.is_empty()),
+            _ => panic!("expected sale page"),
+        }
+    }
+
+    #[test]
+    fn report_payment_breakdown() {
+        let mut state = logged_in_as(Role::Supervisor);
+        sell(&mut state, &["100"]);
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged("60".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        tender(&mut state, PaymentMethod::Card, "60");
+        let _ = state.update(Message::Sale(MessageSale::Back));
+        refund(&mut state, "1", &["1"], "1234");
+        let _ = state.update(Message::Refund(MessageRefund::Back));
+
+        let _ = state.update(Message::Main(MessageMain::Report));
+
+        match state.pages {
+            Pages::Report(report) => {
+                assert_eq!(report.sales, 160);
+                assert_eq!(report.refunds, 100);
+                assert_eq!(
+                    report.payments,
+                    vec![
+                        (PaymentMethod::Cash, 0),
+                        (PaymentMethod::PromptPay, 0),
+                        (PaymentMethod::Card, 60),
+                        (PaymentMethod::Points, 0),
+                        (PaymentMethod::Credit, 0)
+                    ]
+                );
+            }
+            _ => panic!("expected report page"),
+        }
+    }
+
+    /// Issues an invoice for `receipt` as if its files were then written.
+    fn issue_invoice(state: &mut State, receipt: &str, tax_id: &str) {
+        let _ = state.update(Message::Main(MessageMain::Invoice));
+        let _ = state.update(Message::Invoice(MessageInvoice::ReceiptChanged(
+            receipt.to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::NameChanged(
+            "บริษัท ตัวอย่าง จำกัด".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::TaxIdChanged(
+            tax_id.to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::Issue));
+        let _ = state.update(Message::InvoiceSaved(state.requests, Ok(())));
+    }
+
+    #[test]
+    fn invoice_issue() {
+        let mut state = logged_in();
+        state.setting.branch = "00001".to_string();
+        sell(&mut state, &["100"]);
+        sell(&mut state, &["50"]);
+
+        issue_invoice(&mut state, "2", "0105536092641");
+        let _ = state.update(Message::Invoice(MessageInvoice::Back));
+        issue_invoice(&mut state, "1", "0105536092641");
+
+        assert_eq!(state.invoices.len(), 2);
+        assert_eq!(state.invoices[0].number, "00001-000001");
+        assert_eq!(state.invoices[0].total, 50);
+        assert_eq!(state.invoices[0].buyer.branch, "00000");
+        assert_eq!(state.invoices[1].number, "00001-000002");
+    }
+
+    #[test]
+    fn invoice_invalid_tax_id() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+
+        issue_invoice(&mut state, "1", "0105536092642");
+
+        assert!(state.invoices.is_empty());
+        match state.pages {
+            Pages::Invoice(invoice) => {
+                assert_eq!(invoice.error, Some("เลขประจำตัวผู้เสียภาษีไม่ถูกต้อง"))
+            }
+            _ => panic!("expected invoice page"),
+        }
+    }
+
+    #[test]
+    fn invoice_only_once_per_receipt() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+
+        issue_invoice(&mut state, "1", "0105536092641");
+        let _ = state.update(Message::Invoice(MessageInvoice::Back));
+        issue_invoice(&mut state, "1", "0105536092641");
+
+        assert_eq!(state.invoices.len(), 1);
+    }
+
+    #[test]
+    fn invoice_number_kept_until_written() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+        issue_invoice(&mut state, "1", "0105536092641");
+        let _ = state.update(Message::Invoice(MessageInvoice::Back));
+        sell(&mut state, &["50"]);
+        let _ = state.update(Message::Main(MessageMain::Invoice));
+        let _ = state.update(Message::Invoice(MessageInvoice::ReceiptChanged(
+            "2".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::NameChanged(
+            "บริษัท ตัวอย่าง จำกัด".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::TaxIdChanged(
+            "0105536092641".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::Issue));
+        // No second invoice while the first is being written
+        let _ = state.update(Message::Invoice(MessageInvoice::Issue));
+        let _ = state.update(Message::InvoiceSaved(
+            state.requests,
+            Err("ไม่มีฟอนต์".to_string()),
+        ));
+
+        assert_eq!(state.invoices.len(), 1);
+        match &state.pages {
+            Pages::Invoice(invoice) => {
+                assert_eq!(invoice.issued, None);
+                assert_eq!(invoice.failed, Some("ไม่มีฟอนต์".to_string()));
+                assert_eq!(invoice.receipt, "2");
+            }
+            _ => panic!("expected invoice page"),
+        }
+        let _ = state.update(Message::Invoice(MessageInvoice::Issue));
+        let _ = state.update(Message::InvoiceSaved(state.requests, Ok(())));
+        assert_eq!(state.invoices[1].number, "00000-000002");
+    }
+
+    #[test]
+    fn invoice_failing_after_leaving_is_shown() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+        let _ = state.update(Message::Main(MessageMain::Invoice));
+        let _ = state.update(Message::Invoice(MessageInvoice::ReceiptChanged(
+            "1".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::NameChanged(
+            "บริษัท ตัวอย่าง จำกัด".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::TaxIdChanged(
+            "0105536092641".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::Issue));
+        let _ = state.update(Message::Invoice(MessageInvoice::Back));
+        let _ = state.update(Message::InvoiceSaved(
+            state.requests,
+            Err("ดิสก์เต็ม".to_string()),
+        ));
+
+        assert!(state.invoices.is_empty());
+        assert_eq!(
+            state.error,
+            Some("บันทึกใบกำกับภาษีไม่สำเร็จ: ดิสก์เต็ม".to_string())
+        );
+    }
+
+    #[test]
+    fn invoice_saved_after_reopening_is_kept_but_not_shown() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+        let _ = state.update(Message::Main(MessageMain::Invoice));
+        let _ = state.update(Message::Invoice(MessageInvoice::ReceiptChanged(
+            "1".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::NameChanged(
+            "บริษัท ตัวอย่าง จำกัด".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::TaxIdChanged(
+            "0105536092641".to_string(),
+        )));
+        let _ = state.update(Message::Invoice(MessageInvoice::Issue));
+        let _ = state.update(Message::Invoice(MessageInvoice::Back));
+        let _ = state.update(Message::Main(MessageMain::Invoice));
+        let _ = state.update(Message::InvoiceSaved(state.requests, Ok(())));
+
+        assert_eq!(state.invoices.len(), 1);
+        match &state.pages {
+            Pages::Invoice(invoice) => assert_eq!(invoice.issued, None),
+            _ => panic!("expected invoice page"),
+        }
+    }
+
+    #[test]
+    fn records_carry_on_after_restart() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-records-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut state = logged_in();
+        sell(&mut state, &["100", "20"]);
+        issue_invoice(&mut state, "1", "0105536092641");
+        let line = |value: String| value + "\n";
+        std::fs::write(
+            dir.join("transactions.jsonl"),
+            line(serde_json::to_string(&state.transactions[0]).unwrap()),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("invoices.jsonl"),
+            line(serde_json::to_string(&state.invoices[0]).unwrap()),
+        )
+        .unwrap();
+
+        let mut state = State {
+            pages: Pages::Main,
+            ..logged_in()
+        };
+        let loaded = State::load(dir.join("setting.json"), &dir);
+        state.transactions = loaded.transactions;
+        state.invoices = loaded.invoices;
+        sell(&mut state, &["50"]);
+        assert_eq!(state.transactions[1].number, 2);
+        assert_eq!(
+            next_number(&state.invoices, &state.setting.branch),
+            "00000-000002"
+        );
+        // Sales from before the restart can still be refunded
+        refund(&mut state, "1", &["0", "1"], "1234");
+        assert_eq!(state.transactions.len(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sale_vat_exclusive() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        if let Pages::Sale(sale) = &mut state.pages {
+            sale.item.vat = Vat::Exclusive;
+        }
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "100".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.total, 107);
+                assert_eq!(
+                    vat::Breakdown::of(&sale.items, state.setting.vat_rate),
+                    vat::Breakdown {
+                        taxable: 10000,
+                        vat: 700,
+                        exempt: 0
+                    }
+                );
+            }
+            _ => panic!("expected sale page"),
+        }
+    }
+
+    #[test]
+    fn report_covers_one_day() {
+        let mut state = logged_in_as(Role::Manager);
+        sell(&mut state, &["100"]);
+        sell(&mut state, &["20"]);
+        sell(&mut state, &["5"]);
+        let today = chrono::Local::now().naive_local();
+        state.transactions[0].time = Some(today - chrono::Duration::days(1));
+        // Kept before sales had a time
+        state.transactions[2].time = None;
+
+        let _ = state.update(Message::Main(MessageMain::Report));
+        let Pages::Report(report) = &state.pages else {
+            panic!("expected report page");
+        };
+        assert_eq!((report.day, report.sales), (today.date(), 20));
+
+        let _ = state.update(Message::Report(MessageReport::Previous));
+        let Pages::Report(report) = &state.pages else {
+            panic!("expected report page");
+        };
+        assert_eq!(report.sales, 100);
+    }
+
+    #[test]
+    fn report_by_month_at_each_sale_rate() {
+        use chrono::Datelike;
+
+        let mut state = logged_in_as(Role::Manager);
+        sell(&mut state, &["107"]);
+        state.setting.vat_rate = 10;
+        sell(&mut state, &["107"]);
+        let today = chrono::Local::now().naive_local();
+        state.transactions[0].time = today.with_day(1);
+
+        let _ = state.update(Message::Main(MessageMain::Report));
+        let _ = state.update(Message::Report(MessageReport::PeriodSelected(
+            Period::Month,
+        )));
+        let Pages::Report(report) = &state.pages else {
+            panic!("expected report page");
+        };
+        // 7.00 at the old rate and 9.73 at the new one
+        assert_eq!((report.sales, report.vat), (214, 1673));
+
+        let _ = state.update(Message::Report(MessageReport::Previous));
+        let Pages::Report(report) = &state.pages else {
+            panic!("expected report page");
+        };
+        assert_eq!(report.sale_count, 0);
+    }
+
+    #[test]
+    fn report_taxable_and_exempt() {
+        let mut state = logged_in_as(Role::Supervisor);
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "107".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        if let Pages::Sale(sale) = &mut state.pages {
+            sale.item.vat = Vat::Exempt;
+        }
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged("50".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        let _ = state.update(Message::Sale(MessageSale::Back));
+
+        let _ = state.update(Message::Main(MessageMain::Report));
+
+        match state.pages {
+            Pages::Report(report) => {
+                assert_eq!(report.taxable, 10000);
+                assert_eq!(report.vat, 700);
+                assert_eq!(report.exempt, 5000);
+            }
+            _ => panic!("expected report page"),
+        }
+    }
+
+    fn three_for_fifty() -> Promotion {
+        Promotion {
+            name: "3 ชิ้น 50 บาท".to_string(),
+            rule: promotion::Rule::MultiBuy {
+                barcode: "20".to_string(),
+                quantity: 3,
+                price: 50,
+            },
+            starts: None,
+            ends: None,
+        }
+    }
+
+    #[test]
+    fn sale_applies_promotion() {
+        let mut state = State {
+            promotions: vec![three_for_fifty()],
+            ..logged_in()
+        };
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        for barcode in ["20", "20", "100", "20"] {
+            let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+                barcode.to_string(),
+            )));
+            let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        }
+
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.discounts.len(), 1);
+                assert_eq!(sale.discounts[0].amount, 10);
+                assert_eq!(sale.total, 150);
+            }
+            _ => panic!(),
+        }
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        assert_eq!(state.transactions[0].total, 150);
+        assert_eq!(state.transactions[0].discounts.len(), 1);
+    }
+
+    fn repriced(rule: promotion::Rule, items: Vec<Item>) -> Sale {
+        let mut sale = Sale {
+            items,
+            ..Sale::default()
+        };
+        let promotion = Promotion {
+            name: "ลดราคา".to_string(),
+            rule,
+            starts: None,
+            ends: None,
+        };
+        sale.reprice(&[promotion], vat::VAT_RATE, 20);
+        sale
+    }
+
+    /// Three of a 10 baht item sold before VAT: 11 a unit with VAT but 32
+    /// for the line.
+    fn three_before_vat() -> Item {
+        Item {
+            barcode: "10".to_string(),
+            price: 10,
+            amount: "3".to_string(),
+            sum: Vat::Exclusive.charge(10, 3, vat::VAT_RATE),
+            vat: Vat::Exclusive,
+            ..Item::default()
+        }
+    }
+
+    #[test]
+    fn promotion_never_more_than_the_line() {
+        let sale = repriced(
+            promotion::Rule::Percent {
+                barcodes: vec!["10".to_string()],
+                percent: 100,
+            },
+            vec![three_before_vat()],
+        );
+
+        assert_eq!(sale.discounts[0].amount, 32);
+        assert_eq!(sale.total, 0);
+    }
+
+    #[test]
+    fn fixed_promotion_over_the_total() {
+        let other = Item {
+            barcode: "100".to_string(),
+            price: 100,
+            sum: 100,
+            ..Item::default()
+        };
+        let sale = repriced(
+            promotion::Rule::Fixed {
+                barcodes: vec!["10".to_string()],
+                amount: 1_000_000,
+            },
+            vec![three_before_vat(), other],
+        );
+
+        assert_eq!(sale.discounts[0].amount, 32);
+        assert_eq!(sale.total, 100);
+    }
+
+    #[test]
+    fn refund_spreads_promotion_discount() {
+        let mut state = State {
+            promotions: vec![three_for_fifty()],
+            ..logged_in()
+        };
+        sell(&mut state, &["20", "20", "20"]);
+
+        refund(&mut state, "R00000001", &["0", "0", "1"], "1234");
+
+        assert_eq!(state.transactions[1].total, 16);
+    }
+
+    fn discount(state: &mut State, target: Target, value: &str, percent: bool, pin: &str) {
+        let _ = state.update(Message::Sale(MessageSale::ToDiscount(target)));
+        let _ = state.update(Message::Sale(MessageSale::DiscountPercent(percent)));
+        let _ = state.update(Message::Sale(MessageSale::DiscountValueChanged(
+            value.to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::DiscountReasonSelected(
+            DiscountReason::Damaged,
+        )));
+        let _ = state.update(Message::Sale(MessageSale::DiscountPinChanged(
+            pin.to_string(),
+        )));
+        send(state, Message::Sale(MessageSale::ApplyDiscount));
+    }
+
+    fn scan(state: &mut State, barcodes: &[&str]) {
+        for barcode in barcodes {
+            let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+                barcode.to_string(),
+            )));
+            let _ = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        }
+    }
+
+    #[test]
+    fn discount_line_and_bill() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100", "50"]);
+        let _ = state.update(Message::Sale(MessageSale::LineSelected(1)));
+        discount(&mut state, Target::Line(1), "10", false, "");
+        // 24 baht off in all, over the limit
+        discount(&mut state, Target::Bill, "10", true, "1234");
+
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.discounts.len(), 2);
+                assert_eq!(sale.total, 126);
+                assert_eq!(sale.discount, None);
+            }
+            _ => panic!(),
+        }
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+
+        let report = Report::new(
+            &state.transactions,
+            chrono::Local::now().date_naive(),
+            Period::Day,
+        );
+        assert_eq!(report.sales, 126);
+        assert!(report.discounts.contains(&(DiscountReason::Damaged, 24)));
+    }
+
+    #[test]
+    fn discount_over_limit_needs_pin() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["500"]);
+
+        discount(&mut state, Target::Bill, "50", false, "");
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.total, 500);
+                assert!(sale.discount.as_ref().unwrap().error.is_some());
+            }
+            _ => panic!(),
+        }
+
+        let _ = state.update(Message::Sale(MessageSale::DiscountPinChanged(
+            "1234".to_string(),
+        )));
+        send(&mut state, Message::Sale(MessageSale::ApplyDiscount));
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.total, 450),
+            _ => panic!(),
+        }
+        assert_eq!(
+            state.audit.last().map(|entry| &entry.action),
+            Some(&Action::Discount {
+                total: 50,
+                limit: state.setting.discount_limit,
+                approver: "สมศรี".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn stacked_discounts_count_towards_limit() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["500"]);
+
+        discount(&mut state, Target::Bill, "15", false, "");
+        discount(&mut state, Target::Line(0), "15", false, "");
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.total, 485);
+                assert!(sale.discount.as_ref().unwrap().error.is_some());
+            }
+            _ => panic!(),
+        }
+
+        let _ = state.update(Message::Sale(MessageSale::DiscountPinChanged(
+            "1234".to_string(),
+        )));
+        send(&mut state, Message::Sale(MessageSale::ApplyDiscount));
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.total, 470),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn percent_discount_held_to_limit_as_basket_grows() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100"]);
+        discount(&mut state, Target::Bill, "10", true, "");
+        let _ = state.update(Message::Sale(MessageSale::AmountChanged("4".to_string())));
+        scan(&mut state, &["500"]);
+
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.requested, 210);
+                assert_eq!(sale.total, 2080);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn approved_discount_follows_the_basket_for_a_supervisor() {
+        let mut state = logged_in_as(Role::Supervisor);
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100"]);
+        discount(&mut state, Target::Bill, "10", true, "");
+        scan(&mut state, &["500"]);
+
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.total, 540),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn pin_changed_while_checked_is_ignored() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["500"]);
+        discount(&mut state, Target::Bill, "50", false, "");
+        let _ = state.update(Message::Sale(MessageSale::DiscountPinChanged(
+            "1234".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::ApplyDiscount));
+
+        // Edited before the check of 1234 came back
+        let _ = state.update(Message::Sale(MessageSale::DiscountPinChanged(
+            "12".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::DiscountApproved(
+            state.requests,
+            Some("สมศรี".to_string()),
+        )));
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.total, 500);
+                assert_eq!(sale.discount.as_ref().unwrap().error, None);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn approval_for_a_reopened_form_is_ignored() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["500"]);
+        discount(&mut state, Target::Bill, "50", false, "");
+        let _ = state.update(Message::Sale(MessageSale::DiscountPinChanged(
+            "1234".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::ApplyDiscount));
+        let request = state.requests;
+
+        // Opened afresh, same PIN typed, before the first check came back
+        let _ = state.update(Message::Sale(MessageSale::ToDiscount(Target::Bill)));
+        let _ = state.update(Message::Sale(MessageSale::DiscountPinChanged(
+            "1234".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::DiscountApproved(
+            request,
+            Some("สมศรี".to_string()),
+        )));
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.total, 500);
+                assert!(sale.discount.is_some());
+            }
+            _ => panic!(),
+        }
+    }
+
+    fn override_price(state: &mut State, line: usize, price: &str, pin: &str) {
+        let _ = state.update(Message::Sale(MessageSale::LineSelected(line)));
+        let _ = state.update(Message::Sale(MessageSale::ToOverride(line)));
+        let _ = state.update(Message::Sale(MessageSale::OverridePriceChanged(
+            price.to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::OverrideReasonSelected(
+            OverrideReason::ShelfPrice,
+        )));
+        let _ = state.update(Message::Sale(MessageSale::OverridePinChanged(
+            pin.to_string(),
+        )));
+        send(state, Message::Sale(MessageSale::ApplyOverride));
+    }
+
+    #[test]
+    fn override_price_records_audit() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "1000");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100", "30"]);
+
+        override_price(&mut state, 0, "90", "1234");
+
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.items[0].price, 90);
+                assert_eq!(sale.total, 120);
+                assert_eq!(sale.price_override, None);
+            }
+            _ => panic!(),
+        }
+        // After the drawer opening
+        assert_eq!(state.audit.len(), 2);
+        assert_eq!(state.audit[1].user, "สมชาย");
+        assert_eq!(
+            state.audit[1].action,
+            Action::PriceOverride {
+                barcode: "100".to_string(),
+                old: 100,
+                new: 90,
+                reason: OverrideReason::ShelfPrice,
+                approver: Some("สมศรี".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn override_price_wrong_pin() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100"]);
+
+        override_price(&mut state, 0, "90", "0000");
+
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.total, 100);
+                assert!(sale.price_override.as_ref().unwrap().error.is_some());
+            }
+            _ => panic!(),
+        }
+        assert!(state.audit.is_empty());
+    }
+
+    fn register(state: &mut State, phone: &str, name: &str) {
+        let _ = state.update(Message::Main(MessageMain::Customer));
+        let _ = state.update(Message::Customer(MessageCustomer::PhoneChanged(
+            phone.to_string(),
+        )));
+        let _ = state.update(Message::Customer(MessageCustomer::NameChanged(
+            name.to_string(),
+        )));
+        let _ = state.update(Message::Customer(MessageCustomer::Register));
+        let _ = state.update(Message::Customer(MessageCustomer::Back));
+    }
+
+    fn attach(state: &mut State, phone: &str) {
+        let _ = state.update(Message::Sale(MessageSale::PhoneChanged(phone.to_string())));
+        let _ = state.update(Message::Sale(MessageSale::PhoneSubmit));
+    }
+
+    #[test]
+    fn customer_earns_points() {
+        let mut state = logged_in();
+        register(&mut state, "081-234-5678", "สมหญิง");
+        assert_eq!(state.customers.len(), 1);
+        assert_eq!(state.customers[0].phone, "0812345678");
+
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        attach(&mut state, "0812345678");
+        scan(&mut state, &["100", "30"]);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+
+        assert_eq!(state.customers[0].points, 5);
+        assert_eq!(
+            state.transactions[0].customer,
+            Some("0812345678".to_string())
+        );
+    }
+
+    #[test]
+    fn customer_redeems_points() {
+        let mut state = logged_in();
+        register(&mut state, "0812345678", "สมหญิง");
+        state.customers[0].points = 40;
+
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        attach(&mut state, "0812345678");
+        scan(&mut state, &["100"]);
+        tender(&mut state, PaymentMethod::Points, "40");
+        tender(&mut state, PaymentMethod::Cash, "60");
+
+        assert_eq!(state.transactions.len(), 1);
+        assert_eq!(state.customers[0].points, 2);
+    }
+
+    #[test]
+    fn member_kept_once_points_tendered() {
+        let mut state = logged_in();
+        register(&mut state, "0812345678", "สมหญิง");
+        register(&mut state, "0898765432", "สมปอง");
+        state.customers[0].points = 40;
+
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        attach(&mut state, "0812345678");
+        scan(&mut state, &["100"]);
+        tender(&mut state, PaymentMethod::Points, "40");
+        attach(&mut state, "0898765432");
+        tender(&mut state, PaymentMethod::Cash, "60");
+
+        assert_eq!(
+            state.transactions[0].customer,
+            Some("0812345678".to_string())
+        );
+        assert_eq!(state.customers[0].points, 2);
+        assert_eq!(state.customers[1].points, 0);
+    }
+
+    #[test]
+    fn customer_not_found() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        attach(&mut state, "0899999999");
+        scan(&mut state, &["100"]);
+        tender(&mut state, PaymentMethod::Points, "50");
+
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.customer, None);
+                assert!(sale.payments.is_empty());
+            }
+            _ => panic!(),
+        }
+    }
+
+    /// Opens an account as a manager, then hands the till back.
+    fn open_account(state: &mut State, name: &str, limit: &str) {
+        let user = state.user.clone();
+        state.user = logged_in_as(Role::Manager).user;
+        let _ = state.update(Message::Main(MessageMain::Credit));
+        let _ = state.update(Message::Credit(MessageCredit::NameChanged(
+            name.to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::LimitChanged(
+            limit.to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::Open));
+        let _ = state.update(Message::Credit(MessageCredit::Back));
+        state.user = user;
+    }
+
+    fn sell_on_credit(state: &mut State, name: &str, barcodes: &[&str]) {
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(state, barcodes);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::MethodSelected(
+            PaymentMethod::Credit,
+        )));
+        let _ = state.update(Message::Sale(MessageSale::AccountSelected(
+            name.to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        let _ = state.update(Message::Sale(MessageSale::Back));
+    }
+
+    #[test]
+    fn credit_charges_account() {
+        let mut state = logged_in();
+        open_account(&mut state, "ป้าศรี", "500");
+
+        sell_on_credit(&mut state, "ป้าศรี", &["100", "200"]);
+
+        assert_eq!(state.transactions.len(), 1);
+        assert_eq!(state.accounts[0].balance(), 300);
+        assert_eq!(
+            state.accounts[0].entries[0].kind,
+            credit::EntryKind::Charge { receipt: 1 }
+        );
+    }
+
+    #[test]
+    fn credit_over_limit() {
+        let mut state = logged_in();
+        open_account(&mut state, "ป้าศรี", "500");
+        sell_on_credit(&mut state, "ป้าศรี", &["400"]);
+
+        sell_on_credit(&mut state, "ป้าศรี", &["200"]);
+
+        assert_eq!(state.transactions.len(), 1);
+        assert_eq!(state.accounts[0].balance(), 400);
+    }
+
+    #[test]
+    fn credit_account_locked_once_tendered() {
+        let mut state = logged_in();
+        open_account(&mut state, "ป้าศรี", "500");
+        open_account(&mut state, "ลุงมี", "500");
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["300"]);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::MethodSelected(
+            PaymentMethod::Credit,
+        )));
+        let _ = state.update(Message::Sale(MessageSale::AccountSelected(
+            "ป้าศรี".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::Receive("200".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        let _ = state.update(Message::Sale(MessageSale::AccountSelected(
+            "ลุงมี".to_string(),
+        )));
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.account, Some("ป้าศรี".to_string())),
+            _ => panic!("expected sale page"),
+        }
+
+        // The limit came down before the sale was finished
+        state.accounts[0].limit = 100;
+        let _ = state.update(Message::Sale(MessageSale::MethodSelected(
+            PaymentMethod::Cash,
+        )));
+        let _ = state.update(Message::Sale(MessageSale::Receive("100".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        assert!(state.transactions.is_empty());
+        assert_eq!(state.accounts[0].balance(), 0);
+    }
+
+    #[test]
+    fn credit_accounts_need_permission_and_are_audited() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Credit));
+        let _ = state.update(Message::Credit(MessageCredit::NameChanged(
+            "ป้าศรี".to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::LimitChanged(
+            "500".to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::Open));
+        assert!(state.accounts.is_empty());
+        let _ = state.update(Message::Credit(MessageCredit::Back));
+
+        open_account(&mut state, "ป้าศรี", "500");
+        sell_on_credit(&mut state, "ป้าศรี", &["300"]);
+        let _ = state.update(Message::Main(MessageMain::Credit));
+        let _ = state.update(Message::Credit(MessageCredit::Selected("ป้าศรี".to_string())));
+        let _ = state.update(Message::Credit(MessageCredit::NewLimitChanged(
+            "1000".to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::ChangeLimit));
+        assert_eq!(state.accounts[0].limit, 500);
+        state.user = logged_in_as(Role::Manager).user;
+        let _ = state.update(Message::Credit(MessageCredit::ChangeLimit));
+        assert_eq!(state.accounts[0].limit, 1000);
+        let _ = state.update(Message::Credit(MessageCredit::RepaymentChanged(
+            "120".to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::Repay));
+
+        let actions: Vec<&Action> = state.audit.iter().map(|entry| &entry.action).collect();
+        assert_eq!(
+            actions,
+            [
+                &Action::CreditOpened {
+                    name: "ป้าศรี".to_string(),
+                    limit: 500
+                },
+                &Action::CreditLimit {
+                    name: "ป้าศรี".to_string(),
+                    old: 500,
+                    new: 1000
+                },
+                &Action::Repayment {
+                    name: "ป้าศรี".to_string(),
+                    amount: 120
+                },
+            ]
+        );
+        assert_eq!(state.audit[0].user, "admin");
+    }
+
+    #[test]
+    fn credit_repayment() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "0");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        open_account(&mut state, "ป้าศรี", "500");
+        sell_on_credit(&mut state, "ป้าศรี", &["300"]);
+
+        let _ = state.update(Message::Main(MessageMain::Credit));
+        let _ = state.update(Message::Credit(MessageCredit::Selected("ป้าศรี".to_string())));
+        let _ = state.update(Message::Credit(MessageCredit::RepaymentChanged(
+            "400".to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::Repay));
+        assert_eq!(state.accounts[0].balance(), 300);
+
+        let _ = state.update(Message::Credit(MessageCredit::RepaymentChanged(
+            "120".to_string(),
+        )));
+        let _ = state.update(Message::Credit(MessageCredit::Repay));
+
+        assert_eq!(state.accounts[0].balance(), 180);
+        assert_eq!(state.session.as_ref().unwrap().expected(), 120);
+    }
+
+    #[test]
+    fn refund_goes_back_to_credit_account() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "0");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        open_account(&mut state, "ป้าศรี", "500");
+        sell_on_credit(&mut state, "ป้าศรี", &["100", "200"]);
+
+        refund(&mut state, "1", &["0", "1"], "1234");
+
+        assert_eq!(
+            state.transactions[1].tenders,
+            vec![Tender {
+                method: PaymentMethod::Credit,
+                amount: 200,
+            }]
+        );
+        assert_eq!(state.accounts[0].balance(), 100);
+        assert_eq!(
+            state.accounts[0].entries[1].kind,
+            credit::EntryKind::Refund { receipt: 1 }
+        );
+        // No cash left the drawer
+        assert_eq!(state.session.as_ref().unwrap().expected(), 0);
+    }
+
+    #[test]
+    fn refund_returns_points_and_takes_back_earned() {
+        let mut state = logged_in();
+        register(&mut state, "0812345678", "สมหญิง");
+        state.customers[0].points = 40;
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        attach(&mut state, "0812345678");
+        scan(&mut state, &["100"]);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        tender(&mut state, PaymentMethod::Points, "40");
+        tender(&mut state, PaymentMethod::Cash, "60");
+        let _ = state.update(Message::Sale(MessageSale::Back));
+        // 40 spent, 60 baht paid earns 2
+        assert_eq!(state.customers[0].points, 2);
+
+        refund(&mut state, "1", &["1"], "1234");
+
+        assert_eq!(
+            state.transactions[1].tenders,
+            vec![
+                Tender {
+                    method: PaymentMethod::Points,
+                    amount: 40,
+                },
+                Tender {
+                    method: PaymentMethod::Cash,
+                    amount: 60,
+                },
+            ]
+        );
+        assert_eq!(
+            state.transactions[1].customer,
+            Some("0812345678".to_string())
+        );
+        assert_eq!(state.customers[0].points, 40);
+    }
+
+    #[test]
+    fn refund_in_tender_mix_of_sale() {
+        let mut state = logged_in();
+        open_drawer(&mut state, "0");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100", "20", "20"]);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        tender(&mut state, PaymentMethod::Card, "70");
+        tender(&mut state, PaymentMethod::Cash, "100");
+        let _ = state.update(Message::Sale(MessageSale::Back));
+
+        // 70 by card and 70 in cash once the change is given
+        refund(&mut state, "1", &["0", "1", "0"], "1234");
+        assert_eq!(
+            state.transactions[1].tenders,
+            vec![
+                Tender {
+                    method: PaymentMethod::Card,
+                    amount: 10,
+                },
+                Tender {
+                    method: PaymentMethod::Cash,
+                    amount: 10,
+                },
+            ]
+        );
+        refund(&mut state, "1", &["1", "0", "1"], "1234");
+        let returned = |method| {
+            state.transactions[1..]
+                .iter()
+                .flat_map(|refund| &refund.tenders)
+                .filter(|tender| tender.method == method)
+                .map(|tender| tender.amount)
+                .sum::<u32>()
+        };
+        assert_eq!(returned(PaymentMethod::Card), 70);
+        assert_eq!(returned(PaymentMethod::Cash), 70);
+        assert_eq!(state.session.as_ref().unwrap().expected(), 0);
+    }
+
+    fn sign_in(state: &mut State, name: &str, secret: &str) {
+        let _ = state.update(Message::Login(MessageLogin::NameChanged(name.to_string())));
+        let _ = state.update(Message::Login(MessageLogin::SecretChanged(
+            secret.to_string(),
+        )));
+        send(state, Message::Login(MessageLogin::Submit));
+    }
+
+    #[test]
+    fn first_run_creates_manager() {
+        let mut state = State {
+            setup: true,
+            ..State::default()
+        };
+        sign_in(&mut state, "admin", "9999");
+
+        assert_eq!(state.pages, Pages::Main);
+        assert_eq!(state.users.len(), 1);
+        assert_eq!(state.users[0].role, Role::Manager);
+        assert!(state.users[0].verify("9999"));
+    }
+
+    #[test]
+    fn login_and_logout() {
+        let mut state = State {
+            user: None,
+            pages: Pages::default(),
+            ..logged_in()
+        };
+        sign_in(&mut state, "สมศรี", "0000");
+        match &state.pages {
+            Pages::Login(page) => {
+                assert_eq!(page.error, Some("ชื่อผู้ใช้หรือรหัสผ่านไม่ถูกต้อง"));
+                assert!(page.secret.is_empty());
+            }
+            _ => panic!("expected login page"),
+        }
+
+        sign_in(&mut state, "สมศรี", "1234");
+        assert_eq!(state.pages, Pages::Main);
+        assert_eq!(
+            state.user.as_ref().map(|user| user.role),
+            Some(Role::Supervisor)
+        );
+
+        let _ = state.update(Message::Main(MessageMain::Logout));
+        assert_eq!(state.user, None);
+        assert!(matches!(state.pages, Pages::Login(_)));
+    }
+
+    #[test]
+    fn cashier_cannot_open_back_office() {
+        let mut state = logged_in();
+        for message in [MessageMain::Stock, MessageMain::Setting, MessageMain::Users] {
+            let _ = state.update(Message::Main(message));
+            assert_eq!(state.pages, Pages::Main);
+        }
+
+        let mut state = logged_in_as(Role::Manager);
+        let _ = state.update(Message::Main(MessageMain::Users));
+        assert!(matches!(state.pages, Pages::Users(_)));
+    }
+
+    #[test]
+    fn void_line_needs_permission() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100", "20"]);
+        let _ = state.update(Message::Sale(MessageSale::LineSelected(0)));
+        let _ = state.update(Message::Sale(MessageSale::VoidLine));
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.items.len(), 2),
+            _ => panic!("expected sale page"),
+        }
+        assert!(state.audit.is_empty());
+
+        state.user = logged_in_as(Role::Supervisor).user;
+        let _ = state.update(Message::Sale(MessageSale::VoidLine));
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.items.len(), 1);
+                assert_eq!(sale.total, 20);
+            }
+            _ => panic!("expected sale page"),
+        }
+        assert_eq!(state.audit.len(), 1);
+        assert_eq!(state.audit[0].user, "สมศรี");
+    }
+
+    #[test]
+    fn transaction_records_cashier() {
+        let mut state = logged_in();
+        sell(&mut state, &["100"]);
+
+        assert_eq!(state.transactions[0].user, "สมชาย");
+    }
+
+    #[test]
+    fn audit_chains_sensitive_actions() {
+        let mut state = logged_in_as(Role::Supervisor);
+        open_drawer(&mut state, "500");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["100", "20"]);
+        let _ = state.update(Message::Sale(MessageSale::VoidSale));
+
+        let labels: Vec<&str> = state
+            .audit
+            .iter()
+            .map(|entry| entry.action.label())
+            .collect();
+        assert_eq!(labels, vec!["เปิดลิ้นชัก", "ยกเลิกบิล"]);
+        assert_eq!(audit::verify(&state.audit), Ok(()));
+
+        state.audit[0].user = "สมชาย".to_string();
+        assert_eq!(audit::verify(&state.audit), Err(0));
+    }
+
+    #[test]
+    fn audit_viewer_filters() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Main(MessageMain::Audit));
+        assert_eq!(state.pages, Pages::Main);
+
+        let mut state = logged_in_as(Role::Manager);
+        open_drawer(&mut state, "500");
+        let _ = state.update(Message::Drawer(MessageDrawer::Back));
+        let _ = state.update(Message::Main(MessageMain::Users));
+        let _ = state.update(Message::Users(MessageUsers::NameChanged(
+            "สมหญิง".to_string(),
+        )));
+        let _ = state.update(Message::Users(MessageUsers::SecretChanged(
+            "2222".to_string(),
+        )));
+        let _ = state.update(Message::Users(MessageUsers::Add));
+        let _ = state.update(Message::Users(MessageUsers::Back));
+        let _ = state.update(Message::Main(MessageMain::Audit));
+        let _ = state.update(Message::Audit(MessageAudit::ActionSelected("เพิ่มผู้ใช้")));
+
+        match &state.pages {
+            Pages::Audit(page) => {
+                let entries: Vec<&Entry> = page.filter(&state.audit).collect();
+                assert_eq!(entries.len(), 1);
+                assert_eq!(
+                    entries[0].action,
+                    Action::UserAdded {
+                        name: "สมหญิง".to_string(),
+                        role: Role::Cashier,
+                    }
+                );
+            }
+            _ => panic!("expected audit page"),
+        }
+
+        let _ = state.update(Message::Audit(MessageAudit::FromChanged(
+            "01/01/2000".to_string(),
+        )));
+        let _ = state.update(Message::Audit(MessageAudit::ToChanged(
+            "31/12/2000".to_string(),
+        )));
+        match &state.pages {
+            Pages::Audit(page) => assert_eq!(page.filter(&state.audit).count(), 0),
+            _ => panic!("expected audit page"),
+        }
+    }
+
+    fn change_setting(state: &mut State, field: Field, input: &str) {
+        let _ = state.update(Message::Setting(MessageSetting::Changed(
+            field,
+            input.to_string(),
+        )));
+    }
+
+    #[test]
+    fn language_setting_read_by_pages() {
+        let mut state = logged_in_as(Role::Manager);
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::LanguageSelected(
+            Language::English,
+        )));
+        assert_eq!(state.text("ตั้งค่า"), "ตั้งค่า");
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+
+        assert_eq!(state.setting.language, Language::English);
+        assert_eq!(state.text("ตั้งค่า"), "Settings");
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::Defaults));
+        match &state.pages {
+            Pages::Setting(page) => assert_eq!(page.language, Language::English),
+            _ => panic!("expected setting page"),
+        }
+        let _ = state.update(Message::Setting(MessageSetting::SectionSelected(
+            Section::Display,
+        )));
+        let _ = state.update(Message::Setting(MessageSetting::Defaults));
+        match &state.pages {
+            Pages::Setting(page) => assert_eq!(page.language, Language::Thai),
+            _ => panic!("expected setting page"),
+        }
+    }
+
+    #[test]
+    fn setting_save_validates() {
+        let mut state = logged_in_as(Role::Manager);
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::SectionSelected(
+            Section::Tax,
+        )));
+        change_setting(&mut state, Field::VatRate, "10");
+        change_setting(&mut state, Field::Branch, "12");
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+
+        match &state.pages {
+            Pages::Setting(page) => {
+                assert_eq!(page.section, Section::Shop);
+                assert_eq!(page.error, Some("รหัสสาขาต้องเป็นตัวเลข 5 หลัก"));
+            }
+            _ => panic!("expected setting page"),
+        }
+        assert_eq!(state.setting.vat_rate, 7);
+
+        change_setting(&mut state, Field::Branch, "00001");
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+        assert_eq!(state.pages, Pages::Main);
+        assert_eq!(state.setting.vat_rate, 10);
+        assert_eq!(state.setting.branch, "00001");
+        assert_eq!(state.audit.last().unwrap().action, Action::SettingChange);
+    }
+
+    #[test]
+    fn setting_cancel_and_defaults() {
+        let mut state = logged_in_as(Role::Manager);
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        change_setting(&mut state, Field::ShopName, "ร้านซันมินิมาร์ท");
+        let _ = state.update(Message::Setting(MessageSetting::Cancel));
+        assert_eq!(state.setting.shop_name, "");
+
+        state.setting.printer_columns = 32;
+        state.setting.shop_name = "ร้านซันมินิมาร์ท".to_string();
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::SectionSelected(
+            Section::Printer,
+        )));
+        let _ = state.update(Message::Setting(MessageSetting::Defaults));
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+        assert_eq!(state.setting.printer_columns, 42);
+        assert_eq!(state.setting.shop_name, "ร้านซันมินิมาร์ท");
+    }
+
+    #[test]
+    fn vat_rate_applies_to_exclusive_prices() {
+        let mut state = logged_in();
+        state.setting.vat_rate = 10;
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        if let Pages::Sale(sale) = &mut state.pages {
+            sale.item.vat = Vat::Exclusive;
+        }
+        scan(&mut state, &["100"]);
+
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.total, 110),
+            _ => panic!("expected sale page"),
+        }
+    }
+
+    #[test]
+    fn connection_test_saves_on_confirm() {
+        let mut state = logged_in_as(Role::Manager);
+        let saved = state.setting.database_url().to_string();
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::SectionSelected(
+            Section::Connection,
+        )));
+        change_setting(&mut state, Field::DatabaseUrl, "http://10.0.0.5:3000/");
+        let _ = state.update(Message::Setting(MessageSetting::TestConnection));
+
+        let url = "http://10.0.0.5:3000".to_string();
+        match &state.pages {
+            Pages::Setting(page) => assert_eq!(page.probe, Some(Probe::Testing(url.clone()))),
+            _ => panic!("expected setting page"),
+        }
+        let health = Health {
+            latency: std::time::Duration::from_millis(12),
+            version: Some("1.4.0".to_string()),
+        };
+        let _ = state.update(Message::Health(url.clone(), Ok(health.clone())));
+        assert_eq!(state.setting.database_url(), saved);
+        assert_eq!(state.connection, None);
+
+        let _ = state.update(Message::Setting(MessageSetting::UseConnection));
+        assert_eq!(state.setting.database_url(), url);
+        assert_eq!(state.connection, Some(Ok(health)));
+    }
+
+    #[test]
+    fn connection_test_invalid_url() {
+        let mut state = logged_in_as(Role::Manager);
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        change_setting(&mut state, Field::DatabaseUrl, "10.0.0.5:3000");
+        let _ = state.update(Message::Setting(MessageSetting::TestConnection));
+
+        match &state.pages {
+            Pages::Setting(page) => {
+                assert_eq!(page.probe, None);
+                assert_eq!(page.error, Some("รูปแบบ URL ไม่ถูกต้อง"));
+            }
+            _ => panic!("expected setting page"),
+        }
+    }
+
+    #[test]
+    fn repeated_failures_go_offline() {
+        let mut state = logged_in();
+        let url = "http://10.0.0.5:3000".to_string();
+        state.setting.profiles = vec![Profile::live(url.clone())];
+        state.setting.profile = "live".to_string();
+        let health = Health {
+            latency: std::time::Duration::from_millis(30),
+            version: None,
+        };
+        assert_eq!(state.connectivity(), None);
+
+        let _ = state.update(Message::Health(url.clone(), Ok(health.clone())));
+        assert_eq!(state.connectivity(), Some(Connectivity::Online));
+        for _ in 0..2 {
+            let _ = state.update(Message::Health(url.clone(), Err(Failure::Timeout)));
+            assert_eq!(state.connectivity(), Some(Connectivity::Degraded));
+        }
+        let _ = state.update(Message::Health(url.clone(), Err(Failure::Timeout)));
+        assert_eq!(state.connectivity(), Some(Connectivity::Offline));
+
+        // Another backend's result doesn't count
+        let _ = state.update(Message::Health(
+            "http://10.0.0.6:3000".to_string(),
+            Ok(health.clone()),
+        ));
+        assert_eq!(state.connectivity(), Some(Connectivity::Offline));
+        let _ = state.update(Message::Health(url, Ok(health)));
+        assert_eq!(state.connectivity(), Some(Connectivity::Online));
+    }
+
+    #[test]
+    fn health_check_updates_status_on_any_page() {
+        let mut state = logged_in();
+        state.setting.profiles = vec![Profile::live("http://10.0.0.5:3000".to_string())];
+        state.setting.profile = "live".to_string();
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Health(
+            "http://10.0.0.5:3000".to_string(),
+            Err(Failure::Timeout),
+        ));
+
+        assert_eq!(state.connection, Some(Err(Failure::Timeout)));
+        assert!(matches!(state.pages, Pages::Sale(_)));
+    }
+
+    #[test]
+    fn switch_to_training_profile() {
+        let mut state = logged_in_as(Role::Manager);
+        state.setting.profiles = vec![Profile::live("http://10.0.0.5:3000".to_string())];
+        state.setting.profile = "live".to_string();
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::SectionSelected(
+            Section::Connection,
+        )));
+        let _ = state.update(Message::Setting(MessageSetting::AddProfile));
+        change_setting(&mut state, Field::ProfileName, "training");
+        change_setting(&mut state, Field::DatabaseUrl, "http://10.0.0.6:3000");
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+
+        assert!(matches!(state.pages, Pages::Main));
+        assert_eq!(state.setting.profiles.len(), 2);
+        assert_eq!(state.setting.database_url(), "http://10.0.0.6:3000");
+        assert!(state.setting.training());
+        assert_eq!(state.connection, None);
+
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::ProfileSelected(
+            "live".to_string(),
+        )));
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+        assert_eq!(state.setting.database_url(), "http://10.0.0.5:3000");
+        assert!(!state.setting.training());
+    }
+
+    #[test]
+    fn practice_profile_keeps_its_own_records() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-practice-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut state = logged_in_as(Role::Manager);
+        state.data = dir.clone();
+        state.setting.profiles = vec![
+            Profile::live("http://10.0.0.5:3000".to_string()),
+            Profile {
+                name: "ทดสอบ".to_string(),
+                url: "http://10.0.0.6:3000".to_string(),
+                production: false,
+            },
+        ];
+        state.setting.profile = "live".to_string();
+        sell(&mut state, &["100"]);
+        let real = state.transactions.clone();
+        std::fs::write(
+            dir.join("transactions.jsonl"),
+            serde_json::to_string(&real[0]).unwrap() + "\n",
+        )
+        .unwrap();
+
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::ProfileSelected(
+            "ทดสอบ".to_string(),
+        )));
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+        assert!(state.transactions.is_empty());
+        sell(&mut state, &["20"]);
+        assert_eq!(state.transactions[0].number, 1);
+        // Practice is no tax invoice
+        issue_invoice(&mut state, "1", "0105536092641");
+        assert_eq!(state.issuing, None);
+        assert_eq!(state.invoices.len(), 1);
+        let _ = state.update(Message::Invoice(MessageInvoice::Back));
+
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::ProfileSelected(
+            "live".to_string(),
+        )));
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+        assert_eq!(state.transactions, real);
+        assert!(state.invoices.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_profile_name() {
+        let mut state = logged_in_as(Role::Manager);
+        state.setting.profiles = vec![Profile::live("".to_string())];
+        state.setting.profile = "live".to_string();
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::AddProfile));
+        change_setting(&mut state, Field::ProfileName, "live");
+        let _ = state.update(Message::Setting(MessageSetting::Save));
+
+        match &state.pages {
+            Pages::Setting(page) => {
+                assert_eq!(page.section, Section::Connection);
+                assert_eq!(page.error, Some("ชื่อโปรไฟล์ซ้ำ"));
+            }
+            _ => panic!("expected setting page"),
+        }
+        assert_eq!(state.setting.profiles.len(), 1);
+    }
+
+    fn water() -> Product {
+        Product {
+            barcode: "8850999".to_string(),
+            name: "น้ำดื่ม".to_string(),
+            retail: 7,
+            vat: Vat::Inclusive,
+            stock: 24,
+        }
+    }
+
+    fn start_training(state: &mut State) -> Fake {
+        let _ = state.update(Message::Synced(
+            "".to_string(),
+            0,
+            Ok(Changes {
+                cursor: 1,
+                products: vec![water()],
+                removed: Vec::new(),
+            }),
+        ));
+        let _ = state.update(Message::Main(MessageMain::Training));
+        state.training.as_ref().unwrap().backend.clone()
+    }
+
+    #[test]
+    fn scan_uses_product_cache() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Synced(
+            "".to_string(),
+            0,
+            Ok(Changes {
+                cursor: 4,
+                products: vec![water()],
+                removed: Vec::new(),
+            }),
+        ));
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["8850999"]);
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.items[0].name, "น้ำดื่ม");
+                assert_eq!(sale.total, 7);
+            }
+            _ => panic!("expected sale page"),
+        }
+
+        let changes = Changes {
+            cursor: 5,
+            products: vec![Product {
+                retail: 8,
+                ..water()
+            }],
+            removed: Vec::new(),
+        };
+        let _ = state.update(Message::Synced("".to_string(), 4, Ok(changes)));
+        assert_eq!(state.catalogue.cursor, 5);
+        scan(&mut state, &["8850999"]);
+        match &state.pages {
+            Pages::Sale(sale) => assert_eq!(sale.total, 15),
+            _ => panic!("expected sale page"),
+        }
+    }
+
+    #[test]
+    fn resync_from_settings() {
+        let mut state = logged_in_as(Role::Manager);
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        let _ = state.update(Message::Setting(MessageSetting::Resync));
+        match &state.pages {
+            Pages::Setting(page) => assert!(page.resyncing),
+            _ => panic!("expected setting page"),
+        }
+
+        let _ = state.update(Message::Synced(
+            "".to_string(),
+            0,
+            Err("timeout".to_string()),
+        ));
+        match &state.pages {
+            Pages::Setting(page) => assert!(!page.resyncing),
+            _ => panic!("expected setting page"),
+        }
+    }
+
+    #[test]
+    fn training_sells_from_snapshot_and_is_discarded() {
+        let mut state = logged_in();
+        let real = Customer {
+            phone: "0812345678".to_string(),
+            name: "สมหญิง".to_string(),
+            points: 10,
+        };
+        state.customers = vec![real.clone()];
+        sell(&mut state, &["100"]);
+        let fake = start_training(&mut state);
+        assert!(state.in_training());
+        assert!(state.transactions.is_empty());
+
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::AmountChanged("20".to_string())));
+        scan(&mut state, &["8850999"]);
+        match &state.pages {
+            Pages::Sale(sale) => {
+                assert_eq!(sale.items[0].name, "น้ำดื่ม");
+                assert_eq!(sale.total, 140);
+            }
+            _ => panic!("expected sale page"),
+        }
+        let _ = state.update(Message::Sale(MessageSale::PhoneChanged(
+            "0812345678".to_string(),
+        )));
+        let _ = state.update(Message::Sale(MessageSale::PhoneSubmit));
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Receive("200".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        assert_eq!(state.transactions.len(), 1);
+        assert_ne!(state.customers, vec![real.clone()]);
+
+        // What the api would have sent goes to the fake backend
+        let _ = iced::futures::executor::block_on(api::post_transaction(
+            state.backend(),
+            state.transactions[0].clone(),
+        ));
+        assert_eq!(fake.with(|fake| fake.product("8850999").unwrap().stock), 4);
+
+        let _ = state.update(Message::Sale(MessageSale::Back));
+        let _ = state.update(Message::Main(MessageMain::Training));
+        assert!(!state.in_training());
+        assert_eq!(state.transactions.len(), 1);
+        assert_eq!(state.transactions[0].total, 100);
+        assert_eq!(state.customers, vec![real]);
+    }
+
+    #[test]
+    fn training_audit_is_discarded() {
+        let mut state = logged_in_as(Role::Supervisor);
+        let audit = state.audit.clone();
+        start_training(&mut state);
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["8850999"]);
+        let _ = state.update(Message::Sale(MessageSale::VoidSale));
+        assert_eq!(state.audit.len(), audit.len() + 1);
+
+        let _ = state.update(Message::Sale(MessageSale::Back));
+        let _ = state.update(Message::Main(MessageMain::Training));
+        assert_eq!(state.audit, audit);
+    }
+
+    #[test]
+    fn logout_ends_training() {
+        let mut state = logged_in();
+        start_training(&mut state);
+        let _ = state.update(Message::Main(MessageMain::Logout));
+        assert_eq!(state.training, None);
+    }
+
+    /// Runs `task` and the tasks of the messages it leads to, as the runtime
+    /// would, until there is nothing left to do.
+    async fn drive(state: &mut State, task: Task<Message>) {
+        let mut tasks = std::collections::VecDeque::from([task]);
+        while let Some(task) = tasks.pop_front() {
+            let Some(mut stream) = iced_runtime::task::into_stream(task) else {
+                continue;
+            };
+            while let Some(action) = stream.next().await {
+                if let iced_runtime::Action::Output(message) = action {
+                    tasks.push_back(state.update(message));
+                }
+            }
+        }
+    }
+
+    /// A cashier on the main page, using a mock backend with the sample
+    /// products already synced and records kept in `name` under the temp
+    /// directory.
+    async fn on_mock_backend(name: &str) -> (State, mock::Shared) {
+        let (url, store) = mock::spawn(mock::Store::sample()).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut state = logged_in();
+        state.setting.profiles = vec![Profile::live(url)];
+        state.setting.profile = "live".to_string();
+        state.config = dir.join("setting.json");
+        state.data = dir;
+
+        let task = Task::batch([state.check_connection(), state.sync_products(0)]);
+        drive(&mut state, task).await;
+        (state, store)
+    }
+
+    #[tokio::test]
+    async fn drawer_session_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-drawer-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut state = logged_in();
+        state.config = dir.join("setting.json");
+        state.data = dir.clone();
+        let _ = state.update(Message::Main(MessageMain::Drawer));
+        let _ = state.update(Message::Drawer(MessageDrawer::FloatChanged(
+            "500".to_string(),
+        )));
+        let task = state.update(Message::Drawer(MessageDrawer::Open));
+        drive(&mut state, task).await;
+        let _ = state.update(Message::Drawer(MessageDrawer::AmountChanged(
+            "120".to_string(),
+        )));
+        let task = state.update(Message::Drawer(MessageDrawer::CashOut));
+        drive(&mut state, task).await;
+
+        let loaded = State::load(state.config.clone(), &dir);
+        assert_eq!(loaded.session, state.session);
+        assert_eq!(loaded.session.map(|session| session.expected()), Some(380));
+
+        let _ = state.update(Message::Drawer(MessageDrawer::ToCount));
+        let task = state.update(Message::Drawer(MessageDrawer::Close));
+        drive(&mut state, task).await;
+        assert!(!dir.join("drawer.json").exists());
+        assert_eq!(State::load(state.config.clone(), &dir).session, None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn journal_cleared_once_sale_recorded() {
+        let (mut state, _) = on_mock_backend("sunminimart-mock-journal").await;
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::BarcodeChanged(
+            "8850999320014".to_string(),
+        )));
+        let task = state.update(Message::Sale(MessageSale::BarcodeSubmit));
+        drive(&mut state, task).await;
+        assert!(state.data.join("sale.json").exists());
+
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let task = state.update(Message::Sale(MessageSale::Pay));
+        assert_eq!(state.journaled, None);
+        // Left in place until the transaction is appended
+        assert!(state.data.join("sale.json").exists());
+        drive(&mut state, task).await;
+        assert!(!state.data.join("sale.json").exists());
+        let (recorded, _) = read_lines::<Transaction>(&state.data.join("transactions.jsonl"));
+        assert_eq!(recorded, state.transactions);
+        std::fs::remove_dir_all(&state.data).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sale_against_mock_backend() {
+        let (mut state, store) = on_mock_backend("sunminimart-mock-sale").await;
+        assert_eq!(state.connectivity(), Some(Connectivity::Online));
+        assert_eq!(state.catalogue.products.len(), 4);
+
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Sale(MessageSale::AmountChanged("3".to_string())));
+        scan(&mut state, &["8850999320014"]);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Receive("50".to_string())));
+        let task = state.update(Message::Sale(MessageSale::Pay));
+        drive(&mut state, task).await;
+        assert_eq!(state.transactions[0].total, 21);
+        assert_eq!(store.lock().unwrap().transactions.len(), 1);
+
+        let task = state.sync_products(state.catalogue.cursor);
+        drive(&mut state, task).await;
+        assert_eq!(state.catalogue.product("8850999320014").unwrap().stock, 97);
+        std::fs::remove_dir_all(&state.data).unwrap();
+    }
+
+    #[tokio::test]
+    async fn new_backend_replaces_the_catalogue() {
+        let (mut state, _) = on_mock_backend("sunminimart-mock-switch").await;
+        let old = state.setting.database_url().to_string();
+        let mut other = mock::Store::default();
+        other.upsert(Product {
+            stock: 1,
+            ..water()
+        });
+        let (url, _) = mock::spawn(other).await.unwrap();
+        state.user = Some(User::new("admin".to_string(), Role::Manager, "9999").unwrap());
+        let _ = state.update(Message::Main(MessageMain::Setting));
+        change_setting(&mut state, Field::DatabaseUrl, &url);
+        let task = state.update(Message::Setting(MessageSetting::Save));
+        assert!(state.catalogue.products.is_empty());
+        drive(&mut state, task).await;
+        assert_eq!(state.catalogue.products.len(), 1);
+        assert_eq!(state.catalogue.product("8850999").unwrap().stock, 1);
+
+        // A sync from the backend left comes too late to count
+        let task = state.update(Message::Synced(
+            old,
+            0,
+            Ok(Changes {
+                cursor: 9,
+                products: vec![water()],
+                removed: Vec::new(),
+            }),
+        ));
+        drive(&mut state, task).await;
+        assert_eq!(state.catalogue.cursor, 1);
+        assert_eq!(state.catalogue.product("8850999").unwrap().stock, 1);
+        std::fs::remove_dir_all(&state.data).unwrap();
+    }
+
+    #[tokio::test]
+    async fn add_product_to_mock_backend() {
+        let (mut state, store) = on_mock_backend("sunminimart-mock-product").await;
+        state.user = Some(User::new("admin".to_string(), Role::Manager, "9999").unwrap());
+        let _ = state.update(Message::Main(MessageMain::Stock));
+        let _ = state.update(Message::Stock(MessageStock::ToAddProduct));
+        for message in [
+            MessageStock::BarcodeChanged("8851234567890".to_string()),
+            MessageStock::NameChanged("ไข่ไก่".to_string()),
+            MessageStock::PriceChanged("x".to_string()),
+            MessageStock::VatSelected(Vat::Exempt),
+        ] {
+            let _ = state.update(Message::Stock(message));
+        }
+        let _ = state.update(Message::Stock(MessageStock::Save));
+        assert_eq!(store.lock().unwrap().products.len(), 4);
+
+        let _ = state.update(Message::Stock(MessageStock::PriceChanged("5".to_string())));
+        let task = state.update(Message::Stock(MessageStock::Save));
+        drive(&mut state, task).await;
+        let product = state.catalogue.product("8851234567890").unwrap();
+        assert_eq!((product.retail, product.vat), (5, Vat::Exempt));
+        match &state.pages {
+            Pages::Stock(Stock::AddProduct(item)) => {
+                assert_eq!(item.result, Some(Ok("8851234567890".to_string())))
+            }
+            _ => panic!("expected stock page"),
+        }
+        std::fs::remove_dir_all(&state.data).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refund_restocks_mock_backend() {
+        let (mut state, store) = on_mock_backend("sunminimart-mock-refund").await;
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["8851959132012", "8851959132012"]);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Receive("24".to_string())));
+        let task = state.update(Message::Sale(MessageSale::Pay));
+        drive(&mut state, task).await;
+        let _ = state.update(Message::Sale(MessageSale::Back));
+
+        let _ = state.update(Message::Main(MessageMain::Refund));
+        let _ = state.update(Message::Refund(MessageRefund::ReceiptChanged(
+            "1".to_string(),
+        )));
+        let _ = state.update(Message::Refund(MessageRefund::ReceiptSubmit));
+        let _ = state.update(Message::Refund(MessageRefund::QuantityChanged(
+            0,
+            "1".to_string(),
+        )));
+        let _ = state.update(Message::Refund(MessageRefund::ReasonSelected(
+            RefundReason::Damaged,
+        )));
+        let _ = state.update(Message::Refund(MessageRefund::PinChanged(
+            "1234".to_string(),
+        )));
+        let task = state.update(Message::Refund(MessageRefund::Refund));
+        drive(&mut state, task).await;
+
+        assert_eq!(state.transactions.len(), 2);
+        let store = store.lock().unwrap();
+        assert_eq!(store.movements.len(), 1);
+        assert_eq!(store.products["8851959132012"].stock, 99);
+        assert_eq!(store.transactions.len(), 2);
+        std::fs::remove_dir_all(&state.data).unwrap();
+    }
+
+    #[test]
+    fn scan_pay_and_print_receipt() {
+        let mut app = Harness::new(logged_in(), vec![water()]);
+        app.send(Message::Main(MessageMain::Sale));
+        assert_eq!(app.focused(), Some(Id::new("barcode")));
+
+        app.send(Message::Sale(MessageSale::BarcodeChanged(
+            "8850999".to_string(),
+        )))
+        .send(Message::Sale(MessageSale::AmountChanged("2".to_string())))
+        .send(Message::Sale(MessageSale::BarcodeSubmit));
+        assert_eq!(app.sale().items.len(), 1);
+        assert_eq!(app.sale().total, 14);
+
+        app.key(Named::F12);
+        assert!(app.sale().paying);
+        assert_eq!(app.focused(), Some(Id::new("received")));
+
+        app.send(Message::Sale(MessageSale::Receive("20".to_string())))
+            .send(Message::Sale(MessageSale::Pay));
+        assert_eq!(app.sale(), &Sale::default());
+        assert_eq!(app.focused(), Some(Id::new("barcode")));
+        assert!(app
+            .produced
+            .iter()
+            .any(|message| matches!(message, Message::Printed(Ok(())))));
+
+        let receipt = app.receipt();
+        let contains = |text: &str| {
+            let needle = receipt::encode(text);
+            receipt.windows(needle.len()).any(|window| window == needle)
+        };
+        assert!(contains("ไม่ใช่ใบเสร็จรับเงิน"));
+        assert!(contains("เงินทอน"));
+        assert_eq!(
+            app.backend()
+                .with(|backend| backend.product("8850999").unwrap().stock),
+            22
+        );
+        assert_eq!(app.state.transactions.len(), 1);
+
+        app.key(Named::Escape);
+        assert_eq!(app.state.pages, Pages::Main);
+    }
+
+    #[test]
+    fn leaving_pay_refocuses_barcode() {
+        let mut app = Harness::new(logged_in(), vec![water()]);
+        app.send(Message::Main(MessageMain::Sale))
+            .send(Message::Sale(MessageSale::BarcodeChanged(
+                "8850999".to_string(),
+            )))
+            .send(Message::Sale(MessageSale::BarcodeSubmit))
+            .key(Named::F12)
+            .key(Named::F12);
+
+        assert!(!app.sale().paying);
+        assert_eq!(app.sale().items.len(), 1);
+        assert_eq!(app.focused(), Some(Id::new("barcode")));
+    }
+
+    #[test]
+    fn stray_messages_are_ignored() {
+        let mut app = Harness::new(logged_in(), vec![water()]);
+        app.send(Message::Main(MessageMain::Sale))
+            .send(Message::Sale(MessageSale::BarcodeChanged(
+                "8850999".to_string(),
+            )))
+            .send(Message::Sale(MessageSale::BarcodeSubmit))
+            // Released as the page changed
+            .send(Message::Stock(MessageStock::Back))
+            .send(Message::Setting(MessageSetting::Cancel));
+
+        assert_eq!(app.sale().items.len(), 1);
+    }
+
+    #[test]
+    fn background_errors_show_on_any_page() {
+        let mut state = logged_in();
+        let _ = state.update(Message::Printed(Err("กระดาษหมด".to_string())));
+        assert_eq!(state.error, Some("พิมพ์ใบเสร็จไม่สำเร็จ: กระดาษหมด".to_string()));
+
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        let _ = state.update(Message::Api(Err("timeout".to_string())));
+        let _ = state.update(Message::Saved(Ok(())));
+        assert_eq!(
+            state.error,
+            Some("ส่งข้อมูลไปยังเซิร์ฟเวอร์ไม่สำเร็จ: timeout".to_string())
+        );
+
+        let _ = state.update(Message::DismissError);
+        assert_eq!(state.error, None);
+    }
+
+    #[test]
+    fn late_export_is_kept_off_a_reopened_viewer() {
+        let mut state = logged_in_as(Role::Manager);
+        let _ = state.update(Message::Main(MessageMain::Audit));
+        let _ = state.update(Message::Audit(MessageAudit::Export));
+        let _ = state.update(Message::Audit(MessageAudit::Back));
+        let _ = state.update(Message::Main(MessageMain::Audit));
+        let _ = state.update(Message::Audit(MessageAudit::Exported(
+            1,
+            Ok("audit.csv".to_string()),
+        )));
+        let Pages::Audit(page) = &state.pages else {
+            panic!("expected audit page");
+        };
+        assert_eq!(page.exported, None);
+
+        let _ = state.update(Message::Audit(MessageAudit::Export));
+        let _ = state.update(Message::Audit(MessageAudit::Exported(
+            2,
+            Ok("audit.csv".to_string()),
+        )));
+        let Pages::Audit(page) = &state.pages else {
+            panic!("expected audit page");
+        };
+        assert_eq!(page.exported, Some(Ok("audit.csv".to_string())));
+        assert_eq!(page.exporting, None);
+    }
+
+    #[tokio::test]
+    async fn audit_export_beside_the_records() {
+        let dir =
+            std::env::temp_dir().join(format!("sunminimart-audit-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut state = logged_in_as(Role::Manager);
+        state.data = dir.clone();
+        let _ = state.update(Message::Main(MessageMain::Audit));
+        let task = state.update(Message::Audit(MessageAudit::Export));
+        drive(&mut state, task).await;
+        let Pages::Audit(page) = &state.pages else {
+            panic!("expected audit page");
+        };
+        let Some(Ok(path)) = &page.exported else {
+            panic!("expected an export");
+        };
+        assert!(Path::new(path).starts_with(&dir));
+        assert!(Path::new(path).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn left_by_crash() -> Snapshot {
+        Snapshot {
+            items: vec![Item {
+                barcode: "8850999".to_string(),
+                name: "น้ำดื่ม".to_string(),
+                price: 7,
+                amount: "3".to_string(),
+                sum: 21,
+                vat: Vat::Inclusive,
+            }],
+            payments: vec![Tender {
+                method: PaymentMethod::Cash,
+                amount: 20,
+            }],
+            ..Snapshot::default()
+        }
+    }
+
+    #[test]
+    fn restores_sale_left_by_crash() {
+        let mut state = State {
+            recovered: Some(left_by_crash()),
+            ..logged_in()
+        };
+        // Not before the one left is settled
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        assert_eq!(state.pages, Pages::Main);
+
+        let _ = state.update(Message::Main(MessageMain::Restore));
+        let Pages::Sale(sale) = &state.pages else {
+            panic!("expected sale page");
+        };
+        assert_eq!(sale.items, left_by_crash().items);
+        assert_eq!(sale.total, 21);
+        assert_eq!(sale.remaining(), 1);
+        assert!(sale.paying);
+        assert_eq!(state.recovered, None);
+        assert_eq!(state.journaled, Some(left_by_crash()));
+    }
+
+    #[test]
+    fn discard_sale_left_by_crash() {
+        let mut state = State {
+            recovered: Some(left_by_crash()),
+            ..logged_in()
+        };
+        let _ = state.update(Message::Main(MessageMain::Discard));
+        assert!(state.recovered.is_some());
+
+        let mut state = State {
+            recovered: Some(left_by_crash()),
+            ..logged_in_as(Role::Supervisor)
+        };
+        let _ = state.update(Message::Main(MessageMain::Discard));
+        assert_eq!(state.recovered, None);
+        assert!(matches!(
+            state.audit.last().map(|entry| &entry.action),
+            Some(Action::VoidSale {
+                items: 1,
+                total: 21,
+                approver: Some(_),
+            })
+        ));
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        assert_eq!(state.pages, Pages::Sale(Sale::default()));
+    }
+
+    #[test]
+    fn discount_after_tenders_finishes_sale() {
+        let mut state = logged_in_as(Role::Supervisor);
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["500"]);
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Receive("300".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        let _ = state.update(Message::Sale(MessageSale::ExitPay));
+        discount(&mut state, Target::Bill, "300", false, "");
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+
+        assert_eq!(state.transactions.len(), 1);
+        let transaction = &state.transactions[0];
+        assert_eq!(transaction.total, 200);
+        assert_eq!(transaction.change, 100);
+        assert_eq!(transaction.cash(), 200);
+        assert_eq!(state.journaled, None);
+    }
+
+    #[test]
+    fn settle_gives_back_what_was_not_taken_first() {
+        let tender = |method, amount| Tender { method, amount };
+        let mut sale = Sale {
+            total: 120,
+            payments: vec![
+                tender(PaymentMethod::Credit, 100),
+                tender(PaymentMethod::Cash, 50),
+                tender(PaymentMethod::PromptPay, 100),
+            ],
+            ..Sale::default()
+        };
+        assert_eq!(sale.settle(), 30);
+        assert_eq!(
+            sale.payments,
+            vec![
+                tender(PaymentMethod::Cash, 50),
+                tender(PaymentMethod::PromptPay, 100),
+            ]
+        );
+
+        let mut sale = Sale {
+            total: 150,
+            payments: vec![tender(PaymentMethod::PromptPay, 200)],
+            ..Sale::default()
+        };
+        assert_eq!(sale.settle(), 0);
+        assert_eq!(sale.payments, vec![tender(PaymentMethod::PromptPay, 150)]);
+    }
+
+    #[test]
+    fn journal_follows_sale() {
+        let mut state = logged_in_as(Role::Supervisor);
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        assert_eq!(state.journaled, None);
+
+        scan(&mut state, &["100", "200"]);
+        let journaled = state.journaled.clone().unwrap();
+        assert_eq!(journaled.items.len(), 2);
+
+        let _ = state.update(Message::Sale(MessageSale::EnterPay));
+        let _ = state.update(Message::Sale(MessageSale::Receive("100".to_string())));
+        assert_eq!(state.journaled, Some(journaled));
+        // Tenders taken are kept with the basket
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        assert_eq!(state.journaled.as_ref().unwrap().payments.len(), 1);
+        let _ = state.update(Message::Sale(MessageSale::Receive("200".to_string())));
+        let _ = state.update(Message::Sale(MessageSale::Pay));
+        assert_eq!(state.journaled, None);
+
+        scan(&mut state, &["100"]);
+        assert!(state.journaled.is_some());
+        let _ = state.update(Message::Sale(MessageSale::VoidSale));
+        assert_eq!(state.journaled, None);
+
+        // Training sales are never journaled
+        let _ = state.update(Message::Sale(MessageSale::Back));
+        start_training(&mut state);
+        let _ = state.update(Message::Main(MessageMain::Sale));
+        scan(&mut state, &["8850999"]);
+        assert_eq!(state.journaled, None);
+    }
+
+    #[test]
+    fn load_keeps_records_beside_config() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-load-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let snapshot = serde_json::to_string(&left_by_crash()).unwrap();
+        std::fs::write(dir.join("sale.json"), snapshot).unwrap();
+
+        let state = State::load(dir.join("setting.json"), &dir);
+        assert_eq!(state.data, dir);
+        assert_eq!(state.recovered, Some(left_by_crash()));
+        // Tests start from nothing on disk
+        assert_eq!(State::default().recovered, None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_audit_log_stays_broken() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-damaged-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut state = State {
+            data: dir.clone(),
+            ..logged_in()
+        };
+        open_drawer(&mut state, "500");
+        let entry = serde_json::to_string(&state.audit[0]).unwrap();
+        std::fs::write(dir.join("audit.jsonl"), format!("{}\nnot json\n", entry)).unwrap();
+
+        let mut state = State {
+            user: state.user,
+            pages: Pages::Main,
+            ..State::load(dir.join("setting.json"), &dir)
+        };
+        assert_eq!(state.audit.len(), 1);
+        assert!(state.audit_error.is_some());
+        // Chained onto what was read rather than starting over
+        open_drawer(&mut state, "500");
+        assert_eq!(audit::verify(&state.audit), Ok(()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_users_file_blocks_login() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-login-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(State::load(dir.join("setting.json"), &dir).setup);
+
+        std::fs::write(dir.join("users.json"), "not json").unwrap();
+        let mut state = State::load(dir.join("setting.json"), &dir);
+        assert!(!state.setup);
+        assert!(state.users_error.is_some());
+        // Not taken for a first run that would make anyone the manager
+        sign_in(&mut state, "anyone", "0000");
+        assert!(matches!(state.pages, Pages::Login(_)));
+        assert!(state.users.is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     // #[test]
     // fn pay() {
     //     let mut state = State::default();
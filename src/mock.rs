@@ -0,0 +1,197 @@
+//! Backend stand-in keeping everything in memory, for running the till
+//! without the real server and for tests against real HTTP. Covers the
+//! endpoints the client calls. Seeded with a few products it serves with
+//! `cargo run --features mock -- --mock-backend 127.0.0.1:3000`.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use crate::api::Product;
+use crate::vat::Vat;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Movement {
+    pub barcode: String,
+    pub amount: i64,
+    pub kind: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PriceCheck {
+    pub barcode: String,
+    pub price: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Store {
+    pub products: BTreeMap<String, Product>,
+    /// Cursor of the last change to each product.
+    pub changed: BTreeMap<String, u64>,
+    pub cursor: u64,
+    pub movements: Vec<Movement>,
+    pub price_checks: Vec<PriceCheck>,
+    /// Transactions as posted.
+    pub transactions: Vec<Value>,
+}
+
+impl Store {
+    /// A few products to scan.
+    pub fn sample() -> Self {
+        let mut store = Store::default();
+        for (barcode, name, retail, vat) in [
+            ("8850999320014", "น้ำดื่ม 600 มล.", 7, Vat::Exempt),
+            ("8851959132012", "นมจืด 225 มล.", 12, Vat::Inclusive),
+            ("8850157400013", "บะหมี่กึ่งสำเร็จรูป", 6, Vat::Inclusive),
+            ("8850123110016", "ขนมปังแผ่น", 35, Vat::Exclusive),
+        ] {
+            store.upsert(Product {
+                barcode: barcode.to_string(),
+                name: name.to_string(),
+                retail,
+                vat,
+                stock: 100,
+            });
+        }
+        store
+    }
+
+    pub fn upsert(&mut self, product: Product) {
+        self.cursor += 1;
+        self.changed.insert(product.barcode.clone(), self.cursor);
+        self.products.insert(product.barcode.clone(), product);
+    }
+
+    /// Stock of unknown products isn't tracked.
+    fn move_stock(&mut self, barcode: &str, amount: i64) {
+        if let Some(product) = self.products.get_mut(barcode) {
+            product.stock += amount;
+            self.cursor += 1;
+            self.changed.insert(barcode.to_string(), self.cursor);
+        }
+    }
+}
+
+pub type Shared = Arc<Mutex<Store>>;
+
+fn lock(store: &Shared) -> std::sync::MutexGuard<'_, Store> {
+    store.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "version": concat!("mock-", env!("CARGO_PKG_VERSION")) }))
+}
+
+#[derive(Deserialize)]
+struct Since {
+    #[serde(default)]
+    since: u64,
+}
+
+async fn changes(State(store): State<Shared>, Query(Since { since }): Query<Since>) -> Json<Value> {
+    let store = lock(&store);
+    let products: Vec<&Product> = store
+        .changed
+        .iter()
+        .filter(|(_, cursor)| **cursor > since)
+        .filter_map(|(barcode, _)| store.products.get(barcode))
+        .collect();
+    Json(json!({
+        "cursor": store.cursor,
+        "products": products,
+        "removed": [],
+    }))
+}
+
+async fn add_product(State(store): State<Shared>, Json(product): Json<Product>) -> StatusCode {
+    let mut store = lock(&store);
+    // Adding a product again keeps what is on hand
+    let stock = store
+        .products
+        .get(&product.barcode)
+        .map_or(0, |known| known.stock);
+    store.upsert(Product { stock, ..product });
+    StatusCode::CREATED
+}
+
+async fn movement(State(store): State<Shared>, Json(movement): Json<Movement>) -> StatusCode {
+    let mut store = lock(&store);
+    store.move_stock(&movement.barcode, movement.amount);
+    store.movements.push(movement);
+    StatusCode::CREATED
+}
+
+async fn price_check(State(store): State<Shared>, Json(check): Json<PriceCheck>) -> StatusCode {
+    lock(&store).price_checks.push(check);
+    StatusCode::CREATED
+}
+
+#[derive(Deserialize)]
+struct Line {
+    barcode: String,
+    amount: String,
+}
+
+#[derive(Deserialize)]
+struct Sold {
+    kind: Value,
+    items: Vec<Line>,
+}
+
+async fn transaction(State(store): State<Shared>, Json(transaction): Json<Value>) -> StatusCode {
+    let Ok(sold) = serde_json::from_value::<Sold>(transaction.clone()) else {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    };
+    let mut store = lock(&store);
+    // Refunds are restocked by their own movements
+    if sold.kind == "Sale" {
+        for line in sold.items {
+            store.move_stock(&line.barcode, -line.amount.parse::<i64>().unwrap_or(0));
+        }
+    }
+    store.transactions.push(transaction);
+    StatusCode::CREATED
+}
+
+pub fn router(store: Shared) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/products", post(add_product))
+        .route("/products/changes", get(changes))
+        .route("/stock/movements", post(movement))
+        .route("/stock/price-checks", post(price_check))
+        .route("/transactions", post(transaction))
+        .with_state(store)
+}
+
+pub async fn serve(listener: TcpListener, store: Shared) -> std::io::Result<()> {
+    axum::serve(listener, router(store)).await
+}
+
+/// Serves the sample store on `address` until the process is stopped.
+#[cfg(feature = "mock")]
+pub fn run(address: &str) -> std::io::Result<()> {
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let listener = TcpListener::bind(address).await?;
+        println!("mock backend on http://{}", listener.local_addr()?);
+        serve(listener, Arc::new(Mutex::new(Store::sample()))).await
+    })
+}
+
+/// Starts a server on a free local port in the background, returning its
+/// URL and store.
+#[cfg(test)]
+pub async fn spawn(store: Store) -> std::io::Result<(String, Shared)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let store = Arc::new(Mutex::new(store));
+    tokio::spawn(serve(listener, store.clone()));
+    Ok((url, store))
+}
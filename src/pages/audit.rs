@@ -0,0 +1,123 @@
+use chrono::NaiveDate;
+use iced::widget::{column, container, keyed_column, pick_list, row, scrollable, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::audit::{verify, Action, Entry};
+use crate::{custom_widget, Message, MessageAudit, State};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct AuditPage {
+    pub user: Option<String>,
+    /// One of [`Action::LABELS`].
+    pub action: Option<&'static str>,
+    /// Date range as `dd/mm/yyyy`, either end may be left empty.
+    pub from: String,
+    pub to: String,
+    /// Export request waiting on its result.
+    pub exporting: Option<u64>,
+    /// Path of the last export, or why it failed.
+    pub exported: Option<Result<String, String>>,
+}
+
+impl AuditPage {
+    pub fn filter<'a>(&'a self, log: &'a [Entry]) -> impl Iterator<Item = &'a Entry> + 'a {
+        let date = |input: &str| NaiveDate::parse_from_str(input.trim(), "%d/%m/%Y").ok();
+        let (from, to) = (date(&self.from), date(&self.to));
+        log.iter().filter(move |entry| {
+            let day = entry.time.date();
+            self.user.as_ref().is_none_or(|user| &entry.user == user)
+                && self
+                    .action
+                    .is_none_or(|action| entry.action.label() == action)
+                && from.is_none_or(|from| day >= from)
+                && to.is_none_or(|to| day <= to)
+        })
+    }
+}
+
+impl State {
+    pub fn audit_page<'a>(&'a self, page: &'a AuditPage) -> Element<'a, Message> {
+        let status = match (&self.audit_error, verify(&self.audit)) {
+            (Some(error), _) => format!("บันทึกเสียหาย อ่านไม่ได้บางส่วน: {}", error),
+            (None, Ok(())) => "ตรวจสอบแล้ว ไม่พบการแก้ไขบันทึก".to_string(),
+            (None, Err(index)) => format!("บันทึกถูกแก้ไขตั้งแต่รายการที่ {}", index + 1),
+        };
+
+        let mut users: Vec<String> = self.audit.iter().map(|entry| entry.user.clone()).collect();
+        users.sort();
+        users.dedup();
+        let filters = row![
+            pick_list(users, page.user.clone(), |user| {
+                Message::Audit(MessageAudit::UserSelected(user))
+            })
+            .placeholder("ผู้ใช้ทั้งหมด")
+            .text_size(25)
+            .width(Fill),
+            pick_list(Action::LABELS, page.action, |action| {
+                Message::Audit(MessageAudit::ActionSelected(action))
+            })
+            .placeholder("ทุกรายการ")
+            .text_size(25)
+            .width(Fill),
+            custom_widget::text("ตั้งแต่", 25),
+            custom_widget::text_input("from", &page.from, 25)
+                .on_input(|input: String| Message::Audit(MessageAudit::FromChanged(input))),
+            custom_widget::text("ถึง", 25),
+            custom_widget::text_input("to", &page.to, 25)
+                .on_input(|input: String| Message::Audit(MessageAudit::ToChanged(input))),
+            custom_widget::button("ล้าง", 25).on_press(Message::Audit(MessageAudit::Clear)),
+            custom_widget::button("ส่งออก", 25).on_press_maybe(
+                page.exporting
+                    .is_none()
+                    .then_some(Message::Audit(MessageAudit::Export))
+            ),
+        ]
+        .spacing(10)
+        .align_y(Center);
+
+        let mut content = column![custom_widget::text(status, 30), filters]
+            .spacing(10)
+            .align_x(Center);
+        match &page.exported {
+            Some(Ok(path)) => {
+                content = content.push(custom_widget::text(format!("ส่งออกไปที่ {}", path), 25))
+            }
+            Some(Err(_)) => content = content.push(custom_widget::text("ส่งออกไม่สำเร็จ", 25)),
+            None => {}
+        }
+
+        let title = row![
+            custom_widget::text("เวลา", 20).width(Fill),
+            custom_widget::text("ผู้ใช้", 20).width(Fill),
+            custom_widget::text("รายการ", 20).width(FillPortion(3)),
+        ];
+        let entries: Vec<&Entry> = page.filter(&self.audit).collect();
+        let list = keyed_column(entries.into_iter().rev().enumerate().map(|(index, entry)| {
+            (
+                index,
+                container(row![
+                    custom_widget::text(entry.time.format("%d/%m/%Y %H:%M").to_string(), 25)
+                        .width(Fill),
+                    custom_widget::text(&entry.user, 25).width(Fill),
+                    custom_widget::text(entry.action.to_string(), 25).width(FillPortion(3)),
+                ])
+                .style(|_| container::bordered_box(&Theme::Light))
+                .into(),
+            )
+        }));
+        content = content.push(column![title, scrollable(list).height(Fill)]);
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content.padding(25))
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(6)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .padding(10)
+        .into()
+    }
+}
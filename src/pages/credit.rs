@@ -0,0 +1,180 @@
+use iced::widget::{column, container, keyed_column, pick_list, row, scrollable, Row, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::credit::{find, EntryKind, AGING};
+use crate::receipt::receipt_barcode;
+use crate::user::Permission;
+use crate::{custom_widget, Message, MessageCredit, State};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct CreditPage {
+    /// Name and limit for opening a new account.
+    pub name: String,
+    pub limit: String,
+    /// Account whose statement is shown.
+    pub selected: Option<String>,
+    /// Limit to give the selected account.
+    pub new_limit: String,
+    pub repayment: String,
+    /// Shows the aging report instead of a statement.
+    pub aging: bool,
+    pub error: Option<&'static str>,
+}
+
+impl State {
+    pub fn credit_page<'a>(&'a self, page: &'a CreditPage) -> Element<'a, Message> {
+        let open = row![
+            custom_widget::text("ชื่อ", 25),
+            custom_widget::text_input("name", &page.name, 25)
+                .on_input(|input: String| Message::Credit(MessageCredit::NameChanged(input))),
+            custom_widget::text("วงเงิน", 25),
+            custom_widget::text_input("limit", &page.limit, 25)
+                .on_input(|input: String| Message::Credit(MessageCredit::LimitChanged(input)))
+                .on_submit(Message::Credit(MessageCredit::Open)),
+            custom_widget::button("เปิดบัญชี", 25).on_press_maybe(
+                self.allowed(Permission::Credit)
+                    .then_some(Message::Credit(MessageCredit::Open))
+            ),
+        ]
+        .spacing(10)
+        .align_y(Center);
+        let names: Vec<String> = self
+            .accounts
+            .iter()
+            .map(|account| account.name.clone())
+            .collect();
+        let select = row![
+            pick_list(names, page.selected.clone(), |name| {
+                Message::Credit(MessageCredit::Selected(name))
+            })
+            .placeholder("เลือกบัญชี")
+            .text_size(25)
+            .width(Fill),
+            custom_widget::button("รายงานอายุหนี้", 25)
+                .on_press(Message::Credit(MessageCredit::ToAging)),
+        ]
+        .spacing(10)
+        .align_y(Center);
+
+        let mut content = column![open, select].spacing(10).align_x(Center);
+        if let Some(error) = page.error {
+            content = content.push(custom_widget::text(error, 25));
+        }
+
+        if page.aging {
+            let today = chrono::Local::now().date_naive();
+            let title = Row::with_children(
+                ["บัญชี", "ยอดค้าง"]
+                    .into_iter()
+                    .chain(AGING)
+                    .map(|label| custom_widget::text(label, 20).width(Fill).into()),
+            );
+            let list = keyed_column(
+                self.accounts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, account)| account.balance() > 0)
+                    .map(|(index, account)| {
+                        let cells = [account.name.clone(), account.balance().to_string()]
+                            .into_iter()
+                            .chain(account.aging(today).map(|amount| amount.to_string()));
+                        (
+                            index,
+                            container(Row::with_children(
+                                cells.map(|cell| custom_widget::text(cell, 25).width(Fill).into()),
+                            ))
+                            .style(|_| container::bordered_box(&Theme::Light))
+                            .into(),
+                        )
+                    }),
+            );
+            content = content.push(column![title, scrollable(list).height(Fill)]);
+        } else if let Some(account) = page
+            .selected
+            .as_ref()
+            .and_then(|name| find(&self.accounts, name))
+        {
+            let title = row![
+                custom_widget::text("วันที่", 20).width(Fill),
+                custom_widget::text("รายการ", 20).width(FillPortion(2)),
+                custom_widget::text("จำนวน", 20).width(Fill),
+                custom_widget::text("คงค้าง", 20).width(Fill),
+            ];
+            let list = keyed_column(account.statement().into_iter().enumerate().map(
+                |(index, (entry, balance))| {
+                    let description = match entry.kind {
+                        EntryKind::Charge { receipt } => receipt_barcode(receipt),
+                        EntryKind::Payment => "ชำระเงิน".to_string(),
+                        EntryKind::Refund { receipt } => {
+                            format!("คืนสินค้า {}", receipt_barcode(receipt))
+                        }
+                    };
+                    (
+                        index,
+                        container(row![
+                            custom_widget::text(entry.date.format("%d/%m/%Y").to_string(), 25)
+                                .width(Fill),
+                            custom_widget::text(description, 25).width(FillPortion(2)),
+                            custom_widget::text(entry.amount, 25).width(Fill),
+                            custom_widget::text(balance, 25).width(Fill),
+                        ])
+                        .style(|_| container::bordered_box(&Theme::Light))
+                        .into(),
+                    )
+                },
+            ));
+            let repay = row![
+                custom_widget::text("รับชำระ", 25),
+                custom_widget::text_input("repayment", &page.repayment, 25)
+                    .on_input(|input: String| {
+                        Message::Credit(MessageCredit::RepaymentChanged(input))
+                    })
+                    .on_submit(Message::Credit(MessageCredit::Repay)),
+                custom_widget::button("บันทึก", 25).on_press(Message::Credit(MessageCredit::Repay)),
+            ]
+            .spacing(10)
+            .align_y(Center);
+            let limit = row![
+                custom_widget::text("วงเงินใหม่", 25),
+                custom_widget::text_input("new limit", &page.new_limit, 25)
+                    .on_input(|input: String| {
+                        Message::Credit(MessageCredit::NewLimitChanged(input))
+                    })
+                    .on_submit(Message::Credit(MessageCredit::ChangeLimit)),
+                custom_widget::button("เปลี่ยนวงเงิน", 25).on_press_maybe(
+                    self.allowed(Permission::Credit)
+                        .then_some(Message::Credit(MessageCredit::ChangeLimit))
+                ),
+            ]
+            .spacing(10)
+            .align_y(Center);
+            content = content.push(column![
+                custom_widget::text(
+                    format!(
+                        "ยอดค้าง {} วงเงินคงเหลือ {}",
+                        account.balance(),
+                        account.available()
+                    ),
+                    30
+                ),
+                title,
+                scrollable(list).height(Fill),
+                repay,
+                limit,
+            ]);
+        }
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content.padding(25))
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(4)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .padding(10)
+        .into()
+    }
+}
@@ -0,0 +1,97 @@
+use iced::widget::{column, container, keyed_column, row, scrollable, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::customer::{earned, find, history, redeemed};
+use crate::receipt::receipt_barcode;
+use crate::{custom_widget, Message, MessageCustomer, State};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct CustomerPage {
+    pub phone: String,
+    /// Name for registering a new member.
+    pub name: String,
+    /// Phone number of the member being shown.
+    pub found: Option<String>,
+    pub error: Option<&'static str>,
+}
+
+impl State {
+    pub fn customer_page<'a>(&'a self, page: &'a CustomerPage) -> Element<'a, Message> {
+        let phone = row![
+            custom_widget::text("เบอร์โทร", 25),
+            custom_widget::text_input("phone", &page.phone, 25)
+                .on_input(|input: String| Message::Customer(MessageCustomer::PhoneChanged(input)))
+                .on_submit(Message::Customer(MessageCustomer::Search)),
+            custom_widget::button("ค้นหา", 25).on_press(Message::Customer(MessageCustomer::Search)),
+        ]
+        .spacing(10)
+        .align_y(Center);
+        let register = row![
+            custom_widget::text("ชื่อ", 25),
+            custom_widget::text_input("name", &page.name, 25)
+                .on_input(|input: String| Message::Customer(MessageCustomer::NameChanged(input)))
+                .on_submit(Message::Customer(MessageCustomer::Register)),
+            custom_widget::button("สมัครสมาชิก", 25)
+                .on_press(Message::Customer(MessageCustomer::Register)),
+        ]
+        .spacing(10)
+        .align_y(Center);
+
+        let mut content = column![phone, register].spacing(10).align_x(Center);
+        if let Some(error) = page.error {
+            content = content.push(custom_widget::text(error, 25));
+        }
+        if let Some(customer) = page
+            .found
+            .as_ref()
+            .and_then(|phone| find(&self.customers, phone))
+        {
+            let title = row![
+                custom_widget::text("เลขที่ใบเสร็จ", 20).width(FillPortion(2)),
+                custom_widget::text("ยอดซื้อ", 20).width(Fill),
+                custom_widget::text("แต้มที่ได้", 20).width(Fill),
+                custom_widget::text("แต้มที่ใช้", 20).width(Fill),
+            ];
+            let list = keyed_column(
+                history(&self.transactions, &customer.phone)
+                    .into_iter()
+                    .map(|transaction| {
+                        (
+                            transaction.number,
+                            container(row![
+                                custom_widget::text(receipt_barcode(transaction.number), 25)
+                                    .width(FillPortion(2)),
+                                custom_widget::text(transaction.total, 25).width(Fill),
+                                custom_widget::text(
+                                    earned(transaction, self.setting.baht_per_point),
+                                    25
+                                )
+                                .width(Fill),
+                                custom_widget::text(redeemed(transaction), 25).width(Fill),
+                            ])
+                            .style(|_| container::bordered_box(&Theme::Light))
+                            .into(),
+                        )
+                    }),
+            );
+            content = content.push(column![
+                custom_widget::text(format!("{} แต้มสะสม {}", customer.name, customer.points), 30),
+                title,
+                scrollable(list).height(Fill),
+            ]);
+        }
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content.padding(25))
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(4)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .padding(10)
+        .into()
+    }
+}
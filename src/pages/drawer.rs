@@ -0,0 +1,139 @@
+use iced::widget::{column, container, keyed_column, row, scrollable, Column, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::drawer::{CashMovementKind, Variance, DENOMINATIONS};
+use crate::{custom_widget, user_name, Message, MessageDrawer, State};
+
+#[derive(PartialEq, Debug)]
+pub enum Drawer {
+    Open { float: String },
+    Session { amount: String, note: String },
+    Count(Vec<String>),
+    Report(Variance),
+}
+
+impl Drawer {
+    pub fn count() -> Self {
+        Drawer::Count(vec!["0".to_string(); DENOMINATIONS.len()])
+    }
+}
+
+impl State {
+    pub fn drawer_page<'a>(&'a self, drawer: &'a Drawer) -> Element<'a, Message> {
+        let content: Column<'a, Message> = match drawer {
+            Drawer::Open { float } => column![
+                row![
+                    custom_widget::text("ชื่อพนักงาน", 25),
+                    custom_widget::text(user_name(&self.user), 25),
+                ],
+                row![
+                    custom_widget::text("เงินทอนเริ่มต้น", 25),
+                    custom_widget::text_input("float", float, 25)
+                        .on_input(|input: String| Message::Drawer(MessageDrawer::FloatChanged(
+                            input
+                        )))
+                        .on_submit(Message::Drawer(MessageDrawer::Open))
+                ],
+                custom_widget::button("เปิดกะ", 25).on_press(Message::Drawer(MessageDrawer::Open)),
+            ],
+            Drawer::Session { amount, note } => {
+                let Some(session) = &self.session else {
+                    return column![].into();
+                };
+                let movements = keyed_column(session.movements.iter().enumerate().map(|x| {
+                    (
+                        x.0,
+                        row![
+                            custom_widget::text(
+                                match x.1.kind {
+                                    CashMovementKind::In => "นำเงินเข้า",
+                                    CashMovementKind::Out => "นำเงินออก",
+                                },
+                                20
+                            ),
+                            custom_widget::text(format!("{}", x.1.amount), 20),
+                            custom_widget::text(&x.1.note, 20).width(FillPortion(2)),
+                        ]
+                        .into(),
+                    )
+                }));
+                column![
+                    custom_widget::text(format!("พนักงาน {}", session.cashier), 25),
+                    custom_widget::text(format!("เงินทอนเริ่มต้น {}", session.float), 25),
+                    row![
+                        custom_widget::text("จำนวนเงิน", 25),
+                        custom_widget::text_input("amount", amount, 25).on_input(
+                            |input: String| Message::Drawer(MessageDrawer::AmountChanged(input))
+                        )
+                    ],
+                    row![
+                        custom_widget::text("หมายเหตุ", 25),
+                        custom_widget::text_input("note", note, 25).on_input(|input: String| {
+                            Message::Drawer(MessageDrawer::NoteChanged(input))
+                        })
+                    ],
+                    row![
+                        custom_widget::button("นำเงินเข้า", 25)
+                            .on_press(Message::Drawer(MessageDrawer::CashIn)),
+                        custom_widget::button("นำเงินออก", 25)
+                            .on_press(Message::Drawer(MessageDrawer::CashOut)),
+                        custom_widget::button("ปิดกะ", 25)
+                            .on_press(Message::Drawer(MessageDrawer::ToCount)),
+                    ]
+                    .spacing(10),
+                    scrollable(movements).height(Fill),
+                ]
+            }
+            Drawer::Count(counts) => {
+                let inputs =
+                    counts
+                        .iter()
+                        .enumerate()
+                        .zip(DENOMINATIONS)
+                        .map(|((index, count), value)| {
+                            row![
+                                custom_widget::text(format!("{}", value), 25),
+                                custom_widget::text_input("count", count, 25).on_input(
+                                    move |input: String| {
+                                        Message::Drawer(MessageDrawer::CountChanged(index, input))
+                                    }
+                                )
+                            ]
+                            .into()
+                        });
+                column![
+                    custom_widget::text("นับเงิน", 25),
+                    Column::with_children(inputs).spacing(5),
+                    custom_widget::button("ปิดกะ", 25)
+                        .on_press(Message::Drawer(MessageDrawer::Close)),
+                ]
+            }
+            Drawer::Report(variance) => column![
+                row![
+                    custom_widget::text("ยอดที่ควรมี", 25),
+                    custom_widget::boxed_text(variance.expected, 25)
+                ],
+                row![
+                    custom_widget::text("ยอดที่นับได้", 25),
+                    custom_widget::boxed_text(variance.counted, 25)
+                ],
+                row![
+                    custom_widget::text("ส่วนต่าง", 25),
+                    custom_widget::boxed_text(variance.difference(), 25)
+                ],
+            ],
+        };
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content.spacing(25).align_x(Center).padding(50))
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(2)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .into()
+    }
+}
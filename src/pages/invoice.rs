@@ -0,0 +1,88 @@
+use iced::widget::{column, container, row, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::tax_invoice::Buyer;
+use crate::{custom_widget, Message, MessageInvoice, State};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct Invoice {
+    pub receipt: String,
+    pub buyer: Buyer,
+    pub error: Option<&'static str>,
+    /// Save request of the invoice being written.
+    pub saving: Option<u64>,
+    /// Why the invoice couldn't be written.
+    pub failed: Option<String>,
+    pub issued: Option<String>,
+}
+
+impl State {
+    pub fn invoice_page<'a>(&self, invoice: &'a Invoice) -> Element<'a, Message> {
+        let receipt = row![
+            custom_widget::text("เลขที่ใบเสร็จ", 25),
+            custom_widget::text_input("receipt", &invoice.receipt, 25)
+                .on_input(|input: String| Message::Invoice(MessageInvoice::ReceiptChanged(input)))
+        ];
+        let name = row![
+            custom_widget::text("ชื่อผู้ซื้อ", 25),
+            custom_widget::text_input("buyer_name", &invoice.buyer.name, 25)
+                .on_input(|input: String| Message::Invoice(MessageInvoice::NameChanged(input)))
+        ];
+        let address = row![
+            custom_widget::text("ที่อยู่", 25),
+            custom_widget::text_input("buyer_address", &invoice.buyer.address, 25)
+                .on_input(|input: String| Message::Invoice(MessageInvoice::AddressChanged(input)))
+        ];
+        let tax_id = row![
+            custom_widget::text("เลขประจำตัวผู้เสียภาษี", 25),
+            custom_widget::text_input("buyer_tax_id", &invoice.buyer.tax_id, 25)
+                .on_input(|input: String| Message::Invoice(MessageInvoice::TaxIdChanged(input)))
+        ];
+        let branch = row![
+            custom_widget::text("สาขา", 25),
+            custom_widget::text_input("buyer_branch", &invoice.buyer.branch, 25)
+                .on_input(|input: String| Message::Invoice(MessageInvoice::BranchChanged(input)))
+                .on_submit(Message::Invoice(MessageInvoice::Issue))
+        ];
+        let issue_button = custom_widget::button("ออกใบกำกับภาษี", 25).on_press_maybe(
+            self.issuing
+                .is_none()
+                .then_some(Message::Invoice(MessageInvoice::Issue)),
+        );
+
+        let mut content = column![receipt, name, address, tax_id, branch, issue_button]
+            .spacing(25)
+            .align_x(Center)
+            .padding(50);
+        if let Some(error) = invoice.error {
+            content = content.push(custom_widget::text(error, 25));
+        }
+        if self.issuing.is_some() {
+            content = content.push(custom_widget::text("กำลังบันทึกใบกำกับภาษี", 25));
+        }
+        if let Some(error) = &invoice.failed {
+            content = content.push(custom_widget::text(
+                format!("บันทึกใบกำกับภาษีไม่สำเร็จ: {}", error),
+                25,
+            ));
+        }
+        if let Some(number) = &invoice.issued {
+            content = content.push(custom_widget::text(
+                format!("ออกใบกำกับภาษีเลขที่ {}", number),
+                25,
+            ));
+        }
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content)
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(2)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .into()
+    }
+}
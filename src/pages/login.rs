@@ -0,0 +1,68 @@
+use iced::widget::{column, container, row, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::{custom_widget, Message, MessageLogin, State};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct Login {
+    pub name: String,
+    pub secret: String,
+    /// Login check waiting on its result.
+    pub checking: Option<u64>,
+    pub error: Option<&'static str>,
+}
+
+impl State {
+    pub fn login_page<'a>(&self, login: &'a Login) -> Element<'a, Message> {
+        // With no users yet, the first login sets up the manager account
+        let title = self.text(match self.setup {
+            true => "สร้างบัญชีผู้จัดการ",
+            false => "เข้าสู่ระบบ",
+        });
+        let name = row![
+            custom_widget::text(self.text("ชื่อผู้ใช้"), 25),
+            custom_widget::text_input("login_name", &login.name, 25)
+                .on_input(|input: String| Message::Login(MessageLogin::NameChanged(input)))
+                .on_submit(Message::Login(MessageLogin::Submit)),
+        ]
+        .spacing(10)
+        .align_y(Center);
+        let secret = row![
+            custom_widget::text(self.text("รหัสผ่าน"), 25),
+            custom_widget::text_input("login_secret", &login.secret, 25)
+                .secure(true)
+                .on_input(|input: String| Message::Login(MessageLogin::SecretChanged(input)))
+                .on_submit(Message::Login(MessageLogin::Submit)),
+        ]
+        .spacing(10)
+        .align_y(Center);
+        let submit =
+            custom_widget::button(title, 25).on_press(Message::Login(MessageLogin::Submit));
+
+        let mut content = column![custom_widget::text(title, 40), name, secret, submit]
+            .spacing(25)
+            .align_x(Center)
+            .padding(50);
+        if let Some(error) = &self.users_error {
+            content = content.push(custom_widget::text(
+                format!("{} {}", self.text("อ่านไฟล์ผู้ใช้ไม่ได้:"), error),
+                25,
+            ));
+        }
+        if let Some(error) = login.error {
+            content = content.push(custom_widget::text(error, 25));
+        }
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content)
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(2)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .into()
+    }
+}
@@ -4,25 +4,112 @@ use iced::Element;
 use iced::Length::{Fill, FillPortion};
 use iced::Theme;
 
+use crate::user::Permission;
 use crate::{custom_widget, Message, MessageMain, State};
 
 impl State {
     pub fn main_page<'a>(&self) -> Element<'a, Message> {
-        let sale_button =
-            custom_widget::button("หน้าต่างการขาย", 40).on_press(Message::Main(MessageMain::Sale));
-        let stock_button =
-            custom_widget::button("คลังสินค้า", 40).on_press(Message::Main(MessageMain::Stock));
-        let setting_button =
-            custom_widget::button("ตั้งค่า", 40).on_press(Message::Main(MessageMain::Setting));
+        // The sale left when the till went down is settled before a new one
+        let recovered = self.recovered.as_ref().filter(|_| self.training.is_none());
+        let sale_button = custom_widget::button(self.text("หน้าต่างการขาย"), 40).on_press_maybe(
+            recovered
+                .is_none()
+                .then_some(Message::Main(MessageMain::Sale)),
+        );
+        let recovery: Element<'a, Message> = match recovered {
+            Some(snapshot) => row![
+                custom_widget::text(
+                    format!(
+                        "{} {} {}",
+                        self.text("พบการขายค้างอยู่"),
+                        snapshot.items.len(),
+                        self.text("รายการ")
+                    ),
+                    30
+                )
+                .width(Fill),
+                custom_widget::button(self.text("ขายต่อ"), 30)
+                    .on_press(Message::Main(MessageMain::Restore)),
+                custom_widget::button(self.text("ยกเลิกการขาย"), 30).on_press_maybe(
+                    self.allowed(Permission::Void)
+                        .then_some(Message::Main(MessageMain::Discard))
+                ),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+            None => Space::with_height(0).into(),
+        };
+        let stock_button = custom_widget::button(self.text("คลังสินค้า"), 40).on_press_maybe(
+            self.allowed(Permission::Stock)
+                .then_some(Message::Main(MessageMain::Stock)),
+        );
+        let refund_button = custom_widget::button(self.text("คืนสินค้า"), 40)
+            .on_press(Message::Main(MessageMain::Refund));
+        let invoice_button = custom_widget::button(self.text("ใบกำกับภาษี"), 40)
+            .on_press(Message::Main(MessageMain::Invoice));
+        let drawer_button = custom_widget::button(self.text("ลิ้นชักเงินสด"), 40)
+            .on_press(Message::Main(MessageMain::Drawer));
+        let report_button = custom_widget::button(self.text("สรุปยอดขาย"), 40).on_press_maybe(
+            self.allowed(Permission::Report)
+                .then_some(Message::Main(MessageMain::Report)),
+        );
+        let customer_button = custom_widget::button(self.text("สมาชิก"), 40)
+            .on_press(Message::Main(MessageMain::Customer));
+        let credit_button = custom_widget::button(self.text("บัญชีค้างจ่าย"), 40)
+            .on_press(Message::Main(MessageMain::Credit));
+        let setting_button = custom_widget::button(self.text("ตั้งค่า"), 40).on_press_maybe(
+            self.allowed(Permission::Setting)
+                .then_some(Message::Main(MessageMain::Setting)),
+        );
+        let users_button = custom_widget::button(self.text("ผู้ใช้งาน"), 40).on_press_maybe(
+            self.allowed(Permission::Users)
+                .then_some(Message::Main(MessageMain::Users)),
+        );
+        let audit_button = custom_widget::button(self.text("บันทึกการตรวจสอบ"), 40).on_press_maybe(
+            self.allowed(Permission::Audit)
+                .then_some(Message::Main(MessageMain::Audit)),
+        );
+        let training_button = custom_widget::button(
+            self.text(match self.training {
+                Some(_) => "จบโหมดฝึกอบรม",
+                None => "โหมดฝึกอบรม",
+            }),
+            40,
+        )
+        .on_press(Message::Main(MessageMain::Training));
+        let logout_button = custom_widget::button(self.text("ออกจากระบบ"), 40)
+            .on_press(Message::Main(MessageMain::Logout));
+        let user = self
+            .user
+            .as_ref()
+            .map(|user| format!("{} ({})", user.name, user.role))
+            .unwrap_or_default();
 
         container(row![
             Space::with_width(FillPortion(1)),
             container(row![
                 Space::with_width(FillPortion(1)),
-                column![sale_button, stock_button, setting_button]
-                    .spacing(20)
-                    .align_x(Center)
-                    .width(FillPortion(3)),
+                column![
+                    custom_widget::text(user, 30),
+                    recovery,
+                    sale_button,
+                    refund_button,
+                    invoice_button,
+                    stock_button,
+                    drawer_button,
+                    report_button,
+                    customer_button,
+                    credit_button,
+                    setting_button,
+                    users_button,
+                    audit_button,
+                    training_button,
+                    logout_button
+                ]
+                .spacing(20)
+                .align_x(Center)
+                .width(FillPortion(3)),
                 Space::with_width(FillPortion(1)),
             ])
             .style(|_| container::bordered_box(&Theme::Light))
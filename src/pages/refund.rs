@@ -0,0 +1,102 @@
+use iced::widget::{column, container, keyed_column, pick_list, row, scrollable, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::transaction::RefundReason;
+use crate::{custom_widget, Item, Message, MessageRefund, State};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct Refund {
+    pub receipt_input: String,
+    pub receipt: Option<u32>,
+    pub lines: Vec<Line>,
+    pub reason: Option<RefundReason>,
+    pub pin: String,
+    /// PIN check waiting on its result.
+    pub checking: Option<u64>,
+    pub error: Option<&'static str>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Line {
+    pub item: Item,
+    pub returnable: u32,
+    pub quantity: String,
+}
+
+impl State {
+    pub fn refund_page<'a>(&self, refund: &'a Refund) -> Element<'a, Message> {
+        let receipt = row![
+            custom_widget::text("เลขที่ใบเสร็จ", 25),
+            custom_widget::text_input("receipt", &refund.receipt_input, 25)
+                .on_input(|input: String| Message::Refund(MessageRefund::ReceiptChanged(input)))
+                .on_submit(Message::Refund(MessageRefund::ReceiptSubmit)),
+        ];
+
+        let title = row![
+            custom_widget::text("รหัสสินค้า", 20).width(FillPortion(2)),
+            custom_widget::text("ชื่อ", 20).width(FillPortion(2)),
+            custom_widget::text("ราคาสินค้า", 20).width(Fill),
+            custom_widget::text("คืนได้", 20).width(Fill),
+            custom_widget::text("จำนวนคืน", 20).width(Fill),
+        ];
+        let list = keyed_column(refund.lines.iter().enumerate().map(|x| {
+            (
+                x.0,
+                container(row![
+                    custom_widget::text(&x.1.item.barcode, 25).width(FillPortion(2)),
+                    custom_widget::text(&x.1.item.name, 25).width(FillPortion(2)),
+                    custom_widget::text(format!("{}", x.1.item.price), 25).width(Fill),
+                    custom_widget::text(format!("{}", x.1.returnable), 25).width(Fill),
+                    custom_widget::text_input("quantity", &x.1.quantity, 25)
+                        .on_input(move |input: String| {
+                            Message::Refund(MessageRefund::QuantityChanged(x.0, input))
+                        })
+                        .width(Fill),
+                ])
+                .style(|_| container::bordered_box(&Theme::Light))
+                .into(),
+            )
+        }));
+
+        let reason = row![
+            custom_widget::text("เหตุผล", 25),
+            pick_list(RefundReason::ALL, refund.reason, |reason| {
+                Message::Refund(MessageRefund::ReasonSelected(reason))
+            })
+            .text_size(25)
+            .width(Fill),
+        ];
+        let pin = row![
+            custom_widget::text("รหัสผู้อนุมัติ", 25),
+            custom_widget::text_input("pin", &refund.pin, 25)
+                .secure(true)
+                .on_input(|input: String| Message::Refund(MessageRefund::PinChanged(input)))
+                .on_submit(Message::Refund(MessageRefund::Refund)),
+        ];
+        let refund_button =
+            custom_widget::button("คืนเงิน", 25).on_press(Message::Refund(MessageRefund::Refund));
+
+        let mut content = column![receipt, title, scrollable(list).height(Fill)]
+            .spacing(10)
+            .align_x(Center);
+        if refund.receipt.is_some() {
+            content = content.push(row![reason, pin, refund_button].spacing(10));
+        }
+        if let Some(error) = refund.error {
+            content = content.push(custom_widget::text(error, 25));
+        }
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content.padding(25))
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(6)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .padding(10)
+        .into()
+    }
+}
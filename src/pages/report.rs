@@ -0,0 +1,93 @@
+use iced::widget::{column, container, pick_list, row, Column, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::report::{Period, Report};
+use crate::vat::baht;
+use crate::{custom_widget, Message, MessageReport, State};
+
+fn signed_baht(satang: i64) -> String {
+    match satang < 0 {
+        true => format!("-{}", baht(satang.unsigned_abs())),
+        false => baht(satang as u64),
+    }
+}
+
+impl State {
+    pub fn report_page<'a>(&self, report: &'a Report) -> Element<'a, Message> {
+        let line = |label: String, value: String| {
+            row![
+                custom_widget::text(label, 25),
+                custom_widget::boxed_text(value, 17)
+            ]
+            .spacing(25)
+            .align_y(Center)
+        };
+
+        let payments = Column::with_children(
+            report
+                .payments
+                .iter()
+                .map(|(method, amount)| line(method.to_string(), amount.to_string()).into()),
+        )
+        .spacing(10);
+
+        let discounts = Column::with_children(
+            report
+                .discounts
+                .iter()
+                .filter(|(_, amount)| *amount > 0)
+                .map(|(reason, amount)| line(reason.to_string(), amount.to_string()).into()),
+        )
+        .spacing(10);
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(
+                column![
+                    custom_widget::text("สรุปยอดขาย", 40),
+                    row![
+                        pick_list(Period::ALL, Some(report.period), |period| {
+                            Message::Report(MessageReport::PeriodSelected(period))
+                        })
+                        .text_size(25),
+                        custom_widget::button("<", 25)
+                            .on_press(Message::Report(MessageReport::Previous)),
+                        custom_widget::text(report.period.label(report.day), 25),
+                        custom_widget::button(">", 25)
+                            .on_press(Message::Report(MessageReport::Next)),
+                    ]
+                    .spacing(25)
+                    .align_y(Center),
+                    line(
+                        format!("ยอดขาย ({} รายการ)", report.sale_count),
+                        report.sales.to_string()
+                    ),
+                    line(
+                        format!("คืนสินค้า ({} รายการ)", report.refund_count),
+                        report.refunds.to_string()
+                    ),
+                    line("ยอดสุทธิ".to_string(), report.net().to_string()),
+                    custom_widget::text("แยกตามการชำระเงิน", 25),
+                    payments,
+                    custom_widget::text("ส่วนลด", 25),
+                    line("โปรโมชั่น".to_string(), report.promotions.to_string()),
+                    discounts,
+                    custom_widget::text("ภาษีมูลค่าเพิ่ม", 25),
+                    line("ยอดขายที่เสียภาษี".to_string(), signed_baht(report.taxable)),
+                    line("ภาษีขาย".to_string(), signed_baht(report.vat)),
+                    line("ยอดขายที่ได้รับยกเว้น".to_string(), signed_baht(report.exempt)),
+                ]
+                .spacing(10)
+                .align_x(Center)
+                .padding(50),
+            )
+            .style(|_| container::bordered_box(&Theme::Light))
+            .width(FillPortion(2)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .into()
+    }
+}
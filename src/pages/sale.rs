@@ -1,17 +1,173 @@
-use iced::widget::{button, column, container, keyed_column, row, scrollable, Space};
-use iced::Element;
+use iced::widget::{
+    button, checkbox, column, container, keyed_column, mouse_area, pick_list, row, scrollable,
+    Column, Row, Space,
+};
 use iced::Length::{Fill, FillPortion};
-use iced::Theme;
+use iced::{Color, Element, Theme};
 
+use crate::audit::OverrideReason;
 use crate::custom_widget;
-use crate::{Message, MessageSale, Pages, Sale, State};
+use crate::customer::find;
+use crate::discount::{DiscountReason, Target};
+use crate::transaction::PaymentMethod;
+use crate::user::Permission;
+use crate::vat::{baht, Breakdown};
+use crate::{Message, MessageSale, Sale, State};
+
+/// A manual discount being keyed in on the sale page.
+#[derive(PartialEq, Debug)]
+pub struct DiscountForm {
+    pub target: Target,
+    /// Whether `value` is a percentage rather than baht.
+    pub percent: bool,
+    pub value: String,
+    pub reason: Option<DiscountReason>,
+    pub pin: String,
+    /// PIN check waiting on its result.
+    pub checking: Option<u64>,
+    pub error: Option<&'static str>,
+}
+
+impl Default for DiscountForm {
+    fn default() -> Self {
+        DiscountForm {
+            target: Target::Bill,
+            percent: false,
+            value: "".to_string(),
+            reason: None,
+            pin: "".to_string(),
+            checking: None,
+            error: None,
+        }
+    }
+}
+
+fn discount_panel(form: &DiscountForm) -> Column<'_, Message> {
+    let title = match form.target {
+        Target::Line(index) => format!("ส่วนลดรายการที่ {}", index + 1),
+        Target::Bill => "ส่วนลดท้ายบิล".to_string(),
+    };
+    let units = Row::with_children([(false, "บาท"), (true, "%")].map(|(percent, label)| {
+        let chooser = custom_widget::button(label, 25);
+        match percent == form.percent {
+            true => chooser.into(),
+            false => chooser
+                .style(button::secondary)
+                .on_press(Message::Sale(MessageSale::DiscountPercent(percent)))
+                .into(),
+        }
+    }))
+    .spacing(10);
+    let value = custom_widget::text_input("discount", &form.value, 25)
+        .on_input(|input: String| Message::Sale(MessageSale::DiscountValueChanged(input)))
+        .on_submit(Message::Sale(MessageSale::ApplyDiscount));
+    let reason = pick_list(DiscountReason::ALL, form.reason, |reason| {
+        Message::Sale(MessageSale::DiscountReasonSelected(reason))
+    })
+    .placeholder("เหตุผล")
+    .text_size(25)
+    .width(Fill);
+    let pin = column![
+        custom_widget::text("รหัสผู้อนุมัติ", 20),
+        custom_widget::text_input("discount_pin", &form.pin, 25)
+            .secure(true)
+            .on_input(|input: String| Message::Sale(MessageSale::DiscountPinChanged(input)))
+            .on_submit(Message::Sale(MessageSale::ApplyDiscount)),
+    ];
+
+    let mut panel = column![
+        custom_widget::text(title, 25),
+        units,
+        value,
+        reason,
+        pin,
+        row![
+            custom_widget::button("ใช้ส่วนลด", 25)
+                .on_press(Message::Sale(MessageSale::ApplyDiscount)),
+            custom_widget::button("ยกเลิก", 25)
+                .style(button::secondary)
+                .on_press(Message::Sale(MessageSale::Back)),
+        ]
+        .spacing(10),
+    ]
+    .spacing(10);
+    if let Some(error) = form.error {
+        panel = panel.push(custom_widget::text(error, 20));
+    }
+    panel
+}
+
+/// A new price being keyed in for one line of the sale.
+#[derive(PartialEq, Debug, Default)]
+pub struct OverrideForm {
+    pub line: usize,
+    pub price: String,
+    pub reason: Option<OverrideReason>,
+    pub pin: String,
+    /// Also ask the stock team to check the shelf label.
+    pub price_check: bool,
+    /// PIN check waiting on its result.
+    pub checking: Option<u64>,
+    pub error: Option<&'static str>,
+}
+
+fn override_panel(form: &OverrideForm) -> Column<'_, Message> {
+    let price = column![
+        custom_widget::text("ราคาใหม่", 20),
+        custom_widget::text_input("override", &form.price, 25)
+            .on_input(|input: String| Message::Sale(MessageSale::OverridePriceChanged(input)))
+            .on_submit(Message::Sale(MessageSale::ApplyOverride)),
+    ];
+    let reason = pick_list(OverrideReason::ALL, form.reason, |reason| {
+        Message::Sale(MessageSale::OverrideReasonSelected(reason))
+    })
+    .placeholder("เหตุผล")
+    .text_size(25)
+    .width(Fill);
+    let pin = column![
+        custom_widget::text("รหัสผู้อนุมัติ", 20),
+        custom_widget::text_input("override_pin", &form.pin, 25)
+            .secure(true)
+            .on_input(|input: String| Message::Sale(MessageSale::OverridePinChanged(input)))
+            .on_submit(Message::Sale(MessageSale::ApplyOverride)),
+    ];
+    let price_check = checkbox("แจ้งตรวจสอบป้ายราคา", form.price_check)
+        .text_size(20)
+        .on_toggle(|checked| Message::Sale(MessageSale::PriceCheckToggled(checked)));
+
+    let mut panel = column![
+        custom_widget::text(format!("แก้ราคารายการที่ {}", form.line + 1), 25),
+        price,
+        reason,
+        pin,
+        price_check,
+        row![
+            custom_widget::button("แก้ราคา", 25).on_press(Message::Sale(MessageSale::ApplyOverride)),
+            custom_widget::button("ยกเลิก", 25)
+                .style(button::secondary)
+                .on_press(Message::Sale(MessageSale::Back)),
+        ]
+        .spacing(10),
+    ]
+    .spacing(10);
+    if let Some(error) = form.error {
+        panel = panel.push(custom_widget::text(error, 20));
+    }
+    panel
+}
 
 impl State {
     pub fn sale_page<'a>(&self, sale: &'a Sale) -> Element<'a, Message> {
         // Right
+        let breakdown =
+            Breakdown::of(&sale.items, self.setting.vat_rate).scaled_to(sale.total as u64 * 100);
         let total_price = column![
             custom_widget::text("รวม", 40),
-            custom_widget::boxed_text(format!("{}", &sale.total), 40)
+            custom_widget::boxed_text(format!("{}", &sale.total), 40),
+            row![
+                custom_widget::text(format!("ก่อนภาษี {}", baht(breakdown.base())), 17),
+                custom_widget::text(format!("ภาษี {}", baht(breakdown.vat)), 17),
+            ]
         ];
         let current_price = column![
             custom_widget::text("ราคา", 40),
@@ -27,10 +183,59 @@ impl State {
             custom_widget::text("เงินทอน", 40),
             custom_widget::boxed_text(sale.change, 40)
         ];
-        let pay_button = button(custom_widget::text("จ่ายเงิน", 40)).on_press(match sale.paying {
+        let remaining = column![
+            custom_widget::text("คงเหลือ", 25),
+            custom_widget::boxed_text(sale.remaining(), 25)
+        ];
+        let payments = Column::with_children(sale.payments.iter().map(|tender| {
+            custom_widget::text(format!("{} {}", tender.method, tender.amount), 20).into()
+        }));
+        let methods = Row::with_children(PaymentMethod::ALL.map(|method| {
+            let chooser = custom_widget::button(method.to_string(), 25);
+            match method == sale.method {
+                true => chooser.into(),
+                false => chooser
+                    .style(button::secondary)
+                    .on_press(Message::Sale(MessageSale::MethodSelected(method)))
+                    .into(),
+            }
+        }))
+        .spacing(10);
+        let promptpay = match &sale.qr {
+            Some(qr) => container(custom_widget::qr_code(qr, 5)).center_x(Fill),
+            None => container(custom_widget::text("ยังไม่ได้ตั้งค่าพร้อมเพย์", 25)),
+        };
+        let pay_label = match (sale.paying, sale.method) {
+            (true, PaymentMethod::Cash) | (false, _) => "จ่ายเงิน",
+            (true, _) => "ยืนยันรับเงิน",
+        };
+        let pay_button = button(custom_widget::text(pay_label, 40)).on_press(match sale.paying {
             false => Message::Sale(MessageSale::EnterPay),
             true => Message::Sale(MessageSale::Pay),
         });
+        let line_buttons = row![
+            custom_widget::button("ส่วนลดรายการ", 20).on_press_maybe(
+                sale.selected
+                    .map(|index| Message::Sale(MessageSale::ToDiscount(Target::Line(index))))
+            ),
+            custom_widget::button("ส่วนลดท้ายบิล", 20)
+                .on_press(Message::Sale(MessageSale::ToDiscount(Target::Bill))),
+            custom_widget::button("แก้ราคา", 20).on_press_maybe(
+                sale.selected
+                    .map(|index| Message::Sale(MessageSale::ToOverride(index)))
+            ),
+        ]
+        .spacing(10);
+        let void = self.allowed(Permission::Void);
+        let void_buttons = row![
+            custom_widget::button("ยกเลิกรายการ", 20).on_press_maybe(
+                (void && sale.selected.is_some()).then_some(Message::Sale(MessageSale::VoidLine))
+            ),
+            custom_widget::button("ยกเลิกบิล", 20).on_press_maybe(
+                (void && !sale.items.is_empty()).then_some(Message::Sale(MessageSale::VoidSale))
+            ),
+        ]
+        .spacing(10);
 
         // Bottom
         let amount = column![
@@ -44,6 +249,28 @@ impl State {
             custom_widget::text_input("barcode", &sale.item.barcode, 25)
                 .on_input(|input: String| Message::Sale(MessageSale::BarcodeChanged(input)))
                 .on_submit(Message::Sale(MessageSale::BarcodeSubmit)),
+            custom_widget::text(sale.item_error.unwrap_or_default(), 17),
+        ];
+        let member = sale
+            .customer_error
+            .map(str::to_string)
+            .or_else(|| {
+                sale.customer
+                    .as_ref()
+                    .and_then(|phone| find(&self.customers, phone))
+                    .map(|customer| format!("{} ({} แต้ม)", customer.name, customer.points))
+            })
+            .unwrap_or_default();
+        // The member is settled once anything is tendered
+        let open = sale.payments.is_empty();
+        let phone = column![
+            custom_widget::text("สมาชิก", 25),
+            custom_widget::text_input("member", &sale.phone, 25)
+                .on_input_maybe(
+                    open.then_some(|input: String| Message::Sale(MessageSale::PhoneChanged(input)))
+                )
+                .on_submit_maybe(open.then_some(Message::Sale(MessageSale::PhoneSubmit))),
+            custom_widget::text(member, 17),
         ];
 
         // Grid
@@ -56,15 +283,37 @@ impl State {
             custom_widget::text("รวม", 20).width(Fill),
         ];
         let list = keyed_column(sale.items.iter().enumerate().map(|x| {
+            let selected = sale.selected == Some(x.0);
             (
                 x.0,
+                mouse_area(
+                    container(row![
+                        custom_widget::text(format!("{}", x.0 + 1), 25).width(Fill),
+                        custom_widget::text(&x.1.barcode, 25).width(FillPortion(2)),
+                        custom_widget::text(&x.1.name, 25).width(FillPortion(2)),
+                        custom_widget::text(format!("{}", x.1.price), 25).width(Fill),
+                        custom_widget::text(x.1.amount.to_string(), 25).width(Fill),
+                        custom_widget::text(format!("{}", x.1.sum), 25).width(Fill),
+                    ])
+                    .style(move |_| match selected {
+                        true => container::rounded_box(&Theme::Light),
+                        false => container::bordered_box(&Theme::Light),
+                    }),
+                )
+                .on_press(Message::Sale(MessageSale::LineSelected(x.0)))
+                .into(),
+            )
+        }))
+        .extend(sale.discounts.iter().enumerate().map(|(index, discount)| {
+            (
+                sale.items.len() + index,
                 container(row![
-                    custom_widget::text(format!("{}", x.0 + 1), 25).width(Fill),
-                    custom_widget::text(&x.1.barcode, 25).width(FillPortion(2)),
-                    custom_widget::text(&x.1.name, 25).width(FillPortion(2)),
-                    custom_widget::text(format!("{}", x.1.price), 25).width(Fill),
-                    custom_widget::text(x.1.amount.to_string(), 25).width(Fill),
-                    custom_widget::text(format!("{}", x.1.sum), 25).width(Fill),
+                    Space::with_width(Fill),
+                    Space::with_width(FillPortion(2)),
+                    custom_widget::text(&discount.name, 25).width(FillPortion(2)),
+                    Space::with_width(Fill),
+                    Space::with_width(Fill),
+                    custom_widget::text(format!("-{}", discount.amount), 25).width(Fill),
                 ])
                 .style(|_| container::bordered_box(&Theme::Light))
                 .into(),
@@ -72,7 +321,23 @@ impl State {
         }));
 
         // Sale view starts here
-        container(column![
+        let mut view = Column::new();
+        if self.in_training() {
+            let banner = match self.training {
+                Some(_) => "โหมดฝึกอบรม — TRAINING MODE (ข้อมูลจำลอง)".to_string(),
+                None => format!("โหมดฝึกอบรม — TRAINING MODE ({})", self.setting.profile),
+            };
+            view = view.push(
+                container(custom_widget::text(banner, 30).color(Color::WHITE))
+                    .style(|_| container::Style {
+                        background: Some(Color::from_rgb(0.8, 0.1, 0.1).into()),
+                        ..Default::default()
+                    })
+                    .center_x(Fill)
+                    .padding(5),
+            );
+        }
+        container(view.push(column![
             row![
                 // Grid
                 column![title, scrollable(list)]
@@ -82,20 +347,55 @@ impl State {
                 column![
                     total_price,
                     current_price,
-                    if !sale.paying {
+                    if let Some(form) = &sale.discount {
+                        discount_panel(form).height(Fill)
+                    } else if let Some(form) = &sale.price_override {
+                        override_panel(form).height(Fill)
+                    } else if !sale.paying {
                         column![
-                            Space::with_height(FillPortion(3)),
+                            Space::with_height(FillPortion(2)),
+                            line_buttons,
+                            void_buttons,
                             container(pay_button).height(Fill)
                         ]
                         .height(Fill)
                     } else {
-                        column![
-                            Space::with_height(Fill),
-                            received.height(Fill),
-                            change.height(Fill),
-                            container(pay_button).height(Fill)
-                        ]
-                        .height(Fill)
+                        column![methods, remaining, payments, received]
+                            .push(match sale.method {
+                                PaymentMethod::Cash => change.height(Fill),
+                                PaymentMethod::PromptPay => column![promptpay].height(Fill),
+                                PaymentMethod::Card => column![].height(Fill),
+                                PaymentMethod::Credit => column![
+                                    pick_list(
+                                        // Locked once credit is tendered
+                                        match sale.charged() {
+                                            0 => self
+                                                .accounts
+                                                .iter()
+                                                .map(|account| account.name.clone())
+                                                .collect::<Vec<_>>(),
+                                            _ => sale.account.iter().cloned().collect(),
+                                        },
+                                        sale.account.clone(),
+                                        |name| Message::Sale(MessageSale::AccountSelected(name))
+                                    )
+                                    .placeholder("เลือกบัญชี")
+                                    .text_size(25)
+                                    .width(Fill),
+                                    custom_widget::text(
+                                        format!("วงเงินคงเหลือ {}", sale.credit(&self.accounts)),
+                                        25
+                                    )
+                                ]
+                                .height(Fill),
+                                PaymentMethod::Points => column![custom_widget::text(
+                                    format!("แต้มคงเหลือ {}", sale.points(&self.customers)),
+                                    25
+                                )]
+                                .height(Fill),
+                            })
+                            .push(container(pay_button).height(Fill))
+                            .height(Fill)
                     }
                 ]
                 .width(Fill)
@@ -107,11 +407,12 @@ impl State {
                 Space::with_width(Fill),
                 amount,
                 barcode,
+                phone,
                 Space::with_width(Fill)
             ]
             .spacing(10)
             .padding(10)
-        ])
+        ]))
         .center(Fill)
         .into()
     }
@@ -1,43 +1,631 @@
+use std::fmt;
+
 use iced::{
-    widget::{button, column, container, row, Space},
+    widget::{button, checkbox, column, container, pick_list, row, Column, Row, Space},
     Alignment::Center,
     Element,
     Length::{Fill, FillPortion},
     Theme,
 };
 
+use crate::api::{parse_url, Failure, Health};
+use crate::promptpay::payload;
+use crate::receipt::PrinterTarget;
+use crate::setting::{Appearance, Language, Profile, Setting};
+use crate::tax_invoice::valid_tax_id;
 use crate::State;
 use crate::{custom_widget, Message, MessageSetting};
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Section {
+    #[default]
+    Shop,
+    Receipt,
+    Printer,
+    Scanner,
+    Tax,
+    Display,
+    Connection,
+}
+
+impl Section {
+    pub const ALL: [Section; 7] = [
+        Section::Shop,
+        Section::Receipt,
+        Section::Printer,
+        Section::Scanner,
+        Section::Tax,
+        Section::Display,
+        Section::Connection,
+    ];
+}
+
+impl Section {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Section::Shop => "ร้านค้า",
+            Section::Receipt => "ใบเสร็จ",
+            Section::Printer => "เครื่องพิมพ์",
+            Section::Scanner => "เครื่องสแกน",
+            Section::Tax => "ภาษีและส่วนลด",
+            Section::Display => "การแสดงผล",
+            Section::Connection => "การเชื่อมต่อ",
+        }
+    }
+}
+
+/// Kind of [`PrinterTarget`], chosen apart from its address.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Printer {
+    #[default]
+    None,
+    Device,
+    Tcp,
+    File,
+}
+
+impl Printer {
+    pub const ALL: [Printer; 4] = [Printer::None, Printer::Device, Printer::Tcp, Printer::File];
+
+    fn of(target: &PrinterTarget) -> (Self, String) {
+        match target {
+            PrinterTarget::None => (Printer::None, "".to_string()),
+            PrinterTarget::Device(path) => (Printer::Device, path.clone()),
+            PrinterTarget::Tcp(address) => (Printer::Tcp, address.clone()),
+            PrinterTarget::File(path) => (Printer::File, path.clone()),
+        }
+    }
+
+    fn target(&self, address: String) -> PrinterTarget {
+        match self {
+            Printer::None => PrinterTarget::None,
+            Printer::Device => PrinterTarget::Device(address),
+            Printer::Tcp => PrinterTarget::Tcp(address),
+            Printer::File => PrinterTarget::File(address),
+        }
+    }
+}
+
+impl fmt::Display for Printer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Printer::None => "ไม่พิมพ์",
+            Printer::Device => "พอร์ต USB",
+            Printer::Tcp => "เครือข่าย",
+            Printer::File => "ไฟล์",
+        })
+    }
+}
+
+/// Text inputs of the settings page.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Field {
+    ShopName,
+    ShopAddress,
+    TaxId,
+    Branch,
+    PromptPay,
+    ReceiptHeader,
+    ReceiptFooter,
+    PrinterAddress,
+    CodePage,
+    Columns,
+    InvoiceFont,
+    InvoiceDir,
+    ScannerPrefix,
+    ScannerSuffix,
+    VatRate,
+    DiscountLimit,
+    BahtPerPoint,
+    FontScale,
+    ProfileName,
+    DatabaseUrl,
+}
+
+impl Field {
+    pub const ALL: [Field; 20] = [
+        Field::ShopName,
+        Field::ShopAddress,
+        Field::TaxId,
+        Field::Branch,
+        Field::PromptPay,
+        Field::ReceiptHeader,
+        Field::ReceiptFooter,
+        Field::PrinterAddress,
+        Field::CodePage,
+        Field::Columns,
+        Field::InvoiceFont,
+        Field::InvoiceDir,
+        Field::ScannerPrefix,
+        Field::ScannerSuffix,
+        Field::VatRate,
+        Field::DiscountLimit,
+        Field::BahtPerPoint,
+        Field::FontScale,
+        Field::ProfileName,
+        Field::DatabaseUrl,
+    ];
+
+    pub fn section(&self) -> Section {
+        match self {
+            Field::ShopName | Field::ShopAddress | Field::TaxId | Field::Branch => Section::Shop,
+            Field::PromptPay => Section::Shop,
+            Field::ReceiptHeader | Field::ReceiptFooter => Section::Receipt,
+            Field::PrinterAddress | Field::CodePage | Field::Columns => Section::Printer,
+            Field::InvoiceFont | Field::InvoiceDir => Section::Printer,
+            Field::ScannerPrefix | Field::ScannerSuffix => Section::Scanner,
+            Field::VatRate | Field::DiscountLimit | Field::BahtPerPoint => Section::Tax,
+            Field::FontScale => Section::Display,
+            Field::ProfileName | Field::DatabaseUrl => Section::Connection,
+        }
+    }
+
+    /// Label, which also serves as the input's id.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Field::ShopName => "ชื่อร้าน",
+            Field::ShopAddress => "ที่อยู่",
+            Field::TaxId => "เลขประจำตัวผู้เสียภาษี",
+            Field::Branch => "รหัสสาขา",
+            Field::PromptPay => "พร้อมเพย์",
+            Field::ReceiptHeader => "หัวใบเสร็จ",
+            Field::ReceiptFooter => "ท้ายใบเสร็จ",
+            Field::PrinterAddress => "ที่อยู่เครื่องพิมพ์",
+            Field::CodePage => "โค้ดเพจภาษาไทย",
+            Field::Columns => "ตัวอักษรต่อบรรทัด",
+            Field::InvoiceFont => "ฟอนต์ใบกำกับภาษี",
+            Field::InvoiceDir => "โฟลเดอร์ใบกำกับภาษี",
+            Field::ScannerPrefix => "อักษรนำหน้ารหัส",
+            Field::ScannerSuffix => "อักษรต่อท้ายรหัส",
+            Field::VatRate => "อัตราภาษีมูลค่าเพิ่ม (%)",
+            Field::DiscountLimit => "ส่วนลดสูงสุดที่ไม่ต้องอนุมัติ",
+            Field::BahtPerPoint => "ยอดซื้อต่อ 1 แต้ม",
+            Field::FontScale => "ขนาดตัวอักษร (%)",
+            Field::ProfileName => "ชื่อโปรไฟล์",
+            Field::DatabaseUrl => "ฐานข้อมูล",
+        }
+    }
+}
+
+/// Connection test of a backend URL, kept apart from the saved one until the
+/// user chooses to use it.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Probe {
+    Testing(String),
+    Done(String, Result<Health, Failure>),
+}
+
+/// Edits to the settings, applied only on save.
+#[derive(PartialEq, Debug, Default)]
+pub struct SettingPage {
+    pub section: Section,
+    /// Text of every [`Field`], in the order of [`Field::ALL`].
+    pub inputs: [String; Field::ALL.len()],
+    pub printer: Printer,
+    pub language: Language,
+    pub appearance: Appearance,
+    /// Backend profiles, the one at `profile` being edited through
+    /// [`Field::ProfileName`] and [`Field::DatabaseUrl`] and used on save.
+    pub profiles: Vec<Profile>,
+    pub profile: usize,
+    pub probe: Option<Probe>,
+    /// Whether a full product resync is under way.
+    pub resyncing: bool,
+    pub error: Option<&'static str>,
+}
+
+impl SettingPage {
+    pub fn new(setting: &Setting) -> Self {
+        let (printer, address) = Printer::of(&setting.printer);
+        let inputs = Field::ALL.map(|field| match field {
+            Field::ShopName => setting.shop_name.clone(),
+            Field::ShopAddress => setting.shop_address.clone(),
+            Field::TaxId => setting.tax_id.clone(),
+            Field::Branch => setting.branch.clone(),
+            Field::PromptPay => setting.promptpay_id.clone(),
+            Field::ReceiptHeader => setting.receipt_header.clone(),
+            Field::ReceiptFooter => setting.receipt_footer.clone(),
+            Field::PrinterAddress => address.clone(),
+            Field::CodePage => setting.printer_code_page.to_string(),
+            Field::Columns => setting.printer_columns.to_string(),
+            Field::InvoiceFont => setting.invoice_font.clone(),
+            Field::InvoiceDir => setting.invoice_dir.clone(),
+            Field::ScannerPrefix => setting.scanner_prefix.clone(),
+            Field::ScannerSuffix => setting.scanner_suffix.clone(),
+            Field::VatRate => setting.vat_rate.to_string(),
+            Field::DiscountLimit => setting.discount_limit.to_string(),
+            Field::BahtPerPoint => setting.baht_per_point.to_string(),
+            Field::FontScale => setting.font_scale.to_string(),
+            Field::ProfileName | Field::DatabaseUrl => "".to_string(),
+        });
+        let mut profiles = setting.profiles.clone();
+        if profiles.is_empty() {
+            profiles.push(Profile::live("".to_string()));
+        }
+        let profile = profiles
+            .iter()
+            .position(|profile| profile.name == setting.profile)
+            .unwrap_or_default();
+        let mut page = SettingPage {
+            section: Section::default(),
+            inputs,
+            printer,
+            language: setting.language,
+            appearance: setting.appearance,
+            profiles,
+            profile,
+            probe: None,
+            resyncing: false,
+            error: None,
+        };
+        page.load();
+        page
+    }
+
+    pub fn input(&self, field: Field) -> &str {
+        &self.inputs[field as usize]
+    }
+
+    pub fn input_mut(&mut self, field: Field) -> &mut String {
+        &mut self.inputs[field as usize]
+    }
+
+    fn load(&mut self) {
+        let Profile { name, url, .. } = self.profiles[self.profile].clone();
+        *self.input_mut(Field::ProfileName) = name;
+        *self.input_mut(Field::DatabaseUrl) = url;
+    }
+
+    /// Profiles with the one being edited checked and updated from the
+    /// inputs, and the name of that one.
+    pub fn profiles(&self) -> Result<(Vec<Profile>, String), (Field, &'static str)> {
+        let name = self.input(Field::ProfileName).trim().to_string();
+        if name.is_empty() {
+            return Err((Field::ProfileName, "กรุณาใส่ชื่อโปรไฟล์"));
+        }
+        let taken = self
+            .profiles
+            .iter()
+            .enumerate()
+            .any(|(index, profile)| index != self.profile && profile.name == name);
+        if taken {
+            return Err((Field::ProfileName, "ชื่อโปรไฟล์ซ้ำ"));
+        }
+        let mut url = self.input(Field::DatabaseUrl).trim().to_string();
+        if !url.is_empty() {
+            url = parse_url(&url).map_err(|error| (Field::DatabaseUrl, error))?;
+        }
+
+        let mut profiles = self.profiles.clone();
+        profiles[self.profile].name = name.clone();
+        profiles[self.profile].url = url;
+        Ok((profiles, name))
+    }
+
+    /// Switches to editing another profile once the current one is valid.
+    pub fn select(&mut self, index: usize) -> Result<(), (Field, &'static str)> {
+        if index >= self.profiles.len() {
+            return Ok(());
+        }
+        (self.profiles, _) = self.profiles()?;
+        self.profile = index;
+        self.load();
+        self.probe = None;
+        Ok(())
+    }
+
+    /// Adds a non-production profile and switches to it.
+    pub fn add_profile(&mut self) -> Result<(), (Field, &'static str)> {
+        (self.profiles, _) = self.profiles()?;
+        let name = (self.profiles.len() + 1..)
+            .map(|number| format!("โปรไฟล์ {}", number))
+            .find(|name| self.profiles.iter().all(|profile| &profile.name != name))
+            .unwrap_or_default();
+        self.profiles.push(Profile {
+            name,
+            url: "".to_string(),
+            production: false,
+        });
+        self.select(self.profiles.len() - 1)
+    }
+
+    /// Removes the profile being edited, keeping at least one.
+    pub fn remove_profile(&mut self) {
+        if self.profiles.len() > 1 {
+            self.profiles.remove(self.profile);
+            self.profile = self.profile.min(self.profiles.len() - 1);
+            self.load();
+            self.probe = None;
+        }
+    }
+
+    /// Puts the fields of `section` back to their defaults.
+    pub fn reset(&mut self, section: Section) {
+        let default = SettingPage::new(&Setting::default());
+        for field in Field::ALL {
+            // Profiles are added and removed on their own
+            if field.section() == section && field != Field::ProfileName {
+                *self.input_mut(field) = default.input(field).to_string();
+            }
+        }
+        match section {
+            Section::Printer => self.printer = default.printer,
+            Section::Display => {
+                self.language = default.language;
+                self.appearance = default.appearance;
+            }
+            _ => {}
+        }
+        self.error = None;
+    }
+
+    /// `setting` with the edits applied, or the first field that isn't valid.
+    pub fn apply(&self, setting: &Setting) -> Result<Setting, (Field, &'static str)> {
+        let text = |field: Field| self.input(field).trim().to_string();
+        let number = |field: Field, range: std::ops::RangeInclusive<u64>, error| {
+            text(field)
+                .parse::<u64>()
+                .ok()
+                .filter(|value| range.contains(value))
+                .ok_or((field, error))
+        };
+
+        let tax_id = text(Field::TaxId);
+        if !tax_id.is_empty() && !valid_tax_id(&tax_id) {
+            return Err((Field::TaxId, "เลขประจำตัวผู้เสียภาษีไม่ถูกต้อง"));
+        }
+        let branch = text(Field::Branch);
+        if branch.len() != 5 || !branch.chars().all(|c| c.is_ascii_digit()) {
+            return Err((Field::Branch, "รหัสสาขาต้องเป็นตัวเลข 5 หลัก"));
+        }
+        let promptpay_id = text(Field::PromptPay);
+        if !promptpay_id.is_empty() && payload(&promptpay_id, 0).is_none() {
+            return Err((Field::PromptPay, "รหัสพร้อมเพย์ไม่ถูกต้อง"));
+        }
+        let (profiles, profile) = self.profiles()?;
+        let address = text(Field::PrinterAddress);
+        if self.printer != Printer::None && address.is_empty() {
+            return Err((Field::PrinterAddress, "กรุณาใส่ที่อยู่เครื่องพิมพ์"));
+        }
+
+        Ok(Setting {
+            shop_name: text(Field::ShopName),
+            shop_address: text(Field::ShopAddress),
+            tax_id,
+            branch,
+            promptpay_id,
+            receipt_header: text(Field::ReceiptHeader),
+            receipt_footer: text(Field::ReceiptFooter),
+            printer: self.printer.target(address),
+            printer_code_page: number(Field::CodePage, 0..=255, "โค้ดเพจต้องอยู่ระหว่าง 0-255")? as u8,
+            printer_columns: number(Field::Columns, 24..=80, "ตัวอักษรต่อบรรทัดต้องอยู่ระหว่าง 24-80")?
+                as usize,
+            invoice_font: text(Field::InvoiceFont),
+            invoice_dir: text(Field::InvoiceDir),
+            // Scanners may well send spaces
+            scanner_prefix: self.input(Field::ScannerPrefix).to_string(),
+            scanner_suffix: self.input(Field::ScannerSuffix).to_string(),
+            vat_rate: number(Field::VatRate, 0..=30, "อัตราภาษีต้องอยู่ระหว่าง 0-30")?,
+            discount_limit: number(
+                Field::DiscountLimit,
+                0..=u32::MAX as u64,
+                "ส่วนลดสูงสุดไม่ถูกต้อง",
+            )? as u32,
+            baht_per_point: number(
+                Field::BahtPerPoint,
+                1..=u32::MAX as u64,
+                "ยอดซื้อต่อแต้มต้องมากกว่า 0",
+            )? as u32,
+            language: self.language,
+            appearance: self.appearance,
+            font_scale: number(Field::FontScale, 50..=200, "ขนาดตัวอักษรต้องอยู่ระหว่าง 50-200")? as u32,
+            profiles,
+            profile,
+            ..setting.clone()
+        })
+    }
+}
+
 impl State {
-    pub fn setting_page<'a>(&self) -> Element<'a, Message> {
-        let database_url = row![
-            custom_widget::text("ฐานข้อมูล", 25),
-            custom_widget::text_input("database_url", &self.setting.database_url, 25)
-                .on_input(|input: String| Message::Setting(MessageSetting::DatabaseChanged(input)))
-                .on_submit(Message::Setting(MessageSetting::ToDatabaseSubmitButton))
-        ];
+    pub fn setting_page<'a>(&self, page: &'a SettingPage) -> Element<'a, Message> {
+        let tabs = Row::with_children(Section::ALL.map(|section| {
+            let tab = custom_widget::button(self.text(section.label()), 20);
+            match section == page.section {
+                true => tab.into(),
+                false => tab
+                    .style(button::secondary)
+                    .on_press(Message::Setting(MessageSetting::SectionSelected(section)))
+                    .into(),
+            }
+        }))
+        .spacing(10);
 
-        let database_button = button(custom_widget::text("เชื่อมต่อ", 25))
-            .on_press(Message::Setting(MessageSetting::DatabaseSubmit));
+        let mut fields = Column::new().spacing(15);
+        match page.section {
+            Section::Printer => {
+                fields = fields.push(
+                    row![
+                        custom_widget::text(self.text("เครื่องพิมพ์ใบเสร็จ"), 25).width(FillPortion(2)),
+                        pick_list(Printer::ALL, Some(page.printer), |printer| {
+                            Message::Setting(MessageSetting::PrinterSelected(printer))
+                        })
+                        .text_size(25)
+                        .width(FillPortion(3)),
+                    ]
+                    .align_y(Center),
+                )
+            }
+            Section::Display => {
+                fields = fields.push(
+                    row![
+                        custom_widget::text(self.text("ภาษา"), 25).width(FillPortion(2)),
+                        pick_list(Language::ALL, Some(page.language), |language| {
+                            Message::Setting(MessageSetting::LanguageSelected(language))
+                        })
+                        .text_size(25)
+                        .width(FillPortion(3)),
+                    ]
+                    .align_y(Center),
+                );
+                fields = fields.push(
+                    row![
+                        custom_widget::text(self.text("ธีม"), 25).width(FillPortion(2)),
+                        pick_list(Appearance::ALL, Some(page.appearance), |appearance| {
+                            Message::Setting(MessageSetting::AppearanceSelected(appearance))
+                        })
+                        .text_size(25)
+                        .width(FillPortion(3)),
+                    ]
+                    .align_y(Center),
+                );
+            }
+            Section::Connection => {
+                let names: Vec<String> = page
+                    .profiles
+                    .iter()
+                    .map(|profile| profile.name.clone())
+                    .collect();
+                let selected = names.get(page.profile).cloned();
+                fields = fields.push(
+                    row![
+                        custom_widget::text(self.text("โปรไฟล์"), 25).width(FillPortion(2)),
+                        pick_list(names, selected, |name| {
+                            Message::Setting(MessageSetting::ProfileSelected(name))
+                        })
+                        .text_size(25)
+                        .width(FillPortion(2)),
+                        custom_widget::button(self.text("เพิ่ม"), 25)
+                            .style(button::secondary)
+                            .on_press(Message::Setting(MessageSetting::AddProfile)),
+                        custom_widget::button(self.text("ลบ"), 25)
+                            .style(button::secondary)
+                            .on_press_maybe(
+                                (page.profiles.len() > 1)
+                                    .then_some(Message::Setting(MessageSetting::RemoveProfile)),
+                            ),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                );
+            }
+            _ => {}
+        }
+        for field in Field::ALL {
+            if field.section() != page.section {
+                continue;
+            }
+            fields = fields.push(
+                row![
+                    custom_widget::text(self.text(field.label()), 25).width(FillPortion(2)),
+                    custom_widget::text_input(field.label(), page.input(field), 25)
+                        .on_input(move |input: String| {
+                            Message::Setting(MessageSetting::Changed(field, input))
+                        })
+                        .on_submit(Message::Setting(MessageSetting::Save))
+                        .width(FillPortion(3)),
+                ]
+                .align_y(Center),
+            );
+        }
+        if page.section == Section::Connection {
+            fields = fields.push(
+                checkbox(
+                    self.text("เซิร์ฟเวอร์จริง (ปิดไว้สำหรับโหมดฝึกอบรม)"),
+                    page.profiles[page.profile].production,
+                )
+                .text_size(25)
+                .on_toggle(|production| {
+                    Message::Setting(MessageSetting::ProductionToggled(production))
+                }),
+            );
+            let testing = matches!(page.probe, Some(Probe::Testing(_)));
+            fields = fields.push(
+                custom_widget::button(self.text("ทดสอบการเชื่อมต่อ"), 25).on_press_maybe(
+                    (!testing).then_some(Message::Setting(MessageSetting::TestConnection)),
+                ),
+            );
+            match &page.probe {
+                Some(Probe::Testing(url)) => {
+                    fields = fields.push(custom_widget::text(format!("กำลังทดสอบ {}", url), 25))
+                }
+                Some(Probe::Done(url, Ok(health))) => {
+                    let version = health.version.as_deref().unwrap_or("ไม่ทราบ");
+                    fields = fields.push(custom_widget::text(
+                        format!(
+                            "เชื่อมต่อ {} สำเร็จ ใช้เวลา {} ms เวอร์ชัน {}",
+                            url,
+                            health.latency.as_millis(),
+                            version
+                        ),
+                        25,
+                    ));
+                    if url != self.setting.database_url() {
+                        fields = fields.push(
+                            custom_widget::button(self.text("ใช้เซิร์ฟเวอร์นี้"), 25)
+                                .on_press(Message::Setting(MessageSetting::UseConnection)),
+                        );
+                    }
+                }
+                Some(Probe::Done(url, Err(failure))) => {
+                    fields = fields.push(custom_widget::text(format!("{}: {}", url, failure), 25))
+                }
+                None => {}
+            }
+            let synced = match self.catalogue.synced {
+                Some(time) => format!(
+                    "สินค้าในเครื่อง {} รายการ ซิงค์ล่าสุด {}",
+                    self.catalogue.products.len(),
+                    time.format("%d/%m/%Y %H:%M")
+                ),
+                None => "ยังไม่เคยซิงค์สินค้า".to_string(),
+            };
+            fields = fields.push(
+                row![
+                    custom_widget::text(synced, 25).width(Fill),
+                    custom_widget::button(
+                        match page.resyncing {
+                            true => "กำลังซิงค์",
+                            false => "ซิงค์สินค้าใหม่ทั้งหมด",
+                        },
+                        25
+                    )
+                    .on_press_maybe(
+                        (!page.resyncing).then_some(Message::Setting(MessageSetting::Resync))
+                    ),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            );
+        }
+
+        let buttons = row![
+            custom_widget::button(self.text("คืนค่าเริ่มต้น"), 25)
+                .style(button::secondary)
+                .on_press(Message::Setting(MessageSetting::Defaults)),
+            Space::with_width(Fill),
+            custom_widget::button(self.text("ยกเลิก"), 25)
+                .style(button::secondary)
+                .on_press(Message::Setting(MessageSetting::Cancel)),
+            custom_widget::button(self.text("บันทึก"), 25)
+                .on_press(Message::Setting(MessageSetting::Save)),
+        ]
+        .spacing(10);
+
+        let mut content = column![tabs, fields.height(Fill)].spacing(25);
+        if let Some(error) = page.error {
+            content = content.push(custom_widget::text(error, 25));
+        }
+        content = content.push(buttons);
 
         container(row![
             Space::with_width(FillPortion(1)),
-            container(
-                column![row![
-                    database_url.width(FillPortion(4)),
-                    database_button.width(FillPortion(1))
-                ]
-                .spacing(25)
-                .align_y(Center)]
-                .align_x(Center)
-                .padding(50),
-            )
-            .style(|_| container::bordered_box(&Theme::Light))
-            .width(FillPortion(2)),
+            container(content.padding(50))
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(4)),
             Space::with_width(FillPortion(1)),
         ])
         .center(Fill)
+        .padding(10)
         .into()
     }
 }
@@ -0,0 +1,77 @@
+use iced::widget::{column, container, row};
+use iced::Color;
+use iced::Element;
+use iced::Length::Fill;
+
+use crate::api::Connectivity;
+use crate::{custom_widget, Message, State};
+
+impl State {
+    /// Backend connection, problems reading settings or records and the last
+    /// error from the background, shown above every page.
+    pub fn status_bar<'a>(&self) -> Element<'a, Message> {
+        let connectivity = self.connectivity();
+        let (status, color) = match (&self.connection, self.setting.database_url().is_empty()) {
+            _ if self.training.is_some() => (
+                self.text("โหมดฝึกอบรม ใช้ข้อมูลจำลอง ไม่บันทึกลงระบบจริง")
+                    .to_string(),
+                Color::from_rgb(0.8, 0.1, 0.1),
+            ),
+            (_, true) => (
+                self.text("ยังไม่ได้ตั้งค่าฐานข้อมูล").to_string(),
+                Color::from_rgb(0.5, 0.5, 0.5),
+            ),
+            (None, false) => (
+                self.text("กำลังตรวจสอบการเชื่อมต่อ").to_string(),
+                Color::from_rgb(0.5, 0.5, 0.5),
+            ),
+            (Some(Ok(health)), false) if connectivity == Some(Connectivity::Online) => (
+                format!("{} {} ms", self.text("ออนไลน์"), health.latency.as_millis()),
+                Color::from_rgb(0.1, 0.6, 0.2),
+            ),
+            (Some(Ok(health)), false) => (
+                format!("{} {} ms", self.text("สัญญาณช้า"), health.latency.as_millis()),
+                Color::from_rgb(0.9, 0.5, 0.0),
+            ),
+            (Some(Err(failure)), false) if connectivity == Some(Connectivity::Degraded) => (
+                format!("{} {}", self.text("การเชื่อมต่อไม่เสถียร:"), failure),
+                Color::from_rgb(0.9, 0.5, 0.0),
+            ),
+            (Some(Err(failure)), false) => (
+                format!("{} {}", self.text("ออฟไลน์:"), failure),
+                Color::from_rgb(0.8, 0.1, 0.1),
+            ),
+        };
+        let mut bar = column![row![
+            custom_widget::text(format!("● {}", status), 16).color(color)
+        ]];
+        if let Some(error) = &self.setting_error {
+            bar = bar.push(
+                custom_widget::text(
+                    format!("{} {}", self.text("อ่านไฟล์ตั้งค่าไม่ได้ ใช้ค่าเริ่มต้นแทน:"), error),
+                    16,
+                )
+                .color(Color::from_rgb(0.8, 0.1, 0.1)),
+            );
+        }
+        if let Some(error) = &self.records_error {
+            bar = bar.push(
+                custom_widget::text(
+                    format!("{} {}", self.text("อ่านประวัติการขายไม่ครบ:"), error),
+                    16,
+                )
+                .color(Color::from_rgb(0.8, 0.1, 0.1)),
+            );
+        }
+        if let Some(error) = &self.error {
+            bar = bar.push(
+                row![
+                    custom_widget::text(error.clone(), 16).color(Color::from_rgb(0.8, 0.1, 0.1)),
+                    custom_widget::button(self.text("ปิด"), 16).on_press(Message::DismissError),
+                ]
+                .spacing(10),
+            );
+        }
+        container(bar).padding([2, 10]).width(Fill).into()
+    }
+}
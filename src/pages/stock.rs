@@ -1,28 +1,30 @@
-use iced::widget::{column, container, row, Row, Space};
+use iced::widget::{column, container, pick_list, row, Row, Space};
 use iced::Alignment::Center;
 use iced::Length::{Fill, FillPortion};
 use iced::Theme;
 use iced::{Element, Pixels};
 
-use crate::{custom_widget, MessageStock, Pages};
+use crate::vat::Vat;
+use crate::{custom_widget, MessageStock};
 use crate::{Message, State};
 
 #[derive(PartialEq, Debug)]
 pub enum Stock {
-    Stock(Vec<Item>),
+    Menu,
     AddProduct(Item),
-    Edit,
-    Restock,
 }
 
+/// A product being added to the backend's catalogue.
 #[derive(PartialEq, Debug, Default)]
 pub struct Item {
     pub barcode: String,
-    name: String,
-    cost: f32,
-    price: u32,
-    amount: u32,
-    exp: u32,
+    pub name: String,
+    pub price: String,
+    pub vat: Vat,
+    /// Whether the product is on its way to the backend.
+    pub saving: bool,
+    /// What came of the last save.
+    pub result: Option<Result<String, String>>,
 }
 
 impl State {
@@ -31,14 +33,12 @@ impl State {
             custom_widget::button("คลังสินค้า", 25).on_press(Message::Stock(MessageStock::Back)),
             custom_widget::button("เพิ่มสินค้า", 25)
                 .on_press(Message::Stock(MessageStock::ToAddProduct)),
-            custom_widget::button("แก้ไขสินค้า", 25).on_press(Message::Stock(MessageStock::Back)),
-            custom_widget::button("นำเข้าสินค้า", 25).on_press(Message::Stock(MessageStock::Back)),
         ]
         .spacing(Pixels(5.0))
         .padding(5);
 
         let content: Row<'a, Message> = match stock {
-            Stock::Stock(_) => row![],
+            Stock::Menu => row![],
             Stock::AddProduct(item) => {
                 let barcode = row![
                     custom_widget::text("รหัสสินค้า", 25),
@@ -50,28 +50,53 @@ impl State {
                 let name = row![
                     custom_widget::text("ชื่อสินค้า", 25),
                     custom_widget::text_input("name", &item.name, 25)
+                        .on_input(|input: String| Message::Stock(MessageStock::NameChanged(input)))
                 ];
 
-                let cost = row![
-                    custom_widget::text("ราคาทุน", 25),
-                    custom_widget::text_input("cost", &item.cost.to_string(), 25)
+                let price = row![
+                    custom_widget::text("ราคาขาย", 25),
+                    custom_widget::text_input("price", &item.price, 25).on_input(
+                        |input: String| Message::Stock(MessageStock::PriceChanged(input))
+                    )
+                ];
+
+                let vat = row![
+                    custom_widget::text("ภาษีมูลค่าเพิ่ม", 25),
+                    pick_list(Vat::ALL, Some(item.vat), |vat| {
+                        Message::Stock(MessageStock::VatSelected(vat))
+                    })
+                    .text_size(25)
+                    .width(Fill)
                 ];
 
+                let result = match &item.result {
+                    Some(Ok(barcode)) => format!("บันทึกสินค้า {} แล้ว", barcode),
+                    Some(Err(error)) => error.clone(),
+                    None => "".to_string(),
+                };
+
                 row![
                     Space::with_width(FillPortion(1)),
                     container(
-                        column![barcode, name, cost]
-                            .spacing(25)
-                            .align_x(Center)
-                            .padding(50),
+                        column![
+                            barcode,
+                            name,
+                            price,
+                            vat,
+                            custom_widget::text(result, 20),
+                            custom_widget::button("บันทึก", 25).on_press_maybe(
+                                (!item.saving).then_some(Message::Stock(MessageStock::Save))
+                            ),
+                        ]
+                        .spacing(25)
+                        .align_x(Center)
+                        .padding(50),
                     )
                     .style(|_| container::bordered_box(&Theme::Light))
                     .width(FillPortion(2)),
                     Space::with_width(FillPortion(1)),
                 ]
             }
-            Stock::Edit => row![],
-            Stock::Restock => row![],
         };
 
         container(
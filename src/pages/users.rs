@@ -0,0 +1,66 @@
+use iced::widget::{column, container, keyed_column, pick_list, row, scrollable, Space};
+use iced::Alignment::Center;
+use iced::Length::{Fill, FillPortion};
+use iced::{Element, Theme};
+
+use crate::user::Role;
+use crate::{custom_widget, Message, MessageUsers, State};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct Users {
+    pub name: String,
+    pub secret: String,
+    pub role: Role,
+    pub error: Option<&'static str>,
+}
+
+impl State {
+    pub fn users_page<'a>(&'a self, users: &'a Users) -> Element<'a, Message> {
+        let form = row![
+            custom_widget::text("ชื่อผู้ใช้", 25),
+            custom_widget::text_input("user_name", &users.name, 25)
+                .on_input(|input: String| Message::Users(MessageUsers::NameChanged(input))),
+            custom_widget::text("รหัสผ่าน", 25),
+            custom_widget::text_input("user_secret", &users.secret, 25)
+                .secure(true)
+                .on_input(|input: String| Message::Users(MessageUsers::SecretChanged(input)))
+                .on_submit(Message::Users(MessageUsers::Add)),
+            pick_list(Role::ALL, Some(users.role), |role| {
+                Message::Users(MessageUsers::RoleSelected(role))
+            })
+            .text_size(25),
+            custom_widget::button("เพิ่มผู้ใช้", 25).on_press(Message::Users(MessageUsers::Add)),
+        ]
+        .spacing(10)
+        .align_y(Center);
+
+        let list = keyed_column(self.users.iter().enumerate().map(|(index, user)| {
+            (
+                index,
+                container(row![
+                    custom_widget::text(&user.name, 25).width(FillPortion(2)),
+                    custom_widget::text(user.role.to_string(), 25).width(Fill),
+                ])
+                .style(|_| container::bordered_box(&Theme::Light))
+                .into(),
+            )
+        }));
+
+        let mut content = column![form].spacing(10).align_x(Center);
+        if let Some(error) = users.error {
+            content = content.push(custom_widget::text(error, 25));
+        }
+        content = content.push(scrollable(list).height(Fill));
+
+        container(row![
+            Space::with_width(FillPortion(1)),
+            container(content.padding(25))
+                .style(|_| container::bordered_box(&Theme::Light))
+                .width(FillPortion(4)),
+            Space::with_width(FillPortion(1)),
+        ])
+        .center(Fill)
+        .padding(10)
+        .into()
+    }
+}
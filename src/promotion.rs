@@ -0,0 +1,361 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::discount::DiscountReason;
+use crate::Item;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Promotion {
+    pub name: String,
+    pub rule: Rule,
+    #[serde(default)]
+    pub starts: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub ends: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum Rule {
+    /// `quantity` units of `barcode` for `price`, e.g. 3 for 50.
+    MultiBuy {
+        barcode: String,
+        quantity: u32,
+        price: u32,
+    },
+    /// Every `buy` units of `barcode` get the next `free` units free.
+    BuyGetFree {
+        barcode: String,
+        buy: u32,
+        free: u32,
+    },
+    /// Any `quantity` units from `barcodes` for `price`.
+    MixMatch {
+        barcodes: Vec<String>,
+        quantity: u32,
+        price: u32,
+    },
+    /// `percent` off every unit in `barcodes`.
+    Percent { barcodes: Vec<String>, percent: u32 },
+    /// `amount` baht off every unit in `barcodes`.
+    Fixed { barcodes: Vec<String>, amount: u32 },
+}
+
+/// A discount line shown under the items of a sale.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Discount {
+    pub name: String,
+    pub amount: u32,
+    /// Why the cashier gave it; `None` for promotions.
+    pub reason: Option<DiscountReason>,
+}
+
+impl Promotion {
+    pub fn active(&self, now: NaiveDateTime) -> bool {
+        self.starts.is_none_or(|starts| starts <= now) && self.ends.is_none_or(|ends| now < ends)
+    }
+}
+
+pub fn get_promotions(dir: &Path) -> std::io::Result<Vec<Promotion>> {
+    let mut file = File::open(dir.join("promotions.json"))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let promotions: Vec<Promotion> = serde_json::from_str(&contents)?;
+    Ok(promotions)
+}
+
+/// Units of one line of the basket not yet claimed by a promotion.
+struct Units {
+    barcode: String,
+    /// Unit price with VAT.
+    price: u32,
+    quantity: u32,
+}
+
+fn units(items: &[Item], rate: u64) -> Vec<Units> {
+    items
+        .iter()
+        .map(|item| Units {
+            barcode: item.barcode.clone(),
+            price: item.vat.charge(item.price, 1, rate),
+            quantity: item.amount.parse::<u32>().unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Takes up to `limit` of the most expensive unclaimed units matching
+/// `barcodes` out of `units`, as (unit price, quantity) from dearest down.
+fn claim(units: &mut [Units], barcodes: &[String], limit: u32) -> Vec<(u32, u32)> {
+    units.sort_by_key(|units| std::cmp::Reverse(units.price));
+    let mut left = limit;
+    let mut claimed = Vec::new();
+    for units in units
+        .iter_mut()
+        .filter(|units| barcodes.contains(&units.barcode))
+    {
+        let quantity = units.quantity.min(left);
+        if quantity > 0 {
+            units.quantity -= quantity;
+            left -= quantity;
+            claimed.push((units.price, quantity));
+        }
+    }
+    claimed
+}
+
+/// Value of `claimed` units.
+fn value(claimed: &[(u32, u32)]) -> u64 {
+    claimed
+        .iter()
+        .map(|(price, quantity)| *price as u64 * *quantity as u64)
+        .sum()
+}
+
+/// Discounts from the promotions running at `now`, on prices with VAT at
+/// `rate`. Promotions are applied in order and each unit counts towards at
+/// most one of them. None takes more than is left of the lines it covers,
+/// and what each line has left after them comes back with the discounts.
+pub fn evaluate(
+    promotions: &[Promotion],
+    items: &[Item],
+    now: NaiveDateTime,
+    rate: u64,
+) -> (Vec<Discount>, Vec<u32>) {
+    let mut units = units(items, rate);
+    // What each line has left to discount, as VAT rounding on single units
+    // can add up to more than the line
+    let mut left: Vec<u32> = items.iter().map(|item| item.sum).collect();
+    let mut discounts = Vec::new();
+
+    for promotion in promotions.iter().filter(|promotion| promotion.active(now)) {
+        let (barcodes, amount) = match &promotion.rule {
+            Rule::MultiBuy {
+                barcode,
+                quantity,
+                price,
+            } => {
+                let barcodes = std::slice::from_ref(barcode);
+                let amount = group(&mut units, barcodes, *quantity, |group| {
+                    value(group).saturating_sub(*price as u64)
+                });
+                (barcodes, amount)
+            }
+            Rule::BuyGetFree { barcode, buy, free } => {
+                let barcodes = std::slice::from_ref(barcode);
+                let amount = group(&mut units, barcodes, buy + free, |group| {
+                    // The cheapest units of each group are the free ones
+                    let mut free = *free;
+                    let mut saving = 0;
+                    for (price, quantity) in group.iter().rev() {
+                        let quantity = (*quantity).min(free);
+                        free -= quantity;
+                        saving += *price as u64 * quantity as u64;
+                    }
+                    saving
+                });
+                (barcodes, amount)
+            }
+            Rule::MixMatch {
+                barcodes,
+                quantity,
+                price,
+            } => {
+                let amount = group(&mut units, barcodes, *quantity, |group| {
+                    value(group).saturating_sub(*price as u64)
+                });
+                (barcodes.as_slice(), amount)
+            }
+            Rule::Percent { barcodes, percent } => {
+                let claimed = claim(&mut units, barcodes, u32::MAX);
+                let amount = value(&claimed) * *percent.min(&100) as u64 / 100;
+                (barcodes.as_slice(), amount)
+            }
+            Rule::Fixed { barcodes, amount } => {
+                let claimed = claim(&mut units, barcodes, u32::MAX);
+                let amount = claimed
+                    .iter()
+                    .map(|(price, quantity)| *price.min(amount) as u64 * *quantity as u64)
+                    .sum();
+                (barcodes.as_slice(), amount)
+            }
+        };
+        let amount = take(&mut left, items, barcodes, amount);
+        if amount > 0 {
+            discounts.push(Discount {
+                name: promotion.name.clone(),
+                amount,
+                reason: None,
+            });
+        }
+    }
+    (discounts, left)
+}
+
+/// Takes up to `amount` off what `left` holds for the lines of `barcodes`,
+/// returning how much it could take.
+fn take(left: &mut [u32], items: &[Item], barcodes: &[String], amount: u64) -> u32 {
+    let mut taken = 0;
+    for (left, item) in left.iter_mut().zip(items) {
+        if barcodes.contains(&item.barcode) {
+            let part = (amount - taken as u64).min(*left as u64) as u32;
+            *left -= part;
+            taken += part;
+        }
+    }
+    taken
+}
+
+/// Claims as many complete groups of `size` units as the basket holds and
+/// sums the discount `saving` gives each group, given as (unit price,
+/// quantity) from dearest down.
+fn group(
+    units: &mut [Units],
+    barcodes: &[String],
+    size: u32,
+    saving: impl Fn(&[(u32, u32)]) -> u64,
+) -> u64 {
+    if size == 0 {
+        return 0;
+    }
+    let available = units
+        .iter()
+        .filter(|units| barcodes.contains(&units.barcode))
+        .fold(0u32, |sum, units| sum.saturating_add(units.quantity));
+    let mut total = 0;
+    let mut current = Vec::new();
+    let mut filled = 0;
+    for (price, mut quantity) in claim(units, barcodes, available - available % size) {
+        while quantity > 0 {
+            // Groups all of one price are worked out once
+            if filled == 0 && quantity >= size {
+                total += (quantity / size) as u64 * saving(&[(price, size)]);
+                quantity %= size;
+                continue;
+            }
+            let part = quantity.min(size - filled);
+            current.push((price, part));
+            filled += part;
+            quantity -= part;
+            if filled == size {
+                total += saving(&current);
+                current.clear();
+                filled = 0;
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(barcode: &str, price: u32, amount: u32) -> Item {
+        Item {
+            barcode: barcode.to_string(),
+            price,
+            amount: amount.to_string(),
+            sum: price * amount,
+            ..Item::default()
+        }
+    }
+
+    fn promotion(rule: Rule) -> Promotion {
+        Promotion {
+            name: "โปรโมชั่น".to_string(),
+            rule,
+            starts: None,
+            ends: None,
+        }
+    }
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2025-06-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn total(promotions: &[Promotion], items: &[Item]) -> u32 {
+        evaluate(promotions, items, now(), crate::vat::VAT_RATE)
+            .0
+            .iter()
+            .map(|discount| discount.amount)
+            .sum()
+    }
+
+    #[test]
+    fn multi_buy() {
+        let promotions = [promotion(Rule::MultiBuy {
+            barcode: "a".to_string(),
+            quantity: 3,
+            price: 50,
+        })];
+        assert_eq!(total(&promotions, &[item("a", 20, 2)]), 0);
+        assert_eq!(
+            total(&promotions, &[item("a", 20, 2), item("a", 20, 5)]),
+            20
+        );
+    }
+
+    #[test]
+    fn buy_get_free() {
+        let promotions = [promotion(Rule::BuyGetFree {
+            barcode: "a".to_string(),
+            buy: 2,
+            free: 1,
+        })];
+        assert_eq!(total(&promotions, &[item("a", 15, 6)]), 30);
+    }
+
+    #[test]
+    fn large_quantity() {
+        let promotions = [promotion(Rule::MultiBuy {
+            barcode: "a".to_string(),
+            quantity: 3,
+            price: 50,
+        })];
+        assert_eq!(total(&promotions, &[item("a", 20, 1_000_000)]), 3_333_330);
+    }
+
+    #[test]
+    fn mix_match() {
+        let promotions = [promotion(Rule::MixMatch {
+            barcodes: vec!["a".to_string(), "b".to_string()],
+            quantity: 2,
+            price: 30,
+        })];
+        assert_eq!(
+            total(&promotions, &[item("a", 20, 1), item("b", 25, 2)]),
+            20
+        );
+    }
+
+    #[test]
+    fn percent_and_fixed_do_not_stack() {
+        let promotions = [
+            promotion(Rule::Percent {
+                barcodes: vec!["a".to_string()],
+                percent: 10,
+            }),
+            promotion(Rule::Fixed {
+                barcodes: vec!["a".to_string(), "b".to_string()],
+                amount: 5,
+            }),
+        ];
+        assert_eq!(
+            total(&promotions, &[item("a", 100, 1), item("b", 3, 2)]),
+            16
+        );
+    }
+
+    #[test]
+    fn outside_time_window() {
+        let mut expired = promotion(Rule::Fixed {
+            barcodes: vec!["a".to_string()],
+            amount: 5,
+        });
+        expired.ends = Some(now());
+        assert_eq!(total(&[expired], &[item("a", 20, 1)]), 0);
+    }
+}
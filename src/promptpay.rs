@@ -0,0 +1,102 @@
+const APPLICATION_ID: &str = "A000000677010111";
+
+/// PromptPay payload encoded as a square matrix of QR modules.
+#[derive(Clone, PartialEq, Debug)]
+pub struct QrCode {
+    pub payload: String,
+    pub width: usize,
+    pub dark: Vec<bool>,
+}
+
+impl QrCode {
+    pub fn new(promptpay_id: &str, amount: u32) -> Option<Self> {
+        let payload = payload(promptpay_id, amount)?;
+        let code = qrcode::QrCode::new(&payload).ok()?;
+        Some(QrCode {
+            width: code.width(),
+            dark: code
+                .to_colors()
+                .into_iter()
+                .map(|color| color == qrcode::Color::Dark)
+                .collect(),
+            payload,
+        })
+    }
+}
+
+fn field(id: &str, value: &str) -> String {
+    format!("{}{:02}{}", id, value.len(), value)
+}
+
+/// EMVCo merchant-presented payload for a PromptPay ID, which may be a mobile
+/// number, a 13-digit national or tax ID, or a 15-digit e-wallet ID.
+pub fn payload(promptpay_id: &str, amount: u32) -> Option<String> {
+    let digits: String = promptpay_id
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let account = match digits.len() {
+        10 if digits.starts_with('0') => field("01", &format!("0066{}", &digits[1..])),
+        13 => field("02", &digits),
+        15 => field("03", &digits),
+        _ => return None,
+    };
+
+    let mut payload = [
+        field("00", "01"),
+        field("01", "12"),
+        field("29", &(field("00", APPLICATION_ID) + &account)),
+        field("58", "TH"),
+        field("53", "764"),
+        field("54", &format!("{}.00", amount)),
+    ]
+    .concat();
+    payload.push_str("6304");
+    payload.push_str(&format!("{:04X}", crc16(payload.as_bytes())));
+    Some(payload)
+}
+
+/// CRC-16/CCITT-FALSE, as required for the EMVCo `63` checksum field.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x1021,
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn payload_mobile() {
+        let payload = payload("081-234-5678", 50).unwrap();
+        let expected =
+            "00020101021229370016A000000677010111011300668123456785802TH5303764540550.006304";
+        assert!(payload.starts_with(expected));
+        let (body, checksum) = payload.split_at(payload.len() - 4);
+        assert_eq!(checksum, format!("{:04X}", crc16(body.as_bytes())));
+    }
+
+    #[test]
+    fn payload_tax_id() {
+        let payload = payload("1234567890123", 1).unwrap();
+        assert!(payload.contains("29370016A00000067701011102131234567890123"));
+    }
+
+    #[test]
+    fn payload_invalid_id() {
+        assert_eq!(payload("12345", 50), None);
+        assert_eq!(payload("08123abcde", 50), None);
+    }
+}
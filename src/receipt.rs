@@ -0,0 +1,281 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::setting::Setting;
+use crate::transaction::{Kind, Transaction};
+
+const ESC: u8 = 0x1B;
+const GS: u8 = 0x1D;
+/// How long a network printer has to accept a connection or take a write.
+const PRINTER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where rendered receipts are sent.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub enum PrinterTarget {
+    #[default]
+    None,
+    /// A character device such as `/dev/usb/lp0`.
+    Device(String),
+    /// A network printer, e.g. `192.168.1.50:9100`.
+    Tcp(String),
+    /// Writes the raw ESC/POS bytes to a file, for testing.
+    File(String),
+}
+
+/// Barcode printed on receipts, e.g. `R00000042`.
+pub fn receipt_barcode(number: u32) -> String {
+    format!("R{:08}", number)
+}
+
+/// Encodes text as TIS-620, the Thai code page on ESC/POS printers. Anything
+/// outside ASCII and the Thai block is printed as `?`.
+pub fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x00..=0x7F => c as u8,
+            code @ 0x0E01..=0x0E5B => (code - 0x0E00 + 0xA0) as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Printed width of `text`; Thai vowel and tone marks sit above or below the
+/// previous character and take no column of their own.
+fn width(text: &str) -> usize {
+    text.chars()
+        .filter(|c| !matches!(*c as u32, 0x0E31 | 0x0E34..=0x0E3A | 0x0E47..=0x0E4E))
+        .count()
+}
+
+struct Receipt {
+    bytes: Vec<u8>,
+    columns: usize,
+}
+
+impl Receipt {
+    fn command(&mut self, command: &[u8]) {
+        self.bytes.extend_from_slice(command);
+    }
+
+    fn line(&mut self, text: &str) {
+        self.bytes.extend(encode(text));
+        self.bytes.push(b'\n');
+    }
+
+    fn centered(&mut self, text: &str) {
+        self.command(&[ESC, b'a', 1]);
+        self.line(text);
+        self.command(&[ESC, b'a', 0]);
+    }
+
+    /// `left` and `right` on one line, with `right` flush to the edge.
+    fn spread(&mut self, left: &str, right: &str) {
+        let padding = self
+            .columns
+            .saturating_sub(width(left) + width(right))
+            .max(1);
+        self.line(&format!("{}{}{}", left, " ".repeat(padding), right));
+    }
+
+    fn rule(&mut self) {
+        self.line(&"-".repeat(self.columns));
+    }
+}
+
+pub fn render(transaction: &Transaction, setting: &Setting, training: bool) -> Vec<u8> {
+    let mut receipt = Receipt {
+        bytes: Vec::new(),
+        columns: setting.printer_columns,
+    };
+    receipt.command(&[ESC, b'@']);
+    receipt.command(&[ESC, b't', setting.printer_code_page]);
+
+    receipt.command(&[ESC, b'!', 0x30]);
+    receipt.centered(&setting.shop_name);
+    receipt.command(&[ESC, b'!', 0x00]);
+    for line in setting.shop_address.lines() {
+        receipt.centered(line);
+    }
+    if !setting.tax_id.is_empty() {
+        receipt.centered(&format!("เลขประจำตัวผู้เสียภาษี {}", setting.tax_id));
+    }
+    for line in setting.receipt_header.lines() {
+        receipt.centered(line);
+    }
+    receipt.rule();
+
+    let title = match transaction.kind {
+        Kind::Sale => "ใบเสร็จรับเงิน",
+        Kind::Refund { .. } => "ใบคืนสินค้า",
+    };
+    receipt.centered(title);
+    if training {
+        receipt.centered("*** โหมดฝึกอบรม ***");
+        receipt.centered("ไม่ใช่ใบเสร็จรับเงิน");
+    }
+    receipt.spread("เลขที่", &receipt_barcode(transaction.number));
+    if let Kind::Refund {
+        receipt: original, ..
+    } = transaction.kind
+    {
+        receipt.spread("อ้างอิงใบเสร็จ", &receipt_barcode(original));
+    }
+    receipt.spread("พนักงาน", &transaction.user);
+    receipt.rule();
+
+    for item in &transaction.items {
+        receipt.line(&item.name);
+        receipt.spread(
+            &format!("  {} x {}", item.amount, item.price),
+            &item.sum.to_string(),
+        );
+    }
+    for discount in &transaction.discounts {
+        receipt.spread(&discount.name, &format!("-{}", discount.amount));
+    }
+    receipt.rule();
+
+    receipt.command(&[ESC, b'!', 0x10]);
+    receipt.spread("รวม", &transaction.total.to_string());
+    receipt.command(&[ESC, b'!', 0x00]);
+    for tender in &transaction.tenders {
+        receipt.spread(&tender.method.to_string(), &tender.amount.to_string());
+    }
+    receipt.spread("เงินทอน", &transaction.change.to_string());
+    receipt.rule();
+
+    if training {
+        receipt.centered("*** โหมดฝึกอบรม ไม่ใช่ใบเสร็จรับเงิน ***");
+    }
+    for line in setting.receipt_footer.lines() {
+        receipt.centered(line);
+    }
+
+    // CODE39 barcode of the receipt number, scanned for refunds
+    let barcode = receipt_barcode(transaction.number);
+    receipt.command(&[ESC, b'a', 1]);
+    receipt.command(&[GS, b'H', 2]);
+    receipt.command(&[GS, b'k', 69, barcode.len() as u8]);
+    receipt.command(barcode.as_bytes());
+    receipt.command(&[ESC, b'a', 0]);
+
+    receipt.command(&[ESC, b'd', 4]);
+    receipt.command(&[GS, b'V', 1]);
+    receipt.bytes
+}
+
+/// Sends `bytes` to the printer. Blocks, so run it off the update thread.
+pub fn print(target: &PrinterTarget, bytes: &[u8]) -> io::Result<()> {
+    match target {
+        PrinterTarget::None => Ok(()),
+        PrinterTarget::Device(path) => OpenOptions::new().write(true).open(path)?.write_all(bytes),
+        PrinterTarget::Tcp(address) => connect(address)?.write_all(bytes),
+        PrinterTarget::File(path) => std::fs::write(path, bytes),
+    }
+}
+
+/// Connects to the first of `address`'s addresses that answers in time,
+/// giving up on a write the printer doesn't take in time either.
+fn connect(address: &str) -> io::Result<TcpStream> {
+    let mut last = io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("no address for {}", address),
+    );
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, PRINTER_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(PRINTER_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(error) => last = error,
+        }
+    }
+    Err(last)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transaction::{PaymentMethod, Tender};
+    use crate::Item;
+
+    fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+        bytes.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn encode_thai() {
+        assert_eq!(encode("ก ฿1"), vec![0xA1, b' ', 0xDF, b'1']);
+        assert_eq!(encode("€"), vec![b'?']);
+    }
+
+    #[test]
+    fn width_skips_marks() {
+        assert_eq!(width("น้ำ"), 2);
+        assert_eq!(width("รวม"), 3);
+    }
+
+    #[test]
+    fn print_to_file() {
+        let setting = Setting {
+            shop_name: "ซันมินิมาร์ท".to_string(),
+            ..Setting::default()
+        };
+        let transaction = Transaction {
+            number: 7,
+            kind: Kind::Sale,
+            items: vec![Item {
+                barcode: "100".to_string(),
+                name: "สินค้าราคา 100 บาท".to_string(),
+                price: 100,
+                amount: "2".to_string(),
+                sum: 200,
+                vat: crate::vat::Vat::Inclusive,
+            }],
+            discounts: Vec::new(),
+            total: 200,
+            tenders: vec![Tender {
+                method: PaymentMethod::Cash,
+                amount: 500,
+            }],
+            change: 300,
+            customer: None,
+            user: "สมชาย".to_string(),
+            time: None,
+            vat_rate: 7,
+        };
+        let path = std::env::temp_dir().join("sunminimart-receipt.bin");
+        let target = PrinterTarget::File(path.display().to_string());
+
+        print(&target, &render(&transaction, &setting, false)).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(&[ESC, b'@', ESC, b't', 21]));
+        assert!(bytes.ends_with(&[GS, b'V', 1]));
+        assert!(contains(&bytes, &encode("ซันมินิมาร์ท")));
+        assert!(contains(&bytes, b"R00000007"));
+        assert!(contains(&bytes, &encode("เงินทอน")));
+        assert!(!contains(&bytes, &encode("ไม่ใช่ใบเสร็จรับเงิน")));
+
+        let bytes = render(&transaction, &setting, true);
+        assert!(contains(&bytes, &encode("*** โหมดฝึกอบรม ***")));
+        assert!(contains(&bytes, &encode("ไม่ใช่ใบเสร็จรับเงิน")));
+    }
+
+    #[test]
+    fn print_to_network_printer() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = PrinterTarget::Tcp(listener.local_addr().unwrap().to_string());
+
+        print(&target, b"receipt").unwrap();
+
+        let mut received = Vec::new();
+        io::Read::read_to_end(&mut listener.accept().unwrap().0, &mut received).unwrap();
+        assert_eq!(received, b"receipt");
+        assert!(print(&PrinterTarget::Tcp("printer".to_string()), b"receipt").is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::discount::Manual;
+use crate::store::Write;
+use crate::transaction::Tender;
+use crate::Item;
+
+/// What of a sale in progress is journaled after every change, so a crash or
+/// power cut loses nothing rung up. Offered for restore on the next launch;
+/// promotions and the total are worked out again.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug, Default)]
+pub struct Snapshot {
+    pub items: Vec<Item>,
+    pub manual: Vec<Manual>,
+    /// Manual discount a supervisor allowed above the limit.
+    #[serde(default)]
+    pub approved: u32,
+    pub customer: Option<String>,
+    pub account: Option<String>,
+    /// Tenders already taken.
+    pub payments: Vec<Tender>,
+}
+
+/// The journal left in `dir`, if any. One that can't be read is set aside
+/// as `sale.broken.json` rather than dropped, and reported.
+pub fn get_snapshot(dir: &Path) -> Result<Option<Snapshot>, String> {
+    let path = dir.join("sale.json");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(format!("{}: {}", path.display(), error)),
+    };
+    serde_json::from_str(&contents).map(Some).map_err(|error| {
+        let broken = dir.join("sale.broken.json");
+        match std::fs::rename(&path, &broken) {
+            Ok(()) => format!(
+                "{}: {}, kept as {}",
+                path.display(),
+                error,
+                broken.display()
+            ),
+            Err(_) => format!("{}: {}", path.display(), error),
+        }
+    })
+}
+
+/// `file` queued for `sale.json`.
+pub async fn save_snapshot(snapshot: Snapshot, file: Write) -> Result<(), String> {
+    let snapshot = serde_json::to_vec(&snapshot).map_err(|error| error.to_string())?;
+    file.replace(&snapshot)
+}
+
+pub async fn remove_snapshot(file: Write) -> Result<(), String> {
+    file.remove()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn broken_journal_is_kept_aside() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(get_snapshot(&dir), Ok(None));
+
+        std::fs::write(dir.join("sale.json"), "{\"items\": [").unwrap();
+        assert!(get_snapshot(&dir).is_err());
+        assert!(!dir.join("sale.json").exists());
+        assert!(dir.join("sale.broken.json").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+
+use chrono::{Datelike, Months, NaiveDate};
+
+use crate::discount::DiscountReason;
+use crate::transaction::{Kind, PaymentMethod, Transaction};
+use crate::vat::Breakdown;
+
+/// Stretch of time a report covers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Period {
+    Day,
+    Month,
+}
+
+impl Period {
+    pub const ALL: [Period; 2] = [Period::Day, Period::Month];
+
+    /// Whether `date` falls in the period around `day`.
+    fn covers(&self, day: NaiveDate, date: NaiveDate) -> bool {
+        match self {
+            Period::Day => date == day,
+            Period::Month => (date.year(), date.month()) == (day.year(), day.month()),
+        }
+    }
+
+    /// The day one period before (`back`) or after `day`.
+    pub fn step(&self, day: NaiveDate, back: bool) -> Option<NaiveDate> {
+        match (self, back) {
+            (Period::Day, true) => day.pred_opt(),
+            (Period::Day, false) => day.succ_opt(),
+            (Period::Month, true) => day.checked_sub_months(Months::new(1)),
+            (Period::Month, false) => day.checked_add_months(Months::new(1)),
+        }
+    }
+
+    pub fn label(&self, day: NaiveDate) -> String {
+        match self {
+            Period::Day => day.format("%d/%m/%Y").to_string(),
+            Period::Month => day.format("%m/%Y").to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Period::Day => "รายวัน",
+            Period::Month => "รายเดือน",
+        })
+    }
+}
+
+/// Summary of the transactions recorded on this till.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    pub period: Period,
+    pub day: NaiveDate,
+    pub sales: u32,
+    pub sale_count: usize,
+    pub refunds: u32,
+    pub refund_count: usize,
+    /// Net amount taken per payment method, after change and refunds.
+    pub payments: Vec<(PaymentMethod, i64)>,
+    /// Net taxable value, VAT and exempt sales, in satang.
+    pub taxable: i64,
+    pub vat: i64,
+    pub exempt: i64,
+    /// Discounts given on sales: promotions, then manual discounts per reason.
+    pub promotions: u32,
+    pub discounts: Vec<(DiscountReason, u32)>,
+}
+
+impl Report {
+    /// Summary of the transactions made in the `period` around `day`, VAT
+    /// taken at the rate each was made at.
+    pub fn new(transactions: &[Transaction], day: NaiveDate, period: Period) -> Self {
+        let mut report = Report {
+            period,
+            day,
+            sales: 0,
+            sale_count: 0,
+            refunds: 0,
+            refund_count: 0,
+            payments: PaymentMethod::ALL.map(|method| (method, 0)).to_vec(),
+            taxable: 0,
+            vat: 0,
+            exempt: 0,
+            promotions: 0,
+            discounts: DiscountReason::ALL.map(|reason| (reason, 0)).to_vec(),
+        };
+
+        for transaction in transactions.iter().filter(|transaction| {
+            transaction
+                .time
+                .is_some_and(|time| period.covers(day, time.date()))
+        }) {
+            let sign = match transaction.kind {
+                Kind::Sale => {
+                    report.sales += transaction.total;
+                    report.sale_count += 1;
+                    1
+                }
+                Kind::Refund { .. } => {
+                    report.refunds += transaction.total;
+                    report.refund_count += 1;
+                    -1
+                }
+            };
+            for (method, amount) in report.payments.iter_mut() {
+                let taken = match method {
+                    PaymentMethod::Cash => transaction.cash(),
+                    _ => transaction
+                        .tenders
+                        .iter()
+                        .filter(|tender| tender.method == *method)
+                        .map(|tender| tender.amount)
+                        .sum(),
+                };
+                *amount += sign * taken as i64;
+            }
+            for discount in &transaction.discounts {
+                match discount.reason {
+                    None => report.promotions += discount.amount,
+                    Some(reason) => {
+                        for (given, amount) in report.discounts.iter_mut() {
+                            if *given == reason {
+                                *amount += discount.amount;
+                            }
+                        }
+                    }
+                }
+            }
+            let breakdown = Breakdown::of(&transaction.items, transaction.vat_rate)
+                .scaled_to(transaction.total as u64 * 100);
+            report.taxable += sign * breakdown.taxable as i64;
+            report.vat += sign * breakdown.vat as i64;
+            report.exempt += sign * breakdown.exempt as i64;
+        }
+        report
+    }
+
+    pub fn net(&self) -> i64 {
+        self.sales as i64 - self.refunds as i64
+    }
+}
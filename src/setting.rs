@@ -1,26 +1,551 @@
-use std::fs::File;
-use std::io::prelude::*;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+use crate::receipt::PrinterTarget;
+use crate::store::Write;
+use crate::vat::VAT_RATE;
+
+/// Format version written to `setting.json`, see [`Setting::migrate`].
+pub const VERSION: u32 = 3;
+
+/// Environment variable with the path of the settings file.
+pub const CONFIG_ENV: &str = "SUNMINIMART_CONFIG";
+
+/// Where the settings live: the `--config` flag, then [`CONFIG_ENV`], then
+/// `sunminimart/setting.json` in the OS config directory.
+pub fn locate(mut args: impl Iterator<Item = String>, env: Option<String>) -> PathBuf {
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            if let Some(path) = args.next() {
+                return PathBuf::from(path);
+            }
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            return PathBuf::from(path);
+        }
+    }
+    if let Some(path) = env.filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("sunminimart"))
+        .unwrap_or_default()
+        .join("setting.json")
+}
+
+/// [`locate`] for this process.
+pub fn config_path() -> PathBuf {
+    locate(std::env::args().skip(1), std::env::var(CONFIG_ENV).ok())
+}
+
+/// Directory the records are kept in, beside the settings file.
+pub fn data_dir(config: &Path) -> PathBuf {
+    config.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Records older versions kept in the working directory. A sale left
+/// unfinished by one isn't carried over.
+const LEGACY: [&str; 6] = [
+    "users.json",
+    "customers.json",
+    "accounts.json",
+    "promotions.json",
+    "audit.jsonl",
+    "products.json",
+];
+
+/// Moves what older versions left in `legacy`, the directory they ran in,
+/// to where it is kept now: `setting.json` to `config` and the records to
+/// [`data_dir`]. Once moved there is nothing left to adopt on the next
+/// launch. Files already there are never replaced, and whatever would have
+/// replaced them stays where it is.
+pub fn adopt_legacy(legacy: &Path, config: &Path) -> Result<(), String> {
+    let data = data_dir(config);
+    let files = std::iter::once(("setting.json", config.to_path_buf()))
+        .chain(LEGACY.iter().map(|name| (*name, data.join(name))));
+    for (name, target) in files {
+        let source = legacy.join(name);
+        if target.exists() || !source.exists() {
+            continue;
+        }
+        if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .map_err(|error| format!("{}: {}", dir.display(), error))?;
+        }
+        // Across file systems a rename fails and the file is copied instead
+        std::fs::rename(&source, &target)
+            .or_else(|_| {
+                std::fs::copy(&source, &target).and_then(|_| std::fs::remove_file(&source))
+            })
+            .map_err(|error| format!("{}: {}", source.display(), error))?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    Thai,
+    English,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::Thai, Language::English];
+
+    /// `thai` in this language, left in Thai where it has no translation.
+    pub fn text(self, thai: &'static str) -> &'static str {
+        match self {
+            Language::Thai => thai,
+            Language::English => ENGLISH
+                .iter()
+                .find(|(text, _)| *text == thai)
+                .map_or(thai, |(_, english)| english),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::Thai => "ไทย",
+            Language::English => "English",
+        })
+    }
+}
+
+/// English for the Thai of the main, login and settings pages and the status
+/// bar.
+const ENGLISH: [(&str, &str); 72] = [
+    ("หน้าต่างการขาย", "Sale"),
+    ("พบการขายค้างอยู่", "Unfinished sale,"),
+    ("รายการ", "items"),
+    ("ขายต่อ", "Resume"),
+    ("ยกเลิกการขาย", "Void sale"),
+    ("คลังสินค้า", "Stock"),
+    ("คืนสินค้า", "Refund"),
+    ("ใบกำกับภาษี", "Tax invoice"),
+    ("ลิ้นชักเงินสด", "Cash drawer"),
+    ("สรุปยอดขาย", "Sales report"),
+    ("สมาชิก", "Members"),
+    ("บัญชีค้างจ่าย", "Credit accounts"),
+    ("ตั้งค่า", "Settings"),
+    ("ผู้ใช้งาน", "Users"),
+    ("บันทึกการตรวจสอบ", "Audit log"),
+    ("โหมดฝึกอบรม", "Training mode"),
+    ("จบโหมดฝึกอบรม", "End training"),
+    ("ออกจากระบบ", "Log out"),
+    ("สร้างบัญชีผู้จัดการ", "Create manager account"),
+    ("เข้าสู่ระบบ", "Log in"),
+    ("ชื่อผู้ใช้", "User name"),
+    ("รหัสผ่าน", "Password"),
+    ("อ่านไฟล์ผู้ใช้ไม่ได้:", "Cannot read users file:"),
+    (
+        "โหมดฝึกอบรม ใช้ข้อมูลจำลอง ไม่บันทึกลงระบบจริง",
+        "Training mode, using sample data and saving nothing",
+    ),
+    ("ยังไม่ได้ตั้งค่าฐานข้อมูล", "No database set up"),
+    ("กำลังตรวจสอบการเชื่อมต่อ", "Checking connection"),
+    ("ออนไลน์", "Online"),
+    ("สัญญาณช้า", "Slow connection"),
+    ("การเชื่อมต่อไม่เสถียร:", "Unstable connection:"),
+    ("ออฟไลน์:", "Offline:"),
+    (
+        "อ่านไฟล์ตั้งค่าไม่ได้ ใช้ค่าเริ่มต้นแทน:",
+        "Cannot read settings, using defaults:",
+    ),
+    ("อ่านประวัติการขายไม่ครบ:", "Sales history partly unreadable:"),
+    ("ปิด", "Close"),
+    ("ร้านค้า", "Shop"),
+    ("ใบเสร็จ", "Receipt"),
+    ("เครื่องพิมพ์", "Printer"),
+    ("เครื่องสแกน", "Scanner"),
+    ("ภาษีและส่วนลด", "Tax and discounts"),
+    ("การแสดงผล", "Display"),
+    ("การเชื่อมต่อ", "Connection"),
+    ("ชื่อร้าน", "Shop name"),
+    ("ที่อยู่", "Address"),
+    ("เลขประจำตัวผู้เสียภาษี", "Tax ID"),
+    ("รหัสสาขา", "Branch code"),
+    ("พร้อมเพย์", "PromptPay"),
+    ("หัวใบเสร็จ", "Receipt header"),
+    ("ท้ายใบเสร็จ", "Receipt footer"),
+    ("ที่อยู่เครื่องพิมพ์", "Printer address"),
+    ("โค้ดเพจภาษาไทย", "Thai code page"),
+    ("ตัวอักษรต่อบรรทัด", "Characters per line"),
+    ("ฟอนต์ใบกำกับภาษี", "Tax invoice font"),
+    ("โฟลเดอร์ใบกำกับภาษี", "Tax invoice folder"),
+    ("อักษรนำหน้ารหัส", "Code prefix"),
+    ("อักษรต่อท้ายรหัส", "Code suffix"),
+    ("อัตราภาษีมูลค่าเพิ่ม (%)", "VAT rate (%)"),
+    ("ส่วนลดสูงสุดที่ไม่ต้องอนุมัติ", "Discount without approval"),
+    ("ยอดซื้อต่อ 1 แต้ม", "Baht per point"),
+    ("ขนาดตัวอักษร (%)", "Font size (%)"),
+    ("ชื่อโปรไฟล์", "Profile name"),
+    ("ฐานข้อมูล", "Database"),
+    ("เครื่องพิมพ์ใบเสร็จ", "Receipt printer"),
+    ("ภาษา", "Language"),
+    ("ธีม", "Theme"),
+    ("โปรไฟล์", "Profile"),
+    ("เพิ่ม", "Add"),
+    ("ลบ", "Remove"),
+    (
+        "เซิร์ฟเวอร์จริง (ปิดไว้สำหรับโหมดฝึกอบรม)",
+        "Production server (off for training)",
+    ),
+    ("ทดสอบการเชื่อมต่อ", "Test connection"),
+    ("ใช้เซิร์ฟเวอร์นี้", "Use this server"),
+    ("คืนค่าเริ่มต้น", "Defaults"),
+    ("ยกเลิก", "Cancel"),
+    ("บันทึก", "Save"),
+];
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Appearance {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    pub const ALL: [Appearance; 2] = [Appearance::Light, Appearance::Dark];
+
+    pub fn theme(&self) -> iced::Theme {
+        match self {
+            Appearance::Light => iced::Theme::Light,
+            Appearance::Dark => iced::Theme::Dark,
+        }
+    }
+}
+
+impl fmt::Display for Appearance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Appearance::Light => "สว่าง",
+            Appearance::Dark => "มืด",
+        })
+    }
+}
+
+/// A backend the till can talk to.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub url: String,
+    /// Sales on any other profile are practice: the till shows training mode
+    /// and receipts say they aren't real.
+    pub production: bool,
+}
+
+impl Profile {
+    pub fn live(url: String) -> Self {
+        Profile {
+            name: "live".to_string(),
+            url,
+            production: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
 pub struct Setting {
-    pub database_url: String,
+    pub version: u32,
+    pub profiles: Vec<Profile>,
+    /// Name of the profile in use.
+    pub profile: String,
+    pub promptpay_id: String,
+    pub shop_name: String,
+    pub shop_address: String,
+    pub tax_id: String,
+    /// `00000` for the head office, as printed on tax invoices.
+    pub branch: String,
+    pub receipt_header: String,
+    pub receipt_footer: String,
+    pub printer: PrinterTarget,
+    /// `ESC t` code page holding TIS-620 on the installed printer.
+    pub printer_code_page: u8,
+    pub printer_columns: usize,
+    /// TrueType font with Thai glyphs used for tax invoice PDFs.
+    pub invoice_font: String,
+    /// Where tax invoices are written, taken from the data directory unless
+    /// absolute.
+    pub invoice_dir: String,
+    /// Characters some scanners send around every code, removed on scan.
+    pub scanner_prefix: String,
+    pub scanner_suffix: String,
+    /// VAT in percent, charged on top of exclusive prices and taken out of
+    /// inclusive ones.
+    pub vat_rate: u64,
+    /// Manual discounts above this many baht need a supervisor PIN.
+    pub discount_limit: u32,
+    /// Members earn one point for every this many baht paid.
+    pub baht_per_point: u32,
+    /// Interface language, where the page has been translated.
+    pub language: Language,
+    pub appearance: Appearance,
+    /// Interface size in percent.
+    pub font_scale: u32,
+}
+
+impl Default for Setting {
+    fn default() -> Self {
+        Setting {
+            version: VERSION,
+            profiles: vec![Profile::live("".to_string())],
+            profile: "live".to_string(),
+            promptpay_id: "".to_string(),
+            shop_name: "".to_string(),
+            shop_address: "".to_string(),
+            tax_id: "".to_string(),
+            branch: "00000".to_string(),
+            receipt_header: "".to_string(),
+            receipt_footer: "ขอบคุณที่ใช้บริการ".to_string(),
+            printer: PrinterTarget::None,
+            printer_code_page: 21,
+            printer_columns: 42,
+            invoice_font: "".to_string(),
+            invoice_dir: "invoices".to_string(),
+            scanner_prefix: "".to_string(),
+            scanner_suffix: "".to_string(),
+            vat_rate: VAT_RATE,
+            discount_limit: 20,
+            baht_per_point: 25,
+            language: Language::Thai,
+            appearance: Appearance::Light,
+            font_scale: 100,
+        }
+    }
 }
 
 impl Setting {
-    pub fn get_setting() -> std::io::Result<Self> {
-        let mut file = File::open("setting.json")?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        let setting: Setting = serde_json::from_str(&contents)?;
+    /// Settings at `path`, or the defaults when there is no file yet. A file
+    /// that can't be read or parsed is an error rather than the defaults, so
+    /// it isn't overwritten unnoticed.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Setting::default()),
+            Err(error) => return Err(format!("{}: {}", path.display(), error)),
+        };
+        serde_json::from_str(&contents)
+            .and_then(Setting::migrate)
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Brings a `setting.json` written by an older version up to [`VERSION`].
+    /// Files without a version are version 1.
+    pub fn migrate(mut value: Value) -> serde_json::Result<Self> {
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(1);
+        if version < 2 {
+            // Version 1 kept the backend as a bare `host:port`
+            if let Some(Value::String(url)) = value.get_mut("database_url") {
+                if !url.is_empty() && !url.contains("://") {
+                    *url = format!("http://{}", url);
+                }
+            }
+        }
+        if version < 3 {
+            // Version 2 had a single backend
+            if let Some(object) = value.as_object_mut() {
+                let url = match object.remove("database_url") {
+                    Some(Value::String(url)) => url,
+                    _ => "".to_string(),
+                };
+                let profile = serde_json::to_value(Profile::live(url))?;
+                object.insert("profiles".to_string(), Value::Array(vec![profile]));
+                object.insert("profile".to_string(), Value::from("live"));
+            }
+        }
+        let mut setting: Setting = serde_json::from_value(value)?;
+        setting.version = VERSION;
         Ok(setting)
     }
 
-    pub fn set_setting(&self) -> std::io::Result<()> {
-        let setting = serde_json::to_string(self)?;
-        let mut file = File::create("setting.json")?;
-        file.write_all(setting.as_bytes())?;
-        Ok(())
+    pub fn active(&self) -> Option<&Profile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == self.profile)
+    }
+
+    /// URL of the backend in use.
+    pub fn database_url(&self) -> &str {
+        self.active()
+            .map(|profile| profile.url.as_str())
+            .unwrap_or("")
+    }
+
+    /// Whether the profile in use is not the production one.
+    pub fn training(&self) -> bool {
+        self.active().is_some_and(|profile| !profile.production)
+    }
+
+    /// Directory tax invoice files are written to, for records kept in `data`.
+    pub fn invoices(&self, data: &Path) -> PathBuf {
+        data.join(&self.invoice_dir)
+    }
+
+    /// Where sales and tax invoices are kept under `data`: apart from the real
+    /// ones on a profile that isn't production, so practice neither takes
+    /// receipt numbers nor shows in reports.
+    pub fn records(&self, data: &Path) -> PathBuf {
+        match self.training() {
+            true => data.join("practice"),
+            false => data.to_path_buf(),
+        }
+    }
+
+    /// `barcode` as scanned, without the scanner's prefix and suffix.
+    pub fn scanned<'a>(&self, barcode: &'a str) -> &'a str {
+        let barcode = barcode.trim();
+        let barcode = barcode
+            .strip_prefix(self.scanner_prefix.as_str())
+            .unwrap_or(barcode);
+        barcode
+            .strip_suffix(self.scanner_suffix.as_str())
+            .unwrap_or(barcode)
+    }
+}
+
+/// `file` queued for the settings file.
+pub async fn save_setting(setting: Setting, file: Write) -> Result<(), String> {
+    let setting = serde_json::to_vec_pretty(&setting).map_err(|error| error.to_string())?;
+    file.replace(&setting)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::write_atomic;
+
+    #[test]
+    fn migrate_version_1() {
+        let setting =
+            Setting::migrate(serde_json::json!({"database_url": "0.0.0.0:3000"})).unwrap();
+        assert_eq!(setting.version, VERSION);
+        assert_eq!(setting.database_url(), "http://0.0.0.0:3000");
+        assert!(!setting.training());
+        assert_eq!(setting.vat_rate, 7);
+        assert_eq!(setting.font_scale, 100);
+    }
+
+    #[test]
+    fn migrate_version_2() {
+        let setting = Setting::migrate(serde_json::json!({
+            "version": 2,
+            "database_url": "http://10.0.0.5:3000",
+            "shop_name": "ร้านซันมินิมาร์ท",
+        }))
+        .unwrap();
+        assert_eq!(
+            setting.profiles,
+            vec![Profile::live("http://10.0.0.5:3000".to_string())]
+        );
+        assert_eq!(setting.database_url(), "http://10.0.0.5:3000");
+        assert_eq!(setting.shop_name, "ร้านซันมินิมาร์ท");
+    }
+
+    #[test]
+    fn config_lookup_order() {
+        let args = |args: &[&str]| {
+            args.iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>()
+                .into_iter()
+        };
+        let env = Some("/etc/sunminimart.json".to_string());
+        assert_eq!(
+            locate(args(&["--config", "till.json"]), env.clone()),
+            PathBuf::from("till.json")
+        );
+        assert_eq!(
+            locate(args(&["--config=till.json"]), env.clone()),
+            PathBuf::from("till.json")
+        );
+        assert_eq!(
+            locate(args(&[]), env),
+            PathBuf::from("/etc/sunminimart.json")
+        );
+        assert!(locate(args(&[]), None).ends_with("sunminimart/setting.json"));
+    }
+
+    #[test]
+    fn load_and_save() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-{}", std::process::id()));
+        let path = dir.join("nested").join("setting.json");
+        assert_eq!(Setting::load(&path), Ok(Setting::default()));
+
+        let setting = Setting {
+            shop_name: "ร้านซันมินิมาร์ท".to_string(),
+            ..Setting::default()
+        };
+        write_atomic(&path, &serde_json::to_vec(&setting).unwrap()).unwrap();
+        assert_eq!(Setting::load(&path), Ok(setting));
+        assert!(!path.with_extension("tmp").exists());
+
+        std::fs::write(&path, "{\"shop_name\": ").unwrap();
+        assert!(Setting::load(&path).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn language_falls_back_to_thai() {
+        assert_eq!(Language::Thai.text("ตั้งค่า"), "ตั้งค่า");
+        assert_eq!(Language::English.text("ตั้งค่า"), "Settings");
+        assert_eq!(Language::English.text("ชำระเงิน"), "ชำระเงิน");
+        let setting: Setting = serde_json::from_str("{\"shop_name\": \"ร้าน\"}").unwrap();
+        assert_eq!(setting.language, Language::Thai);
+    }
+
+    #[test]
+    fn adopt_files_left_by_older_versions() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-legacy-{}", std::process::id()));
+        let legacy = dir.join("old");
+        let config = dir.join("config").join("till.json");
+        std::fs::create_dir_all(&legacy).unwrap();
+        std::fs::write(legacy.join("setting.json"), "{}").unwrap();
+        std::fs::write(legacy.join("users.json"), "[]").unwrap();
+        std::fs::write(legacy.join("sale.json"), "{}").unwrap();
+        adopt_legacy(&legacy, &config).unwrap();
+        assert_eq!(std::fs::read_to_string(&config).unwrap(), "{}");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("config").join("users.json")).unwrap(),
+            "[]"
+        );
+        assert!(!legacy.join("setting.json").exists());
+        assert!(!legacy.join("users.json").exists());
+        assert!(!dir.join("config").join("sale.json").exists());
+
+        // What is there already is kept
+        std::fs::write(legacy.join("users.json"), "[{}]").unwrap();
+        adopt_legacy(&legacy, &config).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("config").join("users.json")).unwrap(),
+            "[]"
+        );
+        assert!(legacy.join("users.json").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invoices_beside_the_records() {
+        let data = Path::new("/var/lib/sunminimart");
+        let mut setting = Setting::default();
+        assert_eq!(setting.invoices(data), data.join("invoices"));
+        setting.invoice_dir = "/srv/invoices".to_string();
+        assert_eq!(setting.invoices(data), PathBuf::from("/srv/invoices"));
+    }
+
+    #[test]
+    fn scanner_prefix_and_suffix() {
+        let setting = Setting {
+            scanner_prefix: "]C1".to_string(),
+            scanner_suffix: "#".to_string(),
+            ..Setting::default()
+        };
+        assert_eq!(setting.scanned("]C18851234567890#"), "8851234567890");
+        assert_eq!(setting.scanned("100"), "100");
     }
 }
@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Replaces `path` through a temporary file and a rename, so a crash
+/// mid-write leaves either the old file or the new one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&temp, path)
+}
+
+/// Reads a journal of one JSON value per line, with why any of it couldn't
+/// be read. Lines that don't parse are left out rather than losing the
+/// rest, and a missing file is an empty journal.
+pub fn read_lines<T: DeserializeOwned>(path: &Path) -> (Vec<T>, Option<String>) {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return (Vec::new(), None),
+        Err(error) => return (Vec::new(), Some(format!("{}: {}", path.display(), error))),
+    };
+    let mut values = Vec::new();
+    let mut error = None;
+    for (index, line) in String::from_utf8_lossy(&contents).lines().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(value) => values.push(value),
+            Err(parse) => {
+                error.get_or_insert(format!("{}:{}: {}", path.display(), index + 1, parse));
+            }
+        }
+    }
+    (values, error)
+}
+
+/// Appends `value` to its journal as a line of JSON, after the lines queued
+/// before it.
+pub async fn append_line<T: Serialize>(value: T, file: Append) -> Result<(), String> {
+    let mut line = serde_json::to_vec(&value).map_err(|error| error.to_string())?;
+    line.push(b'\n');
+    file.write(line)
+}
+
+/// Whole-file writes from tasks that may finish out of order. Each is
+/// numbered when it is queued and none replaces a file a later one has
+/// already written, so the last queued wins.
+#[derive(Clone, Default)]
+pub struct Writes(Arc<Queue>);
+
+#[derive(Default)]
+struct Queue {
+    queued: AtomicU64,
+    /// Number of the last write done to each file.
+    written: Mutex<HashMap<PathBuf, u64>>,
+    /// Appends not yet made to each file.
+    appends: Mutex<HashMap<PathBuf, Pending>>,
+}
+
+/// Appends by number, `None` while the task making it is still running.
+type Pending = BTreeMap<u64, Option<Vec<u8>>>;
+
+impl Writes {
+    pub fn queue(&self, path: PathBuf) -> Write {
+        Write {
+            number: self.0.queued.fetch_add(1, Ordering::SeqCst) + 1,
+            path,
+            writes: self.clone(),
+        }
+    }
+
+    /// An append to `path`, made after every append queued before it.
+    pub fn append(&self, path: PathBuf) -> Append {
+        let number = self.0.queued.fetch_add(1, Ordering::SeqCst) + 1;
+        lock(&self.0.appends)
+            .entry(path.clone())
+            .or_default()
+            .insert(number, None);
+        Append {
+            number,
+            path,
+            writes: self.clone(),
+            done: false,
+        }
+    }
+
+    /// Settles append `number` and makes every append to `path` that no
+    /// longer waits on an earlier one. Fails with the first that couldn't
+    /// be made, whichever task queued it.
+    fn flush(&self, path: &Path, number: u64, contents: Option<Vec<u8>>) -> Result<(), String> {
+        let mut appends = lock(&self.0.appends);
+        let Some(pending) = appends.get_mut(path) else {
+            return Ok(());
+        };
+        match contents {
+            Some(contents) => pending.insert(number, Some(contents)),
+            None => pending.remove(&number),
+        };
+        let mut result = Ok(());
+        while let Some(entry) = pending.first_entry() {
+            if entry.get().is_none() {
+                break;
+            }
+            let contents = entry.remove().unwrap_or_default();
+            let appended = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| OpenOptions::new().create(true).append(true).open(path))
+                .and_then(|mut file| file.write_all(&contents))
+                .map_err(|error| format!("{}: {}", path.display(), error));
+            result = result.and(appended);
+        }
+        result
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl PartialEq for Writes {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Writes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Writes")
+    }
+}
+
+/// A write to `path` in its place in the queue.
+pub struct Write {
+    number: u64,
+    path: PathBuf,
+    writes: Writes,
+}
+
+impl Write {
+    pub fn replace(self, contents: &[u8]) -> Result<(), String> {
+        self.run(|path| write_atomic(path, contents))
+    }
+
+    pub fn remove(self) -> Result<(), String> {
+        self.run(|path| match std::fs::remove_file(path) {
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+            result => result,
+        })
+    }
+
+    fn run(self, write: impl FnOnce(&Path) -> std::io::Result<()>) -> Result<(), String> {
+        let mut written = lock(&self.writes.0.written);
+        if written
+            .get(&self.path)
+            .is_some_and(|last| *last > self.number)
+        {
+            return Ok(());
+        }
+        write(&self.path).map_err(|error| format!("{}: {}", self.path.display(), error))?;
+        written.insert(self.path, self.number);
+        Ok(())
+    }
+}
+
+/// An append to `path` in its place in the queue. Dropped unmade, it no
+/// longer holds back the ones after it.
+pub struct Append {
+    number: u64,
+    path: PathBuf,
+    writes: Writes,
+    done: bool,
+}
+
+impl Append {
+    pub fn write(mut self, contents: Vec<u8>) -> Result<(), String> {
+        self.done = true;
+        self.writes.flush(&self.path, self.number, Some(contents))
+    }
+}
+
+impl Drop for Append {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.writes.flush(&self.path, self.number, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn last_queued_wins() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-store-{}", std::process::id()));
+        let path = dir.join("sale.json");
+        let writes = Writes::default();
+        let first = writes.queue(path.clone());
+        let second = writes.queue(path.clone());
+        let removal = writes.queue(path.clone());
+
+        second.replace(b"2").unwrap();
+        // Finishing late doesn't put the older basket back
+        first.replace(b"1").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"2");
+        removal.remove().unwrap();
+        assert!(!path.exists());
+        assert!(!path.with_extension("tmp").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn appends_keep_their_order() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-append-{}", std::process::id()));
+        // Made by the first append
+        let path = dir.join("practice").join("transactions.jsonl");
+        let writes = Writes::default();
+        let first = writes.append(path.clone());
+        let dropped = writes.append(path.clone());
+        let third = writes.append(path.clone());
+
+        third.write(b"3\n".to_vec()).unwrap();
+        assert!(!path.exists());
+        first.write(b"1\n".to_vec()).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"1\n");
+        drop(dropped);
+        assert_eq!(std::fs::read(&path).unwrap(), b"1\n3\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
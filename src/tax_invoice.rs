@@ -0,0 +1,428 @@
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use printpdf::{Mm, PdfDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::setting::Setting;
+use crate::vat::{baht, recorded_rate, Breakdown};
+use crate::Item;
+
+/// Checks the mod-11 check digit of a 13-digit Thai tax or national ID.
+pub fn valid_tax_id(tax_id: &str) -> bool {
+    let digits: Vec<u32> = tax_id.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() != 13 || tax_id.chars().count() != 13 {
+        return false;
+    }
+    let sum: u32 = digits[..12]
+        .iter()
+        .enumerate()
+        .map(|(index, digit)| digit * (13 - index as u32))
+        .sum();
+    (11 - sum % 11) % 10 == digits[12]
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
+pub struct Buyer {
+    pub name: String,
+    pub address: String,
+    pub tax_id: String,
+    /// `00000` for a head office, otherwise the five-digit branch number.
+    pub branch: String,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct TaxInvoice {
+    pub number: String,
+    pub issued: NaiveDateTime,
+    pub receipt: u32,
+    pub buyer: Buyer,
+    pub items: Vec<Item>,
+    pub total: u32,
+    /// VAT in percent of the sale it was issued for.
+    #[serde(default = "recorded_rate")]
+    pub vat_rate: u64,
+}
+
+/// Next sequential number for `branch`, e.g. `00000-000042`.
+pub fn next_number(invoices: &[TaxInvoice], branch: &str) -> String {
+    let issued = invoices
+        .iter()
+        .filter(|invoice| invoice.number.split('-').next() == Some(branch))
+        .count();
+    format!("{}-{:06}", branch, issued + 1)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl TaxInvoice {
+    /// Revenue Department e-Tax Invoice in the ETDA `TaxInvoice_CrossIndustryInvoice`
+    /// structure.
+    pub fn to_xml(&self, setting: &Setting) -> String {
+        let breakdown =
+            Breakdown::of(&self.items, self.vat_rate).scaled_to(self.total as u64 * 100);
+        let lines: String = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                format!(
+                    "    <ram:IncludedSupplyChainTradeLineItem>\n\
+                     \x20     <ram:AssociatedDocumentLineDocument><ram:LineID>{}</ram:LineID></ram:AssociatedDocumentLineDocument>\n\
+                     \x20     <ram:SpecifiedTradeProduct><ram:ID>{}</ram:ID><ram:Name>{}</ram:Name></ram:SpecifiedTradeProduct>\n\
+                     \x20     <ram:SpecifiedLineTradeAgreement><ram:GrossPriceProductTradePrice><ram:ChargeAmount>{}</ram:ChargeAmount></ram:GrossPriceProductTradePrice></ram:SpecifiedLineTradeAgreement>\n\
+                     \x20     <ram:SpecifiedLineTradeDelivery><ram:BilledQuantity unitCode=\"EA\">{}</ram:BilledQuantity></ram:SpecifiedLineTradeDelivery>\n\
+                     \x20     <ram:SpecifiedLineTradeSettlement><ram:SpecifiedTradeSettlementLineMonetarySummation><ram:NetLineTotalAmount>{}</ram:NetLineTotalAmount></ram:SpecifiedTradeSettlementLineMonetarySummation></ram:SpecifiedLineTradeSettlement>\n\
+                     \x20   </ram:IncludedSupplyChainTradeLineItem>\n",
+                    index + 1,
+                    escape(&item.barcode),
+                    escape(&item.name),
+                    baht(item.price as u64 * 100),
+                    escape(&item.amount),
+                    baht(item.sum as u64 * 100),
+                )
+            })
+            .collect();
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rsm:TaxInvoice_CrossIndustryInvoice xmlns:rsm=\"urn:etda:uncefact:data:standard:TaxInvoice_CrossIndustryInvoice:2\" xmlns:ram=\"urn:etda:uncefact:data:standard:TaxInvoice_ReusableAggregateBusinessInformationEntity:2\">\n\
+             \x20 <rsm:ExchangedDocumentContext>\n\
+             \x20   <ram:GuidelineSpecifiedDocumentContextParameter><ram:ID schemeAgencyID=\"ETDA\" schemeVersionID=\"v2.0\">ER3-2560</ram:ID></ram:GuidelineSpecifiedDocumentContextParameter>\n\
+             \x20 </rsm:ExchangedDocumentContext>\n\
+             \x20 <rsm:ExchangedDocument>\n\
+             \x20   <ram:ID>{number}</ram:ID>\n\
+             \x20   <ram:Name>ใบกำกับภาษี</ram:Name>\n\
+             \x20   <ram:TypeCode>388</ram:TypeCode>\n\
+             \x20   <ram:IssueDateTime>{issued}</ram:IssueDateTime>\n\
+             \x20 </rsm:ExchangedDocument>\n\
+             \x20 <rsm:SupplyChainTradeTransaction>\n\
+             \x20   <ram:ApplicableHeaderTradeAgreement>\n\
+             \x20     <ram:SellerTradeParty>\n\
+             \x20       <ram:Name>{seller}</ram:Name>\n\
+             \x20       <ram:SpecifiedTaxRegistration><ram:ID schemeID=\"TXID\">{seller_tax_id}{seller_branch}</ram:ID></ram:SpecifiedTaxRegistration>\n\
+             \x20       <ram:PostalTradeAddress><ram:LineOne>{seller_address}</ram:LineOne><ram:CountryID>TH</ram:CountryID></ram:PostalTradeAddress>\n\
+             \x20     </ram:SellerTradeParty>\n\
+             \x20     <ram:BuyerTradeParty>\n\
+             \x20       <ram:Name>{buyer}</ram:Name>\n\
+             \x20       <ram:SpecifiedTaxRegistration><ram:ID schemeID=\"TXID\">{buyer_tax_id}{buyer_branch}</ram:ID></ram:SpecifiedTaxRegistration>\n\
+             \x20       <ram:PostalTradeAddress><ram:LineOne>{buyer_address}</ram:LineOne><ram:CountryID>TH</ram:CountryID></ram:PostalTradeAddress>\n\
+             \x20     </ram:BuyerTradeParty>\n\
+             \x20   </ram:ApplicableHeaderTradeAgreement>\n\
+             \x20   <ram:ApplicableHeaderTradeDelivery/>\n\
+             \x20   <ram:ApplicableHeaderTradeSettlement>\n\
+             \x20     <ram:InvoiceCurrencyCode listID=\"ISO 4217 3A\">THB</ram:InvoiceCurrencyCode>\n\
+             \x20     <ram:ApplicableTradeTax><ram:TypeCode>VAT</ram:TypeCode><ram:CalculatedRate>{rate}.00</ram:CalculatedRate><ram:BasisAmount>{taxable}</ram:BasisAmount><ram:CalculatedAmount>{vat}</ram:CalculatedAmount></ram:ApplicableTradeTax>\n\
+             \x20     <ram:ApplicableTradeTax><ram:TypeCode>VAT</ram:TypeCode><ram:CalculatedRate>0.00</ram:CalculatedRate><ram:BasisAmount>{exempt}</ram:BasisAmount><ram:CalculatedAmount>0.00</ram:CalculatedAmount><ram:ExemptionReason>ยกเว้นภาษีมูลค่าเพิ่ม</ram:ExemptionReason></ram:ApplicableTradeTax>\n\
+             \x20     <ram:SpecifiedTradeSettlementHeaderMonetarySummation>\n\
+             \x20       <ram:LineTotalAmount>{total}</ram:LineTotalAmount>\n\
+             \x20       <ram:TaxBasisTotalAmount>{base}</ram:TaxBasisTotalAmount>\n\
+             \x20       <ram:TaxTotalAmount>{vat}</ram:TaxTotalAmount>\n\
+             \x20       <ram:GrandTotalAmount>{total}</ram:GrandTotalAmount>\n\
+             \x20     </ram:SpecifiedTradeSettlementHeaderMonetarySummation>\n\
+             \x20   </ram:ApplicableHeaderTradeSettlement>\n\
+             {lines}\
+             \x20 </rsm:SupplyChainTradeTransaction>\n\
+             </rsm:TaxInvoice_CrossIndustryInvoice>\n",
+            number = escape(&self.number),
+            issued = self.issued.format("%Y-%m-%dT%H:%M:%S"),
+            seller = escape(&setting.shop_name),
+            seller_tax_id = escape(&setting.tax_id),
+            seller_branch = escape(&setting.branch),
+            seller_address = escape(&setting.shop_address),
+            buyer = escape(&self.buyer.name),
+            buyer_tax_id = escape(&self.buyer.tax_id),
+            buyer_branch = escape(&self.buyer.branch),
+            buyer_address = escape(&self.buyer.address),
+            rate = self.vat_rate,
+            taxable = baht(breakdown.taxable),
+            exempt = baht(breakdown.exempt),
+            base = baht(breakdown.base()),
+            vat = baht(breakdown.vat),
+            total = baht(self.total as u64 * 100),
+            lines = lines,
+        )
+    }
+
+    /// A4 PDF of the invoice, set in the TrueType font at `setting.invoice_font`
+    /// since the built-in PDF fonts have no Thai glyphs.
+    pub fn to_pdf(&self, setting: &Setting) -> Result<Vec<u8>, String> {
+        let (document, page, layer) = PdfDocument::new(
+            self.number.as_str(),
+            Mm(PAGE_WIDTH),
+            Mm(PAGE_HEIGHT),
+            "invoice",
+        );
+        let font = File::open(&setting.invoice_font).map_err(|error| error.to_string())?;
+        let font = document
+            .add_external_font(font)
+            .map_err(|error| error.to_string())?;
+
+        for (index, texts) in self.layout(setting).iter().enumerate() {
+            let layer = match index {
+                0 => document.get_page(page).get_layer(layer),
+                _ => {
+                    let (page, layer) =
+                        document.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "invoice");
+                    document.get_page(page).get_layer(layer)
+                }
+            };
+            for text in texts {
+                layer.use_text(&text.text, text.size, Mm(text.x), Mm(text.y), &font);
+            }
+        }
+
+        document.save_to_bytes().map_err(|error| error.to_string())
+    }
+
+    /// The text of the invoice page by page, going on to the next page
+    /// whenever a line would run into the bottom margin.
+    fn layout(&self, setting: &Setting) -> Vec<Vec<Text>> {
+        let mut pages = Pages::default();
+        pages.line(&[("ใบกำกับภาษี / ใบเสร็จรับเงิน", 18.0, 20.0)], 10.0);
+        let header = [
+            setting.shop_name.clone(),
+            setting.shop_address.replace('\n', " "),
+            format!(
+                "เลขประจำตัวผู้เสียภาษี {} สาขา {}",
+                setting.tax_id, setting.branch
+            ),
+            format!("เลขที่ {}", self.number),
+            format!("วันที่ {}", self.issued.format("%d/%m/%Y %H:%M")),
+            format!("ผู้ซื้อ {}", self.buyer.name),
+            self.buyer.address.replace('\n', " "),
+            format!(
+                "เลขประจำตัวผู้เสียภาษี {} สาขา {}",
+                self.buyer.tax_id, self.buyer.branch
+            ),
+        ];
+        for text in &header {
+            pages.line(&[(text, 11.0, 20.0)], 7.0);
+        }
+
+        pages.skip(5.0);
+        for (index, item) in self.items.iter().enumerate() {
+            pages.line(
+                &[
+                    (&format!("{}", index + 1), 11.0, 20.0),
+                    (&item.name, 11.0, 30.0),
+                    (&format!("{} x {}", item.amount, item.price), 11.0, 130.0),
+                    (&baht(item.sum as u64 * 100), 11.0, 170.0),
+                ],
+                7.0,
+            );
+        }
+
+        pages.skip(5.0);
+        let breakdown =
+            Breakdown::of(&self.items, self.vat_rate).scaled_to(self.total as u64 * 100);
+        let totals = [
+            ("มูลค่าสินค้าที่เสียภาษี".to_string(), breakdown.taxable),
+            ("มูลค่าสินค้าที่ได้รับยกเว้น".to_string(), breakdown.exempt),
+            (format!("ภาษีมูลค่าเพิ่ม {}%", self.vat_rate), breakdown.vat),
+            ("รวมทั้งสิ้น".to_string(), self.total as u64 * 100),
+        ];
+        for (label, amount) in totals {
+            pages.line(&[(&label, 11.0, 110.0), (&baht(amount), 11.0, 170.0)], 7.0);
+        }
+        pages.done
+    }
+}
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+/// Baseline of the first line on a page, and the lowest one may sit on.
+const TOP: f32 = 280.0;
+const BOTTOM: f32 = 20.0;
+
+/// Text placed on a page, in millimetres from its bottom left corner.
+#[derive(PartialEq, Debug)]
+struct Text {
+    text: String,
+    size: f32,
+    x: f32,
+    y: f32,
+}
+
+struct Pages {
+    done: Vec<Vec<Text>>,
+    y: f32,
+}
+
+impl Default for Pages {
+    fn default() -> Self {
+        Pages {
+            done: vec![Vec::new()],
+            y: TOP,
+        }
+    }
+}
+
+impl Pages {
+    /// Sets `texts` as (text, size, x) on one line and moves `height` down.
+    fn line(&mut self, texts: &[(&str, f32, f32)], height: f32) {
+        if self.y < BOTTOM {
+            self.done.push(Vec::new());
+            self.y = TOP;
+        }
+        let page = self.done.last_mut().expect("a page to write on");
+        for (text, size, x) in texts {
+            page.push(Text {
+                text: text.to_string(),
+                size: *size,
+                x: *x,
+                y: self.y,
+            });
+        }
+        self.y -= height;
+    }
+
+    fn skip(&mut self, height: f32) {
+        self.y -= height;
+    }
+}
+
+/// Writes `<number>.xml` and `<number>.pdf` into `dir`. An invoice already
+/// there is never replaced, and neither file is left if both can't be
+/// written.
+pub fn save(dir: &Path, number: &str, xml: &[u8], pdf: &[u8]) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    let xml_path = dir.join(format!("{}.xml", number));
+    let pdf_path = dir.join(format!("{}.pdf", number));
+    write_new(&xml_path, xml)?;
+    write_new(&pdf_path, pdf).inspect_err(|_| {
+        let _ = std::fs::remove_file(&xml_path);
+    })
+}
+
+/// Writes a file that must not exist yet, removing what was written of it
+/// on failure.
+fn write_new(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|error| match error.kind() {
+            ErrorKind::AlreadyExists => format!("{} มีอยู่แล้ว", path.display()),
+            _ => format!("{}: {}", path.display(), error),
+        })?;
+    file.write_all(contents).map_err(|error| {
+        let _ = std::fs::remove_file(path);
+        format!("{}: {}", path.display(), error)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vat::{Vat, VAT_RATE};
+
+    fn invoice(total: u32) -> TaxInvoice {
+        TaxInvoice {
+            number: "00000-000001".to_string(),
+            issued: NaiveDateTime::parse_from_str("2025-01-31 10:30:00", "%Y-%m-%d %H:%M:%S")
+                .unwrap(),
+            receipt: 1,
+            buyer: Buyer {
+                name: "บริษัท ตัวอย่าง & ลูก จำกัด".to_string(),
+                address: "1 ถนนสุขุมวิท".to_string(),
+                tax_id: "0105536092641".to_string(),
+                branch: "00000".to_string(),
+            },
+            items: vec![Item {
+                barcode: "1".to_string(),
+                name: "สินค้า".to_string(),
+                price: total,
+                amount: "1".to_string(),
+                sum: total,
+                vat: Vat::Inclusive,
+            }],
+            total,
+            vat_rate: VAT_RATE,
+        }
+    }
+
+    #[test]
+    fn tax_id_checksum() {
+        assert!(valid_tax_id("0105536092641"));
+        assert!(!valid_tax_id("0105536092642"));
+        assert!(!valid_tax_id("010553609264"));
+        assert!(!valid_tax_id("01055360926a1"));
+    }
+
+    #[test]
+    fn vat_breakdown() {
+        let exact = Breakdown::of(&invoice(107).items, VAT_RATE);
+        assert_eq!(exact.vat, 700);
+        assert_eq!(exact.base(), 10000);
+
+        let rounded = Breakdown::of(&invoice(100).items, VAT_RATE);
+        assert_eq!(rounded.vat + rounded.base(), 10000);
+        assert_eq!(baht(rounded.vat), "6.54");
+    }
+
+    #[test]
+    fn numbering_per_branch() {
+        let invoices = vec![invoice(100)];
+        assert_eq!(next_number(&invoices, "00000"), "00000-000002");
+        assert_eq!(next_number(&invoices, "00001"), "00001-000001");
+    }
+
+    #[test]
+    fn xml_structure() {
+        let xml = invoice(107).to_xml(&Setting::default());
+        assert!(xml.contains("<ram:ID>00000-000001</ram:ID>"));
+        assert!(xml.contains("<ram:IssueDateTime>2025-01-31T10:30:00</ram:IssueDateTime>"));
+        assert!(xml.contains("<ram:CalculatedAmount>7.00</ram:CalculatedAmount>"));
+        assert!(xml.contains("<ram:GrandTotalAmount>107.00</ram:GrandTotalAmount>"));
+        assert!(xml.contains("ตัวอย่าง &amp; ลูก"));
+    }
+
+    #[test]
+    fn pdf_without_font() {
+        let setting = Setting {
+            invoice_font: "/nonexistent/font.ttf".to_string(),
+            ..Setting::default()
+        };
+        assert!(invoice(107).to_pdf(&setting).is_err());
+    }
+
+    #[test]
+    fn long_invoice_runs_onto_more_pages() {
+        let mut long = invoice(107);
+        long.items = vec![long.items[0].clone(); 60];
+        let pages = long.layout(&Setting::default());
+
+        assert_eq!(pages.len(), 2);
+        assert!(pages
+            .iter()
+            .flatten()
+            .all(|text| (BOTTOM..=TOP).contains(&text.y)));
+        let last = pages[1].last().unwrap();
+        assert_eq!(last.text, "107.00");
+        assert_eq!(invoice(107).layout(&Setting::default()).len(), 1);
+    }
+
+    #[test]
+    fn save_never_replaces_an_invoice() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-invoice-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        save(&dir, "00000-000001", b"xml", b"pdf").unwrap();
+
+        assert!(save(&dir, "00000-000001", b"other", b"other").is_err());
+        assert_eq!(std::fs::read(dir.join("00000-000001.xml")).unwrap(), b"xml");
+        // The XML isn't left without its PDF
+        std::fs::write(dir.join("00000-000002.pdf"), b"pdf").unwrap();
+        assert!(save(&dir, "00000-000002", b"xml", b"pdf").is_err());
+        assert!(!dir.join("00000-000002.xml").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
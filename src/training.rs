@@ -0,0 +1,197 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::api::Product;
+use crate::audit::Entry;
+use crate::credit::Account;
+use crate::customer::Customer;
+use crate::drawer::Session;
+use crate::tax_invoice::TaxInvoice;
+use crate::transaction::{Kind, Transaction};
+
+/// Backend kept in memory for training, answering the same calls as the
+/// real one.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    pub products: Vec<Product>,
+    pub transactions: Vec<Transaction>,
+    /// Shelf labels reported by barcode and the price charged.
+    pub price_checks: Vec<(String, u32)>,
+}
+
+impl FakeBackend {
+    pub fn product(&self, barcode: &str) -> Option<&Product> {
+        self.products
+            .iter()
+            .find(|product| product.barcode == barcode)
+    }
+
+    /// Adds `product`, replacing the one with the same barcode.
+    pub fn add_product(&mut self, product: Product) {
+        match self
+            .products
+            .iter_mut()
+            .find(|known| known.barcode == product.barcode)
+        {
+            Some(known) => *known = product,
+            None => self.products.push(product),
+        }
+    }
+
+    /// Changes what is on hand of `barcode`, unknown products are ignored.
+    pub fn move_stock(&mut self, barcode: &str, amount: i64) {
+        if let Some(product) = self
+            .products
+            .iter_mut()
+            .find(|product| product.barcode == barcode)
+        {
+            product.stock += amount;
+        }
+    }
+
+    pub fn price_check(&mut self, barcode: String, price: u32) {
+        self.price_checks.push((barcode, price));
+    }
+
+    /// Records `transaction`, taking sold goods off hand. Refunds restock
+    /// through [`FakeBackend::move_stock`] before they are recorded.
+    pub fn transaction(&mut self, transaction: Transaction) {
+        if transaction.kind == Kind::Sale {
+            for item in &transaction.items {
+                let amount = item.amount.parse::<i64>().unwrap_or(0);
+                self.move_stock(&item.barcode, -amount);
+            }
+        }
+        self.transactions.push(transaction);
+    }
+}
+
+/// Handle to a [`FakeBackend`] shared with the tasks calling it. Handles
+/// are equal only when they share the backend.
+#[derive(Clone, Default)]
+pub struct Fake(Arc<Mutex<FakeBackend>>);
+
+impl Fake {
+    /// A backend seeded with a snapshot of the catalogue.
+    pub fn new(products: Vec<Product>) -> Self {
+        Fake(Arc::new(Mutex::new(FakeBackend {
+            products,
+            ..FakeBackend::default()
+        })))
+    }
+
+    pub fn with<T>(&self, f: impl FnOnce(&mut FakeBackend) -> T) -> T {
+        f(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl PartialEq for Fake {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Fake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with(|backend| backend.fmt(f))
+    }
+}
+
+/// Training under way: the backend every call goes to, and the real records
+/// set aside until training ends and whatever was done meanwhile is dropped.
+#[derive(PartialEq, Debug)]
+pub struct Training {
+    pub backend: Fake,
+    pub session: Option<Session>,
+    pub transactions: Vec<Transaction>,
+    pub invoices: Vec<TaxInvoice>,
+    pub customers: Vec<Customer>,
+    pub accounts: Vec<Account>,
+    pub audit: Vec<Entry>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transaction::{PaymentMethod, RefundReason, Tender};
+    use crate::vat::Vat;
+    use crate::Item;
+
+    fn product(barcode: &str, stock: i64) -> Product {
+        Product {
+            barcode: barcode.to_string(),
+            name: "น้ำดื่ม".to_string(),
+            retail: 10,
+            vat: Vat::Inclusive,
+            stock,
+        }
+    }
+
+    fn transaction(kind: Kind, amount: &str) -> Transaction {
+        Transaction {
+            number: 1,
+            kind,
+            items: vec![Item {
+                barcode: "885".to_string(),
+                name: "น้ำดื่ม".to_string(),
+                price: 10,
+                amount: amount.to_string(),
+                sum: 30,
+                vat: Vat::Inclusive,
+            }],
+            discounts: Vec::new(),
+            total: 30,
+            tenders: vec![Tender {
+                method: PaymentMethod::Cash,
+                amount: 30,
+            }],
+            change: 0,
+            customer: None,
+            user: "สมชาย".to_string(),
+            time: None,
+            vat_rate: 7,
+        }
+    }
+
+    #[test]
+    fn sales_and_refunds_move_stock() {
+        let fake = Fake::new(vec![product("885", 10)]);
+        fake.with(|backend| backend.transaction(transaction(Kind::Sale, "3")));
+        assert_eq!(
+            fake.with(|backend| backend.product("885").unwrap().stock),
+            7
+        );
+
+        fake.with(|backend| {
+            backend.move_stock("885", 3);
+            backend.transaction(transaction(
+                Kind::Refund {
+                    receipt: 1,
+                    reason: RefundReason::Damaged,
+                    lines: vec![0],
+                },
+                "3",
+            ));
+        });
+        assert_eq!(
+            fake.with(|backend| backend.product("885").unwrap().stock),
+            10
+        );
+        assert_eq!(fake.with(|backend| backend.transactions.len()), 2);
+    }
+
+    #[test]
+    fn handles_share_one_backend() {
+        let fake = Fake::new(Vec::new());
+        let handle = fake.clone();
+        handle.with(|backend| backend.add_product(product("885", 5)));
+        handle.with(|backend| backend.add_product(product("885", 8)));
+
+        assert_eq!(fake, handle);
+        assert_ne!(fake, Fake::new(Vec::new()));
+        assert_eq!(
+            fake.with(|backend| backend.products.clone()),
+            vec![product("885", 8)]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::promotion::Discount;
+use crate::vat::recorded_rate;
+use crate::Item;
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Transaction {
+    pub number: u32,
+    pub kind: Kind,
+    pub items: Vec<Item>,
+    pub discounts: Vec<Discount>,
+    pub total: u32,
+    pub tenders: Vec<Tender>,
+    pub change: u32,
+    /// Phone number of the member the sale was made to.
+    pub customer: Option<String>,
+    /// Name of the user logged in at the till.
+    pub user: String,
+    /// When it was made, local time. Records kept before this was have none.
+    #[serde(default)]
+    pub time: Option<NaiveDateTime>,
+    /// VAT in percent when it was made, so a later change of rate leaves it be.
+    #[serde(default = "recorded_rate")]
+    pub vat_rate: u64,
+}
+
+impl Transaction {
+    /// Cash that went into (or, for a refund, out of) the drawer.
+    pub fn cash(&self) -> u32 {
+        self.tenders
+            .iter()
+            .filter(|tender| tender.method == PaymentMethod::Cash)
+            .map(|tender| tender.amount)
+            .sum::<u32>()
+            - self.change
+    }
+
+    /// Share of `charged` actually paid once this sale's discounts are spread
+    /// across its lines, so a refund never returns more than was taken.
+    pub fn discounted(&self, charged: u32) -> u32 {
+        let gross: u32 = self.items.iter().map(|item| item.sum).sum();
+        match gross {
+            0 => 0,
+            gross => (charged as u64 * self.total as u64 / gross as u64) as u32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct Tender {
+    pub method: PaymentMethod,
+    pub amount: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub enum PaymentMethod {
+    #[default]
+    Cash,
+    PromptPay,
+    Card,
+    /// Loyalty points, one point to the baht.
+    Points,
+    /// Charged to a credit account, settled later.
+    Credit,
+}
+
+impl PaymentMethod {
+    pub const ALL: [PaymentMethod; 5] = [
+        PaymentMethod::Cash,
+        PaymentMethod::PromptPay,
+        PaymentMethod::Card,
+        PaymentMethod::Points,
+        PaymentMethod::Credit,
+    ];
+}
+
+impl fmt::Display for PaymentMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PaymentMethod::Cash => "เงินสด",
+            PaymentMethod::PromptPay => "พร้อมเพย์",
+            PaymentMethod::Card => "บัตร",
+            PaymentMethod::Points => "แต้มสะสม",
+            PaymentMethod::Credit => "ค้างจ่าย",
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub enum Kind {
+    Sale,
+    /// `lines[i]` is the line of the original receipt that `items[i]` returns.
+    Refund {
+        receipt: u32,
+        reason: RefundReason,
+        lines: Vec<usize>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum RefundReason {
+    Damaged,
+    Expired,
+    WrongItem,
+    ChangedMind,
+    Other,
+}
+
+impl RefundReason {
+    pub const ALL: [RefundReason; 5] = [
+        RefundReason::Damaged,
+        RefundReason::Expired,
+        RefundReason::WrongItem,
+        RefundReason::ChangedMind,
+        RefundReason::Other,
+    ];
+}
+
+impl fmt::Display for RefundReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RefundReason::Damaged => "สินค้าชำรุด",
+            RefundReason::Expired => "สินค้าหมดอายุ",
+            RefundReason::WrongItem => "ขายผิดรายการ",
+            RefundReason::ChangedMind => "ลูกค้าเปลี่ยนใจ",
+            RefundReason::Other => "อื่นๆ",
+        })
+    }
+}
+
+/// Accepts either a typed receipt number or a scanned receipt barcode.
+pub fn parse_receipt(input: &str) -> Option<u32> {
+    let input = input.trim();
+    input
+        .strip_prefix(['R', 'r'])
+        .unwrap_or(input)
+        .parse::<u32>()
+        .ok()
+}
+
+/// Number for the next receipt, after the highest on record.
+pub fn next_receipt(transactions: &[Transaction]) -> u32 {
+    transactions
+        .iter()
+        .map(|transaction| transaction.number)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+pub fn find_sale(transactions: &[Transaction], receipt: u32) -> Option<&Transaction> {
+    transactions
+        .iter()
+        .find(|transaction| transaction.number == receipt && transaction.kind == Kind::Sale)
+}
+
+/// Quantity of each line of `sale` that has not been returned yet.
+pub fn returnable(transactions: &[Transaction], sale: &Transaction) -> Vec<u32> {
+    let mut remaining: Vec<u32> = sale
+        .items
+        .iter()
+        .map(|item| item.amount.parse::<u32>().unwrap_or(0))
+        .collect();
+
+    for transaction in transactions {
+        if let Kind::Refund { receipt, lines, .. } = &transaction.kind {
+            if *receipt != sale.number {
+                continue;
+            }
+            for (line, item) in lines.iter().zip(&transaction.items) {
+                if let Some(amount) = remaining.get_mut(*line) {
+                    *amount = amount.saturating_sub(item.amount.parse::<u32>().unwrap_or(0));
+                }
+            }
+        }
+    }
+    remaining
+}
+
+/// Refunds already made on `sale`.
+fn refunds<'a>(
+    transactions: &'a [Transaction],
+    sale: &'a Transaction,
+) -> impl Iterator<Item = &'a Transaction> {
+    transactions.iter().filter(
+        move |transaction| matches!(transaction.kind, Kind::Refund { receipt, .. } if receipt == sale.number),
+    )
+}
+
+/// Baht already refunded on `sale`.
+pub fn refunded(transactions: &[Transaction], sale: &Transaction) -> u32 {
+    refunds(transactions, sale).map(|refund| refund.total).sum()
+}
+
+/// How `amount` refunded on `sale` goes back: in the mix of tenders it was
+/// paid with, each in proportion, and never more by a tender than the sale
+/// took by it less what earlier refunds gave back.
+pub fn refund_tenders(
+    transactions: &[Transaction],
+    sale: &Transaction,
+    amount: u32,
+) -> Vec<Tender> {
+    let mut paid: Vec<Tender> = Vec::new();
+    for tender in &sale.tenders {
+        match paid.iter_mut().find(|paid| paid.method == tender.method) {
+            Some(paid) => paid.amount += tender.amount,
+            None => paid.push(*tender),
+        }
+    }
+    if let Some(cash) = paid
+        .iter_mut()
+        .find(|paid| paid.method == PaymentMethod::Cash)
+    {
+        cash.amount = cash.amount.saturating_sub(sale.change);
+    }
+    let total: u32 = paid.iter().map(|paid| paid.amount).sum();
+    let left = |method: PaymentMethod, paid: u32| {
+        paid.saturating_sub(
+            refunds(transactions, sale)
+                .flat_map(|refund| &refund.tenders)
+                .filter(|tender| tender.method == method)
+                .map(|tender| tender.amount)
+                .sum(),
+        )
+    };
+
+    let mut tenders: Vec<Tender> = paid
+        .iter()
+        .map(|paid| Tender {
+            method: paid.method,
+            amount: match total {
+                0 => 0,
+                total => (amount as u64 * paid.amount as u64 / total as u64) as u32,
+            }
+            .min(left(paid.method, paid.amount)),
+        })
+        .collect();
+    // What rounding left over goes to whichever tenders still have room
+    let mut rest = amount - tenders.iter().map(|tender| tender.amount).sum::<u32>();
+    for (tender, paid) in tenders.iter_mut().zip(&paid) {
+        let room = left(paid.method, paid.amount) - tender.amount;
+        let more = rest.min(room);
+        tender.amount += more;
+        rest -= more;
+    }
+    tenders.retain(|tender| tender.amount > 0);
+    tenders
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+
+use crate::store::Write;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub enum Role {
+    #[default]
+    Cashier,
+    Supervisor,
+    Manager,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Cashier, Role::Supervisor, Role::Manager];
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        match self {
+            Role::Cashier => false,
+            Role::Supervisor => matches!(
+                permission,
+                Permission::Refund
+                    | Permission::Void
+                    | Permission::Discount
+                    | Permission::PriceOverride
+                    | Permission::Report
+            ),
+            Role::Manager => true,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Cashier => "แคชเชียร์",
+            Role::Supervisor => "หัวหน้ากะ",
+            Role::Manager => "ผู้จัดการ",
+        })
+    }
+}
+
+/// Actions a cashier needs a higher role, or a supervisor's PIN, for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Permission {
+    Stock,
+    Setting,
+    Users,
+    /// Viewing and exporting the audit log.
+    Audit,
+    Report,
+    Refund,
+    Void,
+    /// Manual discounts above the configured limit.
+    Discount,
+    PriceOverride,
+    /// Opening credit accounts and changing their limits.
+    Credit,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+    /// Argon2 hash of the PIN or password, in PHC string format.
+    pub hash: String,
+}
+
+impl User {
+    pub fn new(name: String, role: Role, secret: &str) -> Result<Self, String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(secret.as_bytes(), &salt)
+            .map_err(|error| error.to_string())?
+            .to_string();
+        Ok(User { name, role, hash })
+    }
+
+    pub fn verify(&self, secret: &str) -> bool {
+        PasswordHash::new(&self.hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(secret.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// The user `name` if `secret` is theirs. Hashing is slow, so this and
+/// [`check_pin`] run it on a blocking thread, off the update and the runtime.
+pub async fn login(users: Vec<User>, name: String, secret: String) -> Option<User> {
+    tokio::task::spawn_blocking(move || {
+        users
+            .into_iter()
+            .find(|user| user.name == name)
+            .filter(|user| user.verify(&secret))
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Whether `permission` is granted to the logged-in user by their role.
+pub fn approve(current: Option<&User>, permission: Permission) -> bool {
+    current.is_some_and(|user| user.role.allows(permission))
+}
+
+/// Name of whoever `pin` belongs to, if their role allows `permission`.
+pub async fn check_pin(users: Vec<User>, permission: Permission, pin: String) -> Option<String> {
+    if pin.is_empty() {
+        return None;
+    }
+    tokio::task::spawn_blocking(move || {
+        users
+            .into_iter()
+            .find(|user| user.role.allows(permission) && user.verify(&pin))
+            .map(|user| user.name)
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// The users in `dir`, `None` when there is no file yet and the till is
+/// still to be set up. A file that can't be read or parsed is an error
+/// rather than no users, so it doesn't start a new setup.
+pub fn get_users(dir: &Path) -> Result<Option<Vec<User>>, String> {
+    let path = dir.join("users.json");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(format!("{}: {}", path.display(), error)),
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// `file` queued for `users.json`.
+pub async fn save_users(users: Vec<User>, file: Write) -> Result<(), String> {
+    let users = serde_json::to_vec(&users).map_err(|error| error.to_string())?;
+    file.replace(&users)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hash_and_verify() {
+        let user = User::new("สมชาย".to_string(), Role::Cashier, "1111").unwrap();
+        assert!(user.hash.starts_with("$argon2id$"));
+        assert!(!user.hash.contains("1111"));
+        assert!(user.verify("1111"));
+        assert!(!user.verify("1112"));
+    }
+
+    #[tokio::test]
+    async fn supervisor_pin_approves() {
+        let users = vec![
+            User::new("สมชาย".to_string(), Role::Cashier, "1111").unwrap(),
+            User::new("สมศรี".to_string(), Role::Supervisor, "1234").unwrap(),
+        ];
+        let check = |permission, pin: &str| check_pin(users.clone(), permission, pin.to_string());
+        assert!(!approve(Some(&users[0]), Permission::Refund));
+        assert_eq!(check(Permission::Refund, "").await, None);
+        assert_eq!(check(Permission::Refund, "1111").await, None);
+        assert_eq!(
+            check(Permission::Refund, "1234").await,
+            Some("สมศรี".to_string())
+        );
+        assert_eq!(check(Permission::Setting, "1234").await, None);
+        assert!(approve(Some(&users[1]), Permission::Void));
+    }
+
+    #[test]
+    fn unreadable_users_file_is_not_a_first_run() {
+        let dir = std::env::temp_dir().join(format!("sunminimart-users-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(get_users(&dir), Ok(None));
+
+        std::fs::write(dir.join("users.json"), "[{\"name\": ").unwrap();
+        assert!(get_users(&dir).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Item;
+
+/// Standard Thai VAT in percent, the default for `Setting::vat_rate`.
+pub const VAT_RATE: u64 = 7;
+
+/// Rate taken for records kept before each carried its own.
+pub fn recorded_rate() -> u64 {
+    VAT_RATE
+}
+
+/// How a product's price relates to VAT.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub enum Vat {
+    /// The shelf price already includes VAT.
+    #[default]
+    Inclusive,
+    /// VAT is added on top of the shelf price at the till.
+    Exclusive,
+    Exempt,
+}
+
+impl Vat {
+    pub const ALL: [Vat; 3] = [Vat::Inclusive, Vat::Exclusive, Vat::Exempt];
+
+    /// Amount charged for `amount` units at `price` with VAT at `rate`
+    /// percent, rounded to the baht.
+    pub fn charge(&self, price: u32, amount: u32, rate: u64) -> u32 {
+        let net = price * amount;
+        match self {
+            Vat::Inclusive | Vat::Exempt => net,
+            Vat::Exclusive => {
+                let gross = net as u64 * (100 + rate);
+                ((gross + 50) / 100) as u32
+            }
+        }
+    }
+}
+
+impl fmt::Display for Vat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Vat::Inclusive => "รวมภาษี",
+            Vat::Exclusive => "ไม่รวมภาษี",
+            Vat::Exempt => "ยกเว้นภาษี",
+        })
+    }
+}
+
+/// Split of charged amounts into taxable value, VAT and exempt sales, in satang.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Breakdown {
+    pub taxable: u64,
+    pub vat: u64,
+    pub exempt: u64,
+}
+
+impl Breakdown {
+    pub fn of(items: &[Item], rate: u64) -> Self {
+        items
+            .iter()
+            .fold(Breakdown::default(), |mut breakdown, item| {
+                let charged = item.sum as u64 * 100;
+                match item.vat {
+                    Vat::Inclusive => {
+                        // VAT = charged * rate / (100 + rate), rounded to the satang
+                        let vat = (charged * rate * 2 + 100 + rate) / ((100 + rate) * 2);
+                        breakdown.taxable += charged - vat;
+                        breakdown.vat += vat;
+                    }
+                    Vat::Exclusive => {
+                        let net = item.price as u64 * item.amount.parse::<u64>().unwrap_or(0) * 100;
+                        let net = net.min(charged);
+                        breakdown.taxable += net;
+                        breakdown.vat += charged - net;
+                    }
+                    Vat::Exempt => breakdown.exempt += charged,
+                }
+                breakdown
+            })
+    }
+
+    /// Scales the breakdown down to `total` satang actually charged after
+    /// discounts, spreading the discount across taxable and exempt sales.
+    pub fn scaled_to(self, total: u64) -> Self {
+        let charged = self.taxable + self.vat + self.exempt;
+        if charged == 0 || charged == total {
+            return self;
+        }
+        let gross = self.taxable + self.vat;
+        let exempt = self.exempt * total / charged;
+        let taxed = total - exempt;
+        // VAT = taxed * vat / gross, rounded to the satang
+        let vat = (taxed * self.vat * 2 + gross) / (gross * 2).max(1);
+        Breakdown {
+            taxable: taxed - vat,
+            vat,
+            exempt,
+        }
+    }
+
+    /// Amount before VAT, taxable and exempt together.
+    pub fn base(&self) -> u64 {
+        self.taxable + self.exempt
+    }
+}
+
+/// Formats satang as baht with two decimals.
+pub fn baht(satang: u64) -> String {
+    format!("{}.{:02}", satang / 100, satang % 100)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(price: u32, amount: u32, vat: Vat) -> Item {
+        Item {
+            price,
+            amount: amount.to_string(),
+            sum: vat.charge(price, amount, VAT_RATE),
+            vat,
+            ..Item::default()
+        }
+    }
+
+    #[test]
+    fn charge_exclusive() {
+        assert_eq!(Vat::Exclusive.charge(100, 2, VAT_RATE), 214);
+        assert_eq!(Vat::Exclusive.charge(10, 1, VAT_RATE), 11);
+        assert_eq!(Vat::Inclusive.charge(10, 3, VAT_RATE), 30);
+    }
+
+    #[test]
+    fn breakdown_mixed() {
+        let breakdown = Breakdown::of(
+            &[
+                item(107, 1, Vat::Inclusive),
+                item(100, 1, Vat::Exclusive),
+                item(50, 2, Vat::Exempt),
+            ],
+            VAT_RATE,
+        );
+        assert_eq!(breakdown.taxable, 20000);
+        assert_eq!(breakdown.vat, 1400);
+        assert_eq!(breakdown.exempt, 10000);
+        assert_eq!(breakdown.base() + breakdown.vat, (107 + 107 + 100) * 100);
+    }
+
+    #[test]
+    fn breakdown_scaled_to_discount() {
+        let breakdown = Breakdown::of(
+            &[item(107, 1, Vat::Inclusive), item(107, 1, Vat::Exempt)],
+            VAT_RATE,
+        )
+        .scaled_to(10700);
+        assert_eq!(breakdown.exempt, 5350);
+        assert_eq!(breakdown.vat, 350);
+        assert_eq!(breakdown.taxable, 5000);
+    }
+}